@@ -5,9 +5,10 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use bitflags::bitflags;
+use knuffel::errors::DecodeError;
 use miette::{miette, Context, IntoDiagnostic, NarratableReportHandler};
 use niri_ipc::{LayoutSwitchTarget, SizeChange};
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use smithay::input::keyboard::keysyms::KEY_NoSymbol;
 use smithay::input::keyboard::xkb::{keysym_from_name, KEYSYM_CASE_INSENSITIVE};
 use smithay::input::keyboard::{Keysym, XkbConfig};
@@ -25,8 +26,18 @@ pub struct Config {
     pub layout: Layout,
     #[knuffel(child, default)]
     pub prefer_no_csd: bool,
+    #[knuffel(child, unwrap(argument), default)]
+    pub activation_focus: ActivationFocus,
+    #[knuffel(child, unwrap(argument), default)]
+    pub maximize_to: MaximizeTarget,
+    #[knuffel(child, unwrap(argument), default)]
+    pub fullscreen_grace_period_ms: u16,
+    #[knuffel(child)]
+    pub fullscreen_on_empty_workspace: bool,
     #[knuffel(child, default)]
     pub cursor: Cursor,
+    #[knuffel(child, default)]
+    pub popups: Popups,
     #[knuffel(
         child,
         unwrap(argument),
@@ -39,6 +50,8 @@ pub struct Config {
     pub hotkey_overlay: HotkeyOverlay,
     #[knuffel(child, default)]
     pub animations: Animations,
+    #[knuffel(children(name = "workspace"))]
+    pub workspaces: Vec<Workspace>,
     #[knuffel(children(name = "window-rule"))]
     pub window_rules: Vec<WindowRule>,
     #[knuffel(child, default)]
@@ -62,6 +75,8 @@ pub struct Input {
     pub tablet: Tablet,
     #[knuffel(child)]
     pub disable_power_key_handling: bool,
+    #[knuffel(child)]
+    pub focus_follows_mouse: bool,
 }
 
 #[derive(knuffel::Decode, Debug, Default, PartialEq, Eq)]
@@ -115,6 +130,48 @@ pub enum CenterFocusedColumn {
     OnOverflow,
 }
 
+#[derive(knuffel::DecodeScalar, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ActivationFocus {
+    /// Windows requesting activation are always focused.
+    #[default]
+    Always,
+    /// Windows requesting activation are never focused.
+    Never,
+    /// Windows requesting activation are focused only if the request comes from the app that is
+    /// currently focused.
+    SameApp,
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ColumnPosition {
+    /// New columns go right after the focused column.
+    #[default]
+    AfterFocused,
+    /// New columns go right before the focused column.
+    BeforeFocused,
+    /// New columns go to the start of the workspace.
+    Start,
+    /// New columns go to the end of the workspace.
+    End,
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum MaximizeTarget {
+    /// Maximized windows fill the width of their column.
+    #[default]
+    Column,
+    /// Maximized windows float above the columns and fill the working area of the monitor.
+    Monitor,
+}
+
+#[derive(knuffel::DecodeScalar, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecorationMode {
+    /// The window leaves the decorations to niri.
+    ServerSide,
+    /// The window draws its own decorations.
+    ClientSide,
+}
+
 #[derive(knuffel::DecodeScalar, Debug, Default, PartialEq, Eq)]
 pub enum TrackLayout {
     /// The layout change is global.
@@ -213,6 +270,8 @@ pub struct Output {
     pub position: Option<Position>,
     #[knuffel(child, unwrap(argument, str))]
     pub mode: Option<Mode>,
+    #[knuffel(child)]
+    pub default_column_width: Option<DefaultColumnWidth>,
 }
 
 impl Default for Output {
@@ -224,6 +283,7 @@ impl Default for Output {
             transform: Transform::Normal,
             position: None,
             mode: None,
+            default_column_width: None,
         }
     }
 }
@@ -300,6 +360,8 @@ pub struct Layout {
     pub border: Border,
     #[knuffel(child, unwrap(children), default)]
     pub preset_column_widths: Vec<PresetWidth>,
+    #[knuffel(child, unwrap(children), default)]
+    pub named_column_widths: Vec<NamedWidth>,
     #[knuffel(child)]
     pub default_column_width: Option<DefaultColumnWidth>,
     #[knuffel(child, unwrap(argument), default)]
@@ -308,6 +370,12 @@ pub struct Layout {
     pub gaps: u16,
     #[knuffel(child, default)]
     pub struts: Struts,
+    #[knuffel(child, unwrap(argument))]
+    pub max_columns: Option<u16>,
+    #[knuffel(child, unwrap(argument))]
+    pub max_windows_per_column: Option<u16>,
+    #[knuffel(child)]
+    pub snap_to_preset_column_widths: bool,
 }
 
 impl Default for Layout {
@@ -316,10 +384,14 @@ impl Default for Layout {
             focus_ring: Default::default(),
             border: Default::default(),
             preset_column_widths: Default::default(),
+            named_column_widths: Default::default(),
             default_column_width: Default::default(),
             center_focused_column: Default::default(),
             gaps: 16,
             struts: Default::default(),
+            max_columns: None,
+            max_windows_per_column: None,
+            snap_to_preset_column_widths: false,
         }
     }
 }
@@ -340,6 +412,8 @@ pub struct FocusRing {
     pub active_color: Color,
     #[knuffel(child, default = Self::default().inactive_color)]
     pub inactive_color: Color,
+    #[knuffel(child, default = Self::default().urgent_color)]
+    pub urgent_color: Color,
 }
 
 impl Default for FocusRing {
@@ -349,6 +423,7 @@ impl Default for FocusRing {
             width: 4,
             active_color: Color::new(127, 200, 255, 255),
             inactive_color: Color::new(80, 80, 80, 255),
+            urgent_color: Color::new(155, 0, 0, 255),
         }
     }
 }
@@ -363,6 +438,8 @@ pub struct Border {
     pub active_color: Color,
     #[knuffel(child, default = Self::default().inactive_color)]
     pub inactive_color: Color,
+    #[knuffel(child, default = Self::default().urgent_color)]
+    pub urgent_color: Color,
 }
 
 impl Default for Border {
@@ -372,6 +449,7 @@ impl Default for Border {
             width: 4,
             active_color: Color::new(255, 200, 127, 255),
             inactive_color: Color::new(80, 80, 80, 255),
+            urgent_color: Color::new(155, 0, 0, 255),
         }
     }
 }
@@ -383,6 +461,7 @@ impl From<Border> for FocusRing {
             width: value.width,
             active_color: value.active_color,
             inactive_color: value.inactive_color,
+            urgent_color: value.urgent_color,
         }
     }
 }
@@ -429,6 +508,38 @@ impl Default for Cursor {
     }
 }
 
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Popups {
+    #[knuffel(child, unwrap(argument), default = Self::default().padding)]
+    pub padding: u16,
+    #[knuffel(child, unwrap(argument))]
+    pub window_padding: Option<u16>,
+    #[knuffel(child, unwrap(argument))]
+    pub layer_shell_padding: Option<u16>,
+}
+
+impl Default for Popups {
+    fn default() -> Self {
+        Self {
+            padding: 8,
+            window_padding: None,
+            layer_shell_padding: None,
+        }
+    }
+}
+
+impl Popups {
+    /// Padding to keep between window popups and the edges of their target area.
+    pub fn window_padding(&self) -> u16 {
+        self.window_padding.unwrap_or(self.padding)
+    }
+
+    /// Padding to keep between layer-shell popups and the edges of the output.
+    pub fn layer_shell_padding(&self) -> u16 {
+        self.layer_shell_padding.unwrap_or(self.padding)
+    }
+}
+
 #[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq)]
 pub enum PresetWidth {
     Proportion(#[knuffel(argument)] f64),
@@ -438,6 +549,43 @@ pub enum PresetWidth {
 #[derive(knuffel::Decode, Debug, Clone, PartialEq)]
 pub struct DefaultColumnWidth(#[knuffel(children)] pub Vec<PresetWidth>);
 
+#[derive(knuffel::Decode, Debug, Clone, PartialEq)]
+pub struct OutputColumnWidth {
+    #[knuffel(argument)]
+    pub output: String,
+    #[knuffel(children)]
+    pub width: Vec<PresetWidth>,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, PartialEq)]
+pub struct NamedWidth {
+    #[knuffel(node_name)]
+    pub name: String,
+    #[knuffel(children)]
+    pub width: Vec<PresetWidth>,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetHeight {
+    Fixed(#[knuffel(argument)] i32),
+}
+
+#[derive(knuffel::Decode, Debug, Clone, PartialEq)]
+pub struct DefaultWindowHeight(#[knuffel(children)] pub Vec<PresetHeight>);
+
+#[derive(knuffel::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatingPosition {
+    /// Centered in the working area of the output.
+    Center,
+    /// At a fixed location in logical pixels from the top-left corner of the output.
+    Fixed(#[knuffel(argument)] i32, #[knuffel(argument)] i32),
+    /// With the top-left corner at the cursor.
+    Cursor,
+}
+
+#[derive(knuffel::Decode, Debug, Clone, PartialEq)]
+pub struct DefaultFloatingPosition(#[knuffel(children)] pub Vec<FloatingPosition>);
+
 #[derive(knuffel::Decode, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Struts {
     #[knuffel(child, unwrap(argument), default)]
@@ -539,31 +687,285 @@ pub enum AnimationCurve {
     EaseOutExpo,
 }
 
+#[derive(knuffel::Decode, Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    #[knuffel(argument)]
+    pub name: String,
+}
+
 #[derive(knuffel::Decode, Debug, Default, Clone, PartialEq)]
 pub struct WindowRule {
     #[knuffel(children(name = "match"))]
     pub matches: Vec<Match>,
     #[knuffel(children(name = "exclude"))]
     pub excludes: Vec<Match>,
+    #[knuffel(child, unwrap(argument))]
+    pub priority: Option<i32>,
 
     #[knuffel(child)]
     pub default_column_width: Option<DefaultColumnWidth>,
     #[knuffel(child, unwrap(argument))]
-    pub open_on_output: Option<String>,
+    pub default_column_width_preset: Option<String>,
+    #[knuffel(children(name = "default-column-width-on-output"))]
+    pub default_column_width_on_output: Vec<OutputColumnWidth>,
+    #[knuffel(child, unwrap(argument))]
+    pub max_auto_width: Option<u16>,
+    #[knuffel(child)]
+    pub default_window_height: Option<DefaultWindowHeight>,
+    #[knuffel(child, unwrap(arguments))]
+    pub open_on_output: Option<Vec<String>>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_on_workspace: Option<String>,
+    #[knuffel(child, unwrap(argument))]
+    pub reapply_open_on_output: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_maximized: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_fullscreen: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_floating: Option<bool>,
+    #[knuffel(child)]
+    pub default_floating_position: Option<DefaultFloatingPosition>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_as_tab: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_below_parent: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_replacing_focused: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_in_scratchpad: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub open_focused: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub focus_on_open_across_workspace: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub default_column_position: Option<ColumnPosition>,
+    #[knuffel(child, unwrap(argument))]
+    pub max_columns: Option<u16>,
+    #[knuffel(child, unwrap(argument))]
+    pub max_windows_per_column: Option<u16>,
+    #[knuffel(child, unwrap(argument))]
+    pub mark: Option<String>,
+    #[knuffel(child, unwrap(argument))]
+    pub tiled_state: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub prefer_csd: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub force_decoration: Option<DecorationMode>,
+    #[knuffel(child, unwrap(argument))]
+    pub activation_focus: Option<ActivationFocus>,
+    #[knuffel(child, unwrap(argument))]
+    pub maximize_to: Option<MaximizeTarget>,
+    #[knuffel(child, unwrap(argument))]
+    pub color_scheme: Option<ColorScheme>,
+    #[knuffel(child, unwrap(argument))]
+    pub block_out_from: Option<BlockOutMode>,
+    #[knuffel(child, unwrap(argument))]
+    pub opacity: Option<f32>,
+    #[knuffel(child)]
+    pub border_active_color: Option<Color>,
+    #[knuffel(child)]
+    pub border_inactive_color: Option<Color>,
+    #[knuffel(child, unwrap(argument))]
+    pub min_width: Option<u16>,
+    #[knuffel(child, unwrap(argument))]
+    pub max_width: Option<u16>,
+    #[knuffel(child, unwrap(argument))]
+    pub scale_override: Option<f64>,
+    #[knuffel(child, unwrap(argument))]
+    pub always_on_top: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub focus_follows_mouse: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub popup_pointer_grab: Option<bool>,
+    #[knuffel(child, unwrap(argument))]
+    pub aspect_ratio: Option<f64>,
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    /// The window should prefer a light color scheme.
+    Light,
+    /// The window should prefer a dark color scheme.
+    Dark,
+}
+
+#[derive(knuffel::DecodeScalar, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOutMode {
+    /// Block out from screencasts only.
+    Screencast,
+    /// Block out from all screen captures, including screencasts and screenshots.
+    ScreenCapture,
 }
 
-#[derive(knuffel::Decode, Debug, Default, Clone)]
+/// Window properties to match.
+///
+/// `app_id` and `title` hold alternatives: any one of them matching is enough. They can be set
+/// with a property for a single regex, or with a child node listing several regexes as arguments.
+///
+/// `app_id_not` and `title_not` are set the same way, but none of them may match.
+#[derive(Debug, Clone)]
 pub struct Match {
-    #[knuffel(property, str)]
-    pub app_id: Option<Regex>,
-    #[knuffel(property, str)]
-    pub title: Option<Regex>,
+    pub app_id: Vec<Regex>,
+    pub title: Vec<Regex>,
+    pub app_id_not: Vec<Regex>,
+    pub title_not: Vec<Regex>,
+    pub initial_title: Option<Regex>,
+    pub pid: Option<i32>,
+    pub on_output: Option<String>,
+    pub has_parent: Option<bool>,
+    pub is_fixed_size: Option<bool>,
+    pub case_sensitive: bool,
+}
+
+impl Default for Match {
+    fn default() -> Self {
+        Self {
+            app_id: Vec::new(),
+            title: Vec::new(),
+            app_id_not: Vec::new(),
+            title_not: Vec::new(),
+            initial_title: None,
+            pid: None,
+            on_output: None,
+            has_parent: None,
+            is_fixed_size: None,
+            case_sensitive: true,
+        }
+    }
 }
 
 impl PartialEq for Match {
     fn eq(&self, other: &Self) -> bool {
-        self.app_id.as_ref().map(Regex::as_str) == other.app_id.as_ref().map(Regex::as_str)
-            && self.title.as_ref().map(Regex::as_str) == other.title.as_ref().map(Regex::as_str)
+        fn as_strs(res: &[Regex]) -> Vec<&str> {
+            res.iter().map(Regex::as_str).collect()
+        }
+
+        as_strs(&self.app_id) == as_strs(&other.app_id)
+            && as_strs(&self.title) == as_strs(&other.title)
+            && as_strs(&self.app_id_not) == as_strs(&other.app_id_not)
+            && as_strs(&self.title_not) == as_strs(&other.title_not)
+            && self.initial_title.as_ref().map(Regex::as_str)
+                == other.initial_title.as_ref().map(Regex::as_str)
+            && self.pid == other.pid
+            && self.on_output == other.on_output
+            && self.has_parent == other.has_parent
+            && self.is_fixed_size == other.is_fixed_size
+            && self.case_sensitive == other.case_sensitive
+    }
+}
+
+impl Match {
+    /// Recompiles the regular expressions to ignore case if `case_sensitive` is unset.
+    fn apply_case_sensitivity(&mut self) {
+        if self.case_sensitive {
+            return;
+        }
+
+        for re in self
+            .app_id
+            .iter_mut()
+            .chain(&mut self.title)
+            .chain(&mut self.app_id_not)
+            .chain(&mut self.title_not)
+            .chain(&mut self.initial_title)
+        {
+            // The pattern already compiled once, so it will compile again.
+            *re = RegexBuilder::new(re.as_str())
+                .case_insensitive(true)
+                .build()
+                .unwrap();
+        }
+    }
+}
+
+fn decode_regex<S>(
+    value: &knuffel::ast::Value<S>,
+    ctx: &mut knuffel::decode::Context<S>,
+) -> Result<Regex, DecodeError<S>>
+where
+    S: knuffel::traits::ErrorSpan,
+{
+    let pattern: String = knuffel::traits::DecodeScalar::decode(value, ctx)?;
+    Regex::new(&pattern).map_err(|err| DecodeError::conversion(&value.literal, err))
+}
+
+impl<S> knuffel::Decode<S> for Match
+where
+    S: knuffel::traits::ErrorSpan,
+{
+    fn decode_node(
+        node: &knuffel::ast::SpannedNode<S>,
+        ctx: &mut knuffel::decode::Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        if let Some(type_name) = &node.type_name {
+            ctx.emit_error(DecodeError::unexpected(
+                type_name,
+                "type name",
+                "no type name expected for this node",
+            ));
+        }
+
+        for value in &node.arguments {
+            ctx.emit_error(DecodeError::unexpected(
+                &value.literal,
+                "argument",
+                "no arguments expected for this node",
+            ));
+        }
+
+        let mut rv = Self::default();
+
+        for (name, value) in &node.properties {
+            match &***name {
+                "app-id" => rv.app_id.push(decode_regex(value, ctx)?),
+                "title" => rv.title.push(decode_regex(value, ctx)?),
+                "app-id-not" => rv.app_id_not.push(decode_regex(value, ctx)?),
+                "title-not" => rv.title_not.push(decode_regex(value, ctx)?),
+                "initial-title" => rv.initial_title = Some(decode_regex(value, ctx)?),
+                "pid" => rv.pid = Some(knuffel::traits::DecodeScalar::decode(value, ctx)?),
+                "on-output" => {
+                    rv.on_output = Some(knuffel::traits::DecodeScalar::decode(value, ctx)?);
+                }
+                "has-parent" => {
+                    rv.has_parent = Some(knuffel::traits::DecodeScalar::decode(value, ctx)?);
+                }
+                "is-fixed-size" => {
+                    rv.is_fixed_size = Some(knuffel::traits::DecodeScalar::decode(value, ctx)?);
+                }
+                "case-sensitive" => {
+                    rv.case_sensitive = knuffel::traits::DecodeScalar::decode(value, ctx)?;
+                }
+                name_str => ctx.emit_error(DecodeError::unexpected(
+                    name,
+                    "property",
+                    format!("unexpected property `{}`", name_str.escape_default()),
+                )),
+            }
+        }
+
+        for child in node.children() {
+            let list = match &**child.node_name {
+                "app-id" => &mut rv.app_id,
+                "title" => &mut rv.title,
+                "app-id-not" => &mut rv.app_id_not,
+                "title-not" => &mut rv.title_not,
+                name_str => {
+                    ctx.emit_error(DecodeError::unexpected(
+                        child,
+                        "node",
+                        format!("unexpected node `{}`", name_str.escape_default()),
+                    ));
+                    continue;
+                }
+            };
+
+            for value in &child.arguments {
+                list.push(decode_regex(value, ctx)?);
+            }
+        }
+
+        Ok(rv)
     }
 }
 
@@ -604,6 +1006,7 @@ pub enum Action {
     Suspend,
     PowerOffMonitors,
     ToggleDebugTint,
+    ReleaseGrabs,
     Spawn(#[knuffel(arguments)] Vec<String>),
     #[knuffel(skip)]
     ConfirmScreenshot,
@@ -614,6 +1017,7 @@ pub enum Action {
     ScreenshotWindow,
     CloseWindow,
     FullscreenWindow,
+    ToggleWindowAlwaysOnTop,
     FocusColumnLeft,
     FocusColumnRight,
     FocusColumnFirst,
@@ -635,6 +1039,12 @@ pub enum Action {
     ConsumeWindowIntoColumn,
     ExpelWindowFromColumn,
     CenterColumn,
+    ToggleColumnTabbedDisplay,
+    FocusTabNext,
+    FocusTabPrevious,
+    ToggleScratchpad,
+    MarkWindow(#[knuffel(argument)] String),
+    FocusMarkedWindow(#[knuffel(argument)] String),
     FocusWorkspaceDown,
     FocusWorkspaceUp,
     FocusWorkspace(#[knuffel(argument)] u8),
@@ -681,6 +1091,7 @@ impl From<niri_ipc::Action> for Action {
             niri_ipc::Action::ScreenshotWindow => Self::ScreenshotWindow,
             niri_ipc::Action::CloseWindow => Self::CloseWindow,
             niri_ipc::Action::FullscreenWindow => Self::FullscreenWindow,
+            niri_ipc::Action::ToggleWindowAlwaysOnTop => Self::ToggleWindowAlwaysOnTop,
             niri_ipc::Action::FocusColumnLeft => Self::FocusColumnLeft,
             niri_ipc::Action::FocusColumnRight => Self::FocusColumnRight,
             niri_ipc::Action::FocusColumnFirst => Self::FocusColumnFirst,
@@ -704,6 +1115,12 @@ impl From<niri_ipc::Action> for Action {
             niri_ipc::Action::ConsumeWindowIntoColumn => Self::ConsumeWindowIntoColumn,
             niri_ipc::Action::ExpelWindowFromColumn => Self::ExpelWindowFromColumn,
             niri_ipc::Action::CenterColumn => Self::CenterColumn,
+            niri_ipc::Action::ToggleColumnTabbedDisplay => Self::ToggleColumnTabbedDisplay,
+            niri_ipc::Action::FocusTabNext => Self::FocusTabNext,
+            niri_ipc::Action::FocusTabPrevious => Self::FocusTabPrevious,
+            niri_ipc::Action::ToggleScratchpad => Self::ToggleScratchpad,
+            niri_ipc::Action::MarkWindow { mark } => Self::MarkWindow(mark),
+            niri_ipc::Action::FocusMarkedWindow { mark } => Self::FocusMarkedWindow(mark),
             niri_ipc::Action::FocusWorkspaceDown => Self::FocusWorkspaceDown,
             niri_ipc::Action::FocusWorkspaceUp => Self::FocusWorkspaceUp,
             niri_ipc::Action::FocusWorkspace { index } => Self::FocusWorkspace(index),
@@ -738,6 +1155,7 @@ impl From<niri_ipc::Action> for Action {
             niri_ipc::Action::MoveWorkspaceToMonitorDown => Self::MoveWorkspaceToMonitorDown,
             niri_ipc::Action::MoveWorkspaceToMonitorUp => Self::MoveWorkspaceToMonitorUp,
             niri_ipc::Action::ToggleDebugTint => Self::ToggleDebugTint,
+            niri_ipc::Action::ReleaseGrabs => Self::ReleaseGrabs,
         }
     }
 }
@@ -756,6 +1174,8 @@ pub struct DebugConfig {
     pub disable_cursor_plane: bool,
     #[knuffel(child, unwrap(argument))]
     pub render_drm_device: Option<PathBuf>,
+    #[knuffel(child)]
+    pub log_window_rules: bool,
 }
 
 impl Config {
@@ -776,7 +1196,15 @@ impl Config {
 
     pub fn parse(filename: &str, text: &str) -> Result<Self, knuffel::Error> {
         let _span = tracy_client::span!("Config::parse");
-        knuffel::parse(filename, text)
+        let mut config: Self = knuffel::parse(filename, text)?;
+
+        for rule in &mut config.window_rules {
+            for m in rule.matches.iter_mut().chain(&mut rule.excludes) {
+                m.apply_case_sensitivity();
+            }
+        }
+
+        Ok(config)
     }
 }
 
@@ -947,6 +1375,7 @@ mod tests {
                 }
 
                 disable-power-key-handling
+                focus-follows-mouse
             }
 
             output "eDP-1" {
@@ -954,6 +1383,7 @@ mod tests {
                 transform "flipped-90"
                 position x=10 y=20
                 mode "1920x1080@144"
+                default-column-width { proportion 0.25; }
             }
 
             layout {
@@ -961,6 +1391,7 @@ mod tests {
                     width 5
                     active-color 0 100 200 255
                     inactive-color 255 200 100 0
+                    urgent-color 255 0 0 255
                 }
 
                 border {
@@ -975,6 +1406,11 @@ mod tests {
                     fixed 1280
                 }
 
+                named-column-widths {
+                    narrow { proportion 0.25; }
+                    wide { fixed 1600; }
+                }
+
                 default-column-width { proportion 0.25; }
 
                 gaps 8
@@ -986,17 +1422,35 @@ mod tests {
                 }
 
                 center-focused-column "on-overflow"
+
+                max-columns 5
+                max-windows-per-column 4
+
+                snap-to-preset-column-widths
             }
 
             spawn-at-startup "alacritty" "-e" "fish"
 
             prefer-no-csd
 
+            activation-focus "same-app"
+
+            maximize-to "monitor"
+
+            fullscreen-grace-period-ms 100
+
+            fullscreen-on-empty-workspace
+
             cursor {
                 xcursor-theme "breeze_cursors"
                 xcursor-size 16
             }
 
+            popups {
+                padding 4
+                layer-shell-padding 0
+            }
+
             screenshot-path "~/Screenshots/screenshot.png"
 
             hotkey-overlay {
@@ -1014,11 +1468,58 @@ mod tests {
                 }
             }
 
+            workspace "comms"
+
             window-rule {
-                match app-id=".*alacritty"
+                match app-id=".*alacritty" title-not="^Settings$"
+                match app-id="FIREFOX" initial-title="^Mozilla Firefox$" case-sensitive=false
+                match title="Meet" {
+                    app-id "^chromium$" "^brave$"
+                }
                 exclude title="~"
-
-                open-on-output "eDP-1"
+                exclude pid=1234
+                exclude on-output="HDMI-A-1"
+                exclude has-parent=true
+                exclude is-fixed-size=false
+                priority 10
+
+                default-column-width-preset "narrow"
+                default-column-width-on-output "eDP-1" { fixed 800; }
+                max-auto-width 600
+                default-window-height { fixed 500; }
+                open-on-output "HDMI-A-1" "eDP-1"
+                open-on-workspace "comms"
+                reapply-open-on-output true
+                open-maximized true
+                open-fullscreen false
+                open-floating true
+                default-floating-position { fixed 100 200; }
+                open-as-tab true
+                open-below-parent true
+                open-replacing-focused true
+                open-in-scratchpad true
+                open-focused false
+                focus-on-open-across-workspace true
+                default-column-position "end"
+                max-columns 3
+                max-windows-per-column 1
+                mark "editor"
+                tiled-state false
+                prefer-csd true
+                force-decoration "server-side"
+                activation-focus "never"
+                maximize-to "column"
+                color-scheme "light"
+                block-out-from "screencast"
+                opacity 0.9
+                border-active-color 255 0 0 255
+                min-width 300
+                max-width 800
+                scale-override 1.0
+                always-on-top true
+                focus-follows-mouse false
+                popup-pointer-grab false
+                aspect-ratio 1.5
             }
 
             binds {
@@ -1070,6 +1571,7 @@ mod tests {
                         map_to_output: Some("eDP-1".to_owned()),
                     },
                     disable_power_key_handling: true,
+                    focus_follows_mouse: true,
                 },
                 outputs: vec![Output {
                     off: false,
@@ -1082,6 +1584,9 @@ mod tests {
                         height: 1080,
                         refresh: Some(144.),
                     }),
+                    default_column_width: Some(DefaultColumnWidth(vec![PresetWidth::Proportion(
+                        0.25,
+                    )])),
                 }],
                 layout: Layout {
                     focus_ring: FocusRing {
@@ -1099,6 +1604,12 @@ mod tests {
                             b: 100,
                             a: 0,
                         },
+                        urgent_color: Color {
+                            r: 255,
+                            g: 0,
+                            b: 0,
+                            a: 255,
+                        },
                     },
                     border: Border {
                         off: false,
@@ -1115,6 +1626,12 @@ mod tests {
                             b: 100,
                             a: 0,
                         },
+                        urgent_color: Color {
+                            r: 155,
+                            g: 0,
+                            b: 0,
+                            a: 255,
+                        },
                     },
                     preset_column_widths: vec![
                         PresetWidth::Proportion(0.25),
@@ -1122,6 +1639,16 @@ mod tests {
                         PresetWidth::Fixed(960),
                         PresetWidth::Fixed(1280),
                     ],
+                    named_column_widths: vec![
+                        NamedWidth {
+                            name: "narrow".to_owned(),
+                            width: vec![PresetWidth::Proportion(0.25)],
+                        },
+                        NamedWidth {
+                            name: "wide".to_owned(),
+                            width: vec![PresetWidth::Fixed(1600)],
+                        },
+                    ],
                     default_column_width: Some(DefaultColumnWidth(vec![PresetWidth::Proportion(
                         0.25,
                     )])),
@@ -1133,15 +1660,27 @@ mod tests {
                         bottom: 0,
                     },
                     center_focused_column: CenterFocusedColumn::OnOverflow,
+                    max_columns: Some(5),
+                    max_windows_per_column: Some(4),
+                    snap_to_preset_column_widths: true,
                 },
                 spawn_at_startup: vec![SpawnAtStartup {
                     command: vec!["alacritty".to_owned(), "-e".to_owned(), "fish".to_owned()],
                 }],
                 prefer_no_csd: true,
+                activation_focus: ActivationFocus::SameApp,
+                maximize_to: MaximizeTarget::Monitor,
+                fullscreen_grace_period_ms: 100,
+                fullscreen_on_empty_workspace: true,
                 cursor: Cursor {
                     xcursor_theme: String::from("breeze_cursors"),
                     xcursor_size: 16,
                 },
+                popups: Popups {
+                    padding: 4,
+                    window_padding: None,
+                    layer_shell_padding: Some(0),
+                },
                 screenshot_path: Some(String::from("~/Screenshots/screenshot.png")),
                 hotkey_overlay: HotkeyOverlay {
                     skip_at_startup: true,
@@ -1159,16 +1698,158 @@ mod tests {
                     },
                     ..Default::default()
                 },
+                workspaces: vec![Workspace {
+                    name: "comms".to_owned(),
+                }],
                 window_rules: vec![WindowRule {
-                    matches: vec![Match {
-                        app_id: Some(Regex::new(".*alacritty").unwrap()),
-                        title: None,
-                    }],
-                    excludes: vec![Match {
-                        app_id: None,
-                        title: Some(Regex::new("~").unwrap()),
+                    matches: vec![
+                        Match {
+                            app_id: vec![Regex::new(".*alacritty").unwrap()],
+                            title: vec![],
+                            app_id_not: vec![],
+                            title_not: vec![Regex::new("^Settings$").unwrap()],
+                            initial_title: None,
+                            pid: None,
+                            on_output: None,
+                            has_parent: None,
+                            is_fixed_size: None,
+                            case_sensitive: true,
+                        },
+                        Match {
+                            app_id: vec![Regex::new("FIREFOX").unwrap()],
+                            title: vec![],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: Some(Regex::new("^Mozilla Firefox$").unwrap()),
+                            pid: None,
+                            on_output: None,
+                            has_parent: None,
+                            is_fixed_size: None,
+                            case_sensitive: false,
+                        },
+                        Match {
+                            app_id: vec![
+                                Regex::new("^chromium$").unwrap(),
+                                Regex::new("^brave$").unwrap(),
+                            ],
+                            title: vec![Regex::new("Meet").unwrap()],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: None,
+                            pid: None,
+                            on_output: None,
+                            has_parent: None,
+                            is_fixed_size: None,
+                            case_sensitive: true,
+                        },
+                    ],
+                    excludes: vec![
+                        Match {
+                            app_id: vec![],
+                            title: vec![Regex::new("~").unwrap()],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: None,
+                            pid: None,
+                            on_output: None,
+                            has_parent: None,
+                            is_fixed_size: None,
+                            case_sensitive: true,
+                        },
+                        Match {
+                            app_id: vec![],
+                            title: vec![],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: None,
+                            pid: Some(1234),
+                            on_output: None,
+                            has_parent: None,
+                            is_fixed_size: None,
+                            case_sensitive: true,
+                        },
+                        Match {
+                            app_id: vec![],
+                            title: vec![],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: None,
+                            pid: None,
+                            on_output: Some("HDMI-A-1".to_owned()),
+                            has_parent: None,
+                            is_fixed_size: None,
+                            case_sensitive: true,
+                        },
+                        Match {
+                            app_id: vec![],
+                            title: vec![],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: None,
+                            pid: None,
+                            on_output: None,
+                            has_parent: Some(true),
+                            is_fixed_size: None,
+                            case_sensitive: true,
+                        },
+                        Match {
+                            app_id: vec![],
+                            title: vec![],
+                            app_id_not: vec![],
+                            title_not: vec![],
+                            initial_title: None,
+                            pid: None,
+                            on_output: None,
+                            has_parent: None,
+                            is_fixed_size: Some(false),
+                            case_sensitive: true,
+                        },
+                    ],
+                    priority: Some(10),
+                    default_column_width_preset: Some("narrow".to_owned()),
+                    default_column_width_on_output: vec![OutputColumnWidth {
+                        output: "eDP-1".to_owned(),
+                        width: vec![PresetWidth::Fixed(800)],
                     }],
-                    open_on_output: Some("eDP-1".to_owned()),
+                    max_auto_width: Some(600),
+                    default_window_height: Some(DefaultWindowHeight(vec![PresetHeight::Fixed(
+                        500,
+                    )])),
+                    open_on_output: Some(vec!["HDMI-A-1".to_owned(), "eDP-1".to_owned()]),
+                    open_on_workspace: Some("comms".to_owned()),
+                    reapply_open_on_output: Some(true),
+                    open_maximized: Some(true),
+                    open_fullscreen: Some(false),
+                    open_floating: Some(true),
+                    default_floating_position: Some(DefaultFloatingPosition(vec![
+                        FloatingPosition::Fixed(100, 200),
+                    ])),
+                    open_as_tab: Some(true),
+                    open_below_parent: Some(true),
+                    open_replacing_focused: Some(true),
+                    open_in_scratchpad: Some(true),
+                    open_focused: Some(false),
+                    focus_on_open_across_workspace: Some(true),
+                    default_column_position: Some(ColumnPosition::End),
+                    max_columns: Some(3),
+                    max_windows_per_column: Some(1),
+                    mark: Some("editor".to_owned()),
+                    tiled_state: Some(false),
+                    prefer_csd: Some(true),
+                    force_decoration: Some(DecorationMode::ServerSide),
+                    activation_focus: Some(ActivationFocus::Never),
+                    maximize_to: Some(MaximizeTarget::Column),
+                    color_scheme: Some(ColorScheme::Light),
+                    block_out_from: Some(BlockOutMode::Screencast),
+                    opacity: Some(0.9),
+                    border_active_color: Some(Color::new(255, 0, 0, 255)),
+                    min_width: Some(300),
+                    max_width: Some(800),
+                    scale_override: Some(1.),
+                    always_on_top: Some(true),
+                    focus_follows_mouse: Some(false),
+                    popup_pointer_grab: Some(false),
+                    aspect_ratio: Some(1.5),
                     ..Default::default()
                 }],
                 binds: Binds(vec![
@@ -16,11 +16,24 @@ pub enum Request {
     Outputs,
     /// Perform an action.
     Action(Action),
+    /// Dry-run the window rules against the focused window.
+    FocusedWindowRules,
+    /// Request information about windows that requested attention.
+    UrgentWindows,
+    /// Ask all windows matching the given properties to close.
+    CloseWindows(WindowMatch),
+    /// Start receiving a stream of events.
+    ///
+    /// After the `Handled` reply, niri keeps the connection open and writes an [`Event`] per
+    /// line as things happen.
+    EventStream,
+    /// Request information about the active popup grab, for debugging stuck input.
+    PopupGrab,
 }
 
 /// Reply from niri to client.
 ///
-/// Every request gets one reply.
+/// Every request gets one reply. Replies end with a newline.
 ///
 /// * If an error had occurred, it will be an `Reply::Err`.
 /// * If the request does not need any particular response, it will be
@@ -37,6 +50,18 @@ pub enum Response {
     ///
     /// Map from connector name to output info.
     Outputs(HashMap<String, Output>),
+    /// Window rules matching the focused window.
+    ///
+    /// `None` if no window is focused.
+    FocusedWindowRules(Option<WindowRules>),
+    /// Windows that requested attention and have not been focused since.
+    UrgentWindows(Vec<Window>),
+    /// Number of windows that were asked to close.
+    ClosedWindows(usize),
+    /// Information about the active popup grab.
+    ///
+    /// `None` if there's no popup grab.
+    PopupGrab(Option<PopupGrab>),
 }
 
 /// Actions that niri can perform.
@@ -71,6 +96,8 @@ pub enum Action {
     CloseWindow,
     /// Toggle fullscreen on the focused window.
     FullscreenWindow,
+    /// Toggle whether the focused window stays above other floating windows.
+    ToggleWindowAlwaysOnTop,
     /// Focus the column to the left.
     FocusColumnLeft,
     /// Focus the column to the right.
@@ -113,6 +140,28 @@ pub enum Action {
     ExpelWindowFromColumn,
     /// Center the focused column on the screen.
     CenterColumn,
+    /// Toggle between showing the windows of the focused column stacked or as tabs.
+    ToggleColumnTabbedDisplay,
+    /// Focus the next window in the focused column, wrapping around.
+    FocusTabNext,
+    /// Focus the previous window in the focused column, wrapping around.
+    FocusTabPrevious,
+    /// Show the most recently hidden scratchpad window, or hide the focused scratchpad window.
+    ToggleScratchpad,
+    /// Mark the focused window with a key to find it later.
+    ///
+    /// A window previously marked with the same key loses the mark.
+    MarkWindow {
+        /// Key to mark the window with.
+        #[cfg_attr(feature = "clap", arg())]
+        mark: String,
+    },
+    /// Focus the window marked with a key.
+    FocusMarkedWindow {
+        /// Key that the window was marked with.
+        #[cfg_attr(feature = "clap", arg())]
+        mark: String,
+    },
     /// Focus the workspace below.
     FocusWorkspaceDown,
     /// Focus the workspace above.
@@ -205,6 +254,10 @@ pub enum Action {
     MoveWorkspaceToMonitorUp,
     /// Toggle a debug tint on windows.
     ToggleDebugTint,
+    /// Release the popup grab along with any keyboard and pointer grabs.
+    ///
+    /// A way out when input seems stuck on a menu that is no longer there.
+    ReleaseGrabs,
 }
 
 /// Change in window or column size.
@@ -248,6 +301,182 @@ pub struct Output {
     pub current_mode: Option<usize>,
 }
 
+/// Toplevel window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Window {
+    /// Title of the window, if set.
+    pub title: Option<String>,
+    /// Application ID of the window, if set.
+    pub app_id: Option<String>,
+    /// Name of the output the window is on, if any.
+    pub output: Option<String>,
+}
+
+/// Event from the event stream.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Event {
+    /// A window was opened.
+    WindowOpened(WindowInfo),
+    /// The title or the application ID of an open window changed.
+    WindowChanged(WindowInfo),
+    /// A window was closed.
+    WindowClosed {
+        /// ID of the closed window.
+        id: u64,
+    },
+}
+
+/// Window as reported in the event stream.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WindowInfo {
+    /// Unique ID of the window.
+    ///
+    /// Stays the same for as long as the window exists, even if it is closed and opened again.
+    pub id: u64,
+    /// Title of the window, if set.
+    pub title: Option<String>,
+    /// Application ID of the window, if set.
+    pub app_id: Option<String>,
+    /// Unique ID of the workspace the window is on, if any.
+    pub workspace_id: Option<u64>,
+}
+
+/// Popup grab, which keeps input on the popups of a surface, like an open menu.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PopupGrab {
+    /// Title of the window that the popups belong to, if set.
+    pub root_title: Option<String>,
+    /// Application ID of the window that the popups belong to, if set.
+    pub root_app_id: Option<String>,
+    /// Namespace of the layer surface that the popups belong to, if they belong to one.
+    pub root_layer_namespace: Option<String>,
+    /// Serial of the input event that started the grab.
+    pub serial: u32,
+    /// Serial of the grab that this one continues, for nested popups.
+    pub previous_serial: Option<u32>,
+    /// Whether the grab has ended, but niri hasn't cleaned it up yet.
+    pub has_ended: bool,
+}
+
+/// Properties to match windows against.
+///
+/// Both properties are regular expressions, same as in window rule matches. A window must match
+/// all properties that are set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WindowMatch {
+    /// Regular expression for the application ID.
+    pub app_id: Option<String>,
+    /// Regular expression for the title.
+    pub title: Option<String>,
+}
+
+/// Window rules matching a window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WindowRules {
+    /// Title of the window, if set.
+    pub title: Option<String>,
+    /// Application ID of the window, if set.
+    pub app_id: Option<String>,
+    /// Indices of the matching window rules, in the order they apply.
+    ///
+    /// This is the config order, except that rules with a higher priority apply later.
+    pub matched: Vec<usize>,
+    /// Window rule properties resolved for the window.
+    pub resolved: ResolvedWindowRule,
+}
+
+/// Window rule properties resolved for a window.
+///
+/// Properties that none of the matching window rules set are `None`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResolvedWindowRule {
+    /// Default width of the window's column.
+    pub default_column_width: Option<RuleSize>,
+    /// Maximum width of the window's column when the window chooses its own width.
+    pub max_auto_width: Option<i32>,
+    /// Default height of the window.
+    pub default_window_height: Option<RuleSize>,
+    /// Outputs to open the window on, in order of preference.
+    pub open_on_output: Option<Vec<String>>,
+    /// Named workspace to open the window on.
+    pub open_on_workspace: Option<String>,
+    /// Whether the window moves to its open-on-output when it changes its app ID.
+    pub reapply_open_on_output: Option<bool>,
+    /// Whether the window opens maximized.
+    pub open_maximized: Option<bool>,
+    /// Whether the window opens fullscreen.
+    pub open_fullscreen: Option<bool>,
+    /// Whether the window opens floating.
+    pub open_floating: Option<bool>,
+    /// Where the window appears if it opens floating, as written in the config.
+    pub default_floating_position: Option<String>,
+    /// Whether the window opens as a new tab in the active column.
+    pub open_as_tab: Option<bool>,
+    /// Whether the window opens in the column of its parent window, right below it.
+    pub open_below_parent: Option<bool>,
+    /// Whether the window takes the place of the focused window, closing it.
+    pub open_replacing_focused: Option<bool>,
+    /// Whether the window opens hidden in the scratchpad.
+    pub open_in_scratchpad: Option<bool>,
+    /// Whether the window takes focus when it opens.
+    pub open_focused: Option<bool>,
+    /// Whether niri switches to the workspace and output where the window opens.
+    pub focus_on_open_across_workspace: Option<bool>,
+    /// Where in the workspace the column of the window goes, as written in the config.
+    pub default_column_position: Option<String>,
+    /// Column count past which the window opens on an empty workspace instead.
+    pub max_columns: Option<u16>,
+    /// Maximum number of windows stacked in the column of the window.
+    pub max_windows_per_column: Option<u16>,
+    /// Key that the window is marked with when it opens.
+    pub mark: Option<String>,
+    /// Whether the window is told that it's tiled on all sides.
+    pub tiled_state: Option<bool>,
+    /// Whether the window draws its own decorations.
+    pub prefer_csd: Option<bool>,
+    /// Decoration mode that the window gets regardless of its request, as written in the config.
+    pub force_decoration: Option<String>,
+    /// Focus policy for activation requests, as written in the config.
+    pub activation_focus: Option<String>,
+    /// What maximizing the window does, as written in the config.
+    pub maximize_to: Option<String>,
+    /// Color scheme hinted to the window, as written in the config.
+    pub color_scheme: Option<String>,
+    /// Screen captures that the window is blocked out from, as written in the config.
+    pub block_out_from: Option<String>,
+    /// Opacity of the window.
+    pub opacity: Option<f32>,
+    /// Border color of the window when active, as RGBA.
+    pub border_active_color: Option<[u8; 4]>,
+    /// Border color of the window when inactive, as RGBA.
+    pub border_inactive_color: Option<[u8; 4]>,
+    /// Minimum width of the window in logical pixels.
+    pub min_width: Option<i32>,
+    /// Maximum width of the window in logical pixels.
+    pub max_width: Option<i32>,
+    /// Scale sent to the window as preferred instead of the output scale.
+    pub scale_override: Option<f64>,
+    /// Whether the window stays above other floating windows.
+    pub always_on_top: Option<bool>,
+    /// Whether the window gets focused when the pointer moves onto it.
+    pub focus_follows_mouse: Option<bool>,
+    /// Whether popup grabs of the window also grab the pointer.
+    pub popup_pointer_grab: Option<bool>,
+    /// Width-to-height ratio kept for the window when the layout sizes it.
+    pub aspect_ratio: Option<f64>,
+}
+
+/// Size set by a window rule.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum RuleSize {
+    /// The window picks its own size.
+    WindowChosen,
+    /// Proportion of the working area.
+    Proportion(f64),
+    /// Fixed size in logical pixels.
+    Fixed(i32),
+}
+
 /// Output mode.
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct Mode {
@@ -52,4 +52,23 @@ pub enum Msg {
         #[command(subcommand)]
         action: Action,
     },
+    /// Show which window rules match the focused window.
+    FocusedWindowRules,
+    /// List windows that requested attention.
+    UrgentWindows,
+    /// Ask all windows matching an app ID and/or a title to close.
+    CloseWindows {
+        /// Regular expression for the app ID.
+        #[arg(long)]
+        app_id: Option<String>,
+        /// Regular expression for the title.
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Print window events as they happen.
+    EventStream,
+    /// Show the active popup grab, for debugging stuck input.
+    ///
+    /// Use the release-grabs action to end a grab that got stuck.
+    PopupGrab,
 }
@@ -8,17 +8,16 @@ use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{Client, Resource};
 use smithay::wayland::buffer::BufferHandler;
 use smithay::wayland::compositor::{
-    add_blocker, add_pre_commit_hook, get_parent, is_sync_subsurface, send_surface_state,
-    with_states, BufferAssignment, CompositorClientState, CompositorHandler, CompositorState,
-    SurfaceAttributes,
+    add_blocker, add_pre_commit_hook, get_parent, is_sync_subsurface, with_states,
+    BufferAssignment, CompositorClientState, CompositorHandler, CompositorState, SurfaceAttributes,
 };
 use smithay::wayland::dmabuf::get_dmabuf;
 use smithay::wayland::shm::{ShmHandler, ShmState};
 use smithay::{delegate_compositor, delegate_shm};
 
-use super::xdg_shell::{initial_configure_sent, resolve_window_rules};
+use super::xdg_shell::initial_configure_sent;
 use crate::niri::{ClientState, State};
-use crate::utils::clone2;
+use crate::utils::{clone2, send_scale_transform};
 
 impl CompositorHandler for State {
     fn compositor_state(&mut self) -> &mut CompositorState {
@@ -36,10 +35,10 @@ impl CompositorHandler for State {
         }
 
         if let Some(output) = self.niri.output_for_root(&root) {
-            let scale = output.current_scale().integer_scale();
+            let scale = output.current_scale();
             let transform = output.current_transform();
             with_states(surface, |data| {
-                send_surface_state(surface, data, scale, transform);
+                send_scale_transform(surface, data, scale, transform);
             });
         }
     }
@@ -108,40 +107,7 @@ impl CompositorHandler for State {
                     let window = entry.remove();
                     window.on_commit();
 
-                    let parent = window
-                        .toplevel()
-                        .parent()
-                        .and_then(|parent| self.niri.layout.find_window_and_output(&parent))
-                        .map(|(win, _)| win.clone());
-
-                    let (width, output) = {
-                        let config = self.niri.config.borrow();
-                        let rules = resolve_window_rules(&config.window_rules, window.toplevel());
-                        let output = rules
-                            .open_on_output
-                            .and_then(|name| self.niri.output_by_name.get(name))
-                            .cloned();
-                        (rules.default_width, output)
-                    };
-
-                    let win = window.clone();
-
-                    // Open dialogs immediately to the right of their parent window.
-                    let output = if let Some(p) = parent {
-                        self.niri.layout.add_window_right_of(&p, win, width, false)
-                    } else if let Some(output) = &output {
-                        self.niri
-                            .layout
-                            .add_window_on_output(output, win, width, false);
-                        Some(output)
-                    } else {
-                        self.niri.layout.add_window(win, width, false)
-                    };
-
-                    if let Some(output) = output.cloned() {
-                        self.niri.layout.start_open_animation_for_window(&window);
-                        self.niri.queue_redraw(output);
-                    }
+                    self.map_window(window);
                     return;
                 }
 
@@ -177,22 +143,51 @@ impl CompositorHandler for State {
 
                 if !is_mapped {
                     // The toplevel got unmapped.
+                    self.ipc_window_closed(&window);
                     self.niri.layout.remove_window(&window);
                     self.niri.unmapped_windows.insert(surface.clone(), window);
                     self.niri.queue_redraw(output);
                     return;
                 }
 
-                // The toplevel remains mapped.
+                // The toplevel remains mapped. Title and app ID changes come with a commit, so
+                // apply window rules matching on them right away.
+                self.refresh_window_rules_on_commit(&window, &output);
+                self.ipc_window_changed(&window);
+
                 self.niri.layout.update_window(&window);
 
                 // Popup placement depends on window size which might have changed.
-                self.update_reactive_popups(&window, &output);
+                self.update_reactive_popups(&window);
 
                 self.niri.queue_redraw(output);
                 return;
             }
 
+            // This is a commit of a toplevel hidden in the scratchpad.
+            let scratchpad = &mut self.niri.scratchpad;
+            if let Some(idx) = scratchpad
+                .iter()
+                .position(|win| win.toplevel().wl_surface() == surface)
+            {
+                scratchpad[idx].on_commit();
+
+                let is_mapped =
+                    with_renderer_surface_state(surface, |state| state.buffer().is_some())
+                        .unwrap_or_else(|| {
+                            error!("no renderer surface state even though we use commit handler");
+                            false
+                        });
+
+                if !is_mapped {
+                    // The toplevel got unmapped.
+                    let window = scratchpad.remove(idx);
+                    self.ipc_window_closed(&window);
+                    self.niri.unmapped_windows.insert(surface.clone(), window);
+                }
+                return;
+            }
+
             // This is a commit of a non-toplevel root.
         }
 
@@ -209,7 +204,7 @@ impl CompositorHandler for State {
         self.popups_handle_commit(surface);
         if let Some(popup) = self.niri.popups.find_popup(surface) {
             if let Some(output) = self.output_for_popup(&popup) {
-                self.niri.queue_redraw(output.clone());
+                self.niri.queue_redraw(output);
             }
         }
 
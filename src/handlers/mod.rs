@@ -7,6 +7,7 @@ use std::io::Write;
 use std::os::fd::OwnedFd;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::drm::DrmNode;
@@ -21,11 +22,12 @@ use smithay::reexports::wayland_server::protocol::wl_output::WlOutput;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Resource;
 use smithay::utils::{Logical, Rectangle, Size};
-use smithay::wayland::compositor::{send_surface_state, with_states};
+use smithay::wayland::compositor::{get_parent, with_states};
 use smithay::wayland::dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier};
 use smithay::wayland::drm_lease::{
     DrmLease, DrmLeaseBuilder, DrmLeaseHandler, DrmLeaseRequest, DrmLeaseState, LeaseRejected,
 };
+use smithay::wayland::fractional_scale::FractionalScaleHandler;
 use smithay::wayland::idle_inhibit::IdleInhibitHandler;
 use smithay::wayland::idle_notify::{IdleNotifierHandler, IdleNotifierState};
 use smithay::wayland::input_method::{InputMethodHandler, PopupSurface};
@@ -46,13 +48,17 @@ use smithay::wayland::selection::{SelectionHandler, SelectionTarget};
 use smithay::wayland::session_lock::{
     LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
 };
+use smithay::wayland::xdg_activation::{
+    XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+};
 use smithay::{
     delegate_cursor_shape, delegate_data_control, delegate_data_device, delegate_dmabuf,
-    delegate_drm_lease, delegate_idle_inhibit, delegate_idle_notify, delegate_input_method_manager,
-    delegate_output, delegate_pointer_constraints, delegate_pointer_gestures,
-    delegate_presentation, delegate_primary_selection, delegate_relative_pointer, delegate_seat,
-    delegate_security_context, delegate_session_lock, delegate_tablet_manager,
-    delegate_text_input_manager, delegate_virtual_keyboard_manager,
+    delegate_drm_lease, delegate_fractional_scale, delegate_idle_inhibit, delegate_idle_notify,
+    delegate_input_method_manager, delegate_output, delegate_pointer_constraints,
+    delegate_pointer_gestures, delegate_presentation, delegate_primary_selection,
+    delegate_relative_pointer, delegate_seat, delegate_security_context, delegate_session_lock,
+    delegate_tablet_manager, delegate_text_input_manager, delegate_virtual_keyboard_manager,
+    delegate_xdg_activation,
 };
 
 use crate::delegate_foreign_toplevel;
@@ -60,7 +66,7 @@ use crate::niri::{ClientState, State};
 use crate::protocols::foreign_toplevel::{
     self, ForeignToplevelHandler, ForeignToplevelManagerState,
 };
-use crate::utils::output_size;
+use crate::utils::{output_size, send_scale_transform};
 
 impl SeatHandler for State {
     type KeyboardFocus = WlSurface;
@@ -121,14 +127,7 @@ delegate_pointer_constraints!(State);
 impl InputMethodHandler for State {
     fn new_popup(&mut self, surface: PopupSurface) {
         let popup = PopupKind::from(surface.clone());
-        if let Some(output) = self.output_for_popup(&popup) {
-            let scale = output.current_scale().integer_scale();
-            let transform = output.current_transform();
-            let wl_surface = surface.wl_surface();
-            with_states(wl_surface, |data| {
-                send_surface_state(wl_surface, data, scale, transform);
-            });
-        }
+        self.send_popup_surface_state(&popup);
         if let Err(err) = self.niri.popups.track_popup(popup) {
             warn!("error tracking ime popup {err:?}");
         }
@@ -252,6 +251,7 @@ impl SessionLockHandler for State {
 
     fn lock(&mut self, confirmation: SessionLocker) {
         self.niri.lock(confirmation);
+        self.ungrab_popups();
     }
 
     fn unlock(&mut self) {
@@ -275,11 +275,11 @@ pub fn configure_lock_surface(surface: &LockSurface, output: &Output) {
         let size = output_size(output);
         states.size = Some(Size::from((size.w as u32, size.h as u32)));
     });
-    let scale = output.current_scale().integer_scale();
+    let scale = output.current_scale();
     let transform = output.current_transform();
     let wl_surface = surface.wl_surface();
     with_states(wl_surface, |data| {
-        send_surface_state(wl_surface, data, scale, transform);
+        send_scale_transform(wl_surface, data, scale, transform);
     });
     surface.send_configure();
 }
@@ -379,6 +379,76 @@ impl ForeignToplevelHandler for State {
 }
 delegate_foreign_toplevel!(State);
 
+/// How long an activation token stays valid after it was created.
+const XDG_ACTIVATION_TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl XdgActivationHandler for State {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.niri.activation_state
+    }
+
+    fn request_activation(
+        &mut self,
+        token: XdgActivationToken,
+        token_data: XdgActivationTokenData,
+        surface: WlSurface,
+    ) {
+        self.niri.activation_state.remove_token(&token);
+
+        let Some((window, _)) = self.niri.layout.find_window_and_output(&surface) else {
+            return;
+        };
+        let window = window.clone();
+
+        if token_data.timestamp.elapsed() >= XDG_ACTIVATION_TOKEN_TIMEOUT {
+            debug!("activation token expired, marking the window urgent instead");
+            self.mark_urgent(&window);
+            return;
+        }
+
+        if !self.should_focus_on_activation(&window, &token_data) {
+            debug!("activation denied by activation-focus, marking the window urgent instead");
+            self.mark_urgent(&window);
+            return;
+        }
+
+        self.niri.layout.activate_window(&window);
+        self.niri.queue_redraw_all();
+    }
+}
+delegate_xdg_activation!(State);
+
+impl FractionalScaleHandler for State {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+
+        // Windows go through the layout element to pick up their scale window rule.
+        if let Some((window, output)) = self.niri.layout.find_window_and_output(&root) {
+            let scale = output.current_scale();
+            window.set_preferred_scale_transform(scale, output.current_transform());
+            return;
+        }
+
+        let output = match self.niri.popups.find_popup(&root) {
+            Some(popup) => self.output_for_popup(&popup),
+            None => self.niri.output_for_root(&root).cloned(),
+        };
+        let Some(output) = output else {
+            return;
+        };
+
+        let scale = output.current_scale();
+        let transform = output.current_transform();
+        with_states(&surface, |data| {
+            send_scale_transform(&surface, data, scale, transform);
+        });
+    }
+}
+delegate_fractional_scale!(State);
+
 impl DrmLeaseHandler for State {
     fn drm_lease_state(&mut self, node: DrmNode) -> &mut DrmLeaseState {
         &mut self
@@ -1,77 +1,146 @@
-use niri_config::{Match, WindowRule};
+use std::cell::{Cell, RefCell};
+use std::cmp::{max, min};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use _server_decoration::server::org_kde_kwin_server_decoration::OrgKdeKwinServerDecoration;
+use _server_decoration::server::org_kde_kwin_server_decoration_manager::Mode as KdeDecorationsMode;
+use niri_config::{
+    ActivationFocus, Config, DecorationMode, FloatingPosition, Match, MaximizeTarget,
+};
+use smithay::desktop::utils::{bbox_from_surface_tree, output_update};
 use smithay::desktop::{
     find_popup_root_surface, get_popup_toplevel_coords, layer_map_for_output, LayerSurface,
     PopupKeyboardGrab, PopupKind, PopupManager, PopupPointerGrab, PopupUngrabStrategy, Window,
     WindowSurfaceType,
 };
-use smithay::input::pointer::Focus;
+use smithay::input::pointer::{CursorImageStatus, Focus};
 use smithay::output::Output;
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay::reexports::calloop::RegistrationToken;
 use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1;
-use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_positioner::ConstraintAdjustment;
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_positioner::{
+    Anchor, ConstraintAdjustment, Gravity,
+};
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::{self, ResizeEdge};
+use smithay::reexports::wayland_protocols_misc::server_decoration as _server_decoration;
 use smithay::reexports::wayland_server::protocol::wl_output;
 use smithay::reexports::wayland_server::protocol::wl_seat::WlSeat;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
-use smithay::utils::{Logical, Rectangle, Serial};
-use smithay::wayland::compositor::{send_surface_state, with_states};
+use smithay::reexports::wayland_server::{DisplayHandle, Resource, WEnum};
+use smithay::utils::{Logical, Point, Rectangle, Serial, Size};
+use smithay::wayland::compositor::{get_parent, with_states};
 use smithay::wayland::input_method::InputMethodSeat;
 use smithay::wayland::shell::kde::decoration::{KdeDecorationHandler, KdeDecorationState};
 use smithay::wayland::shell::wlr_layer::Layer;
 use smithay::wayland::shell::xdg::decoration::XdgDecorationHandler;
 use smithay::wayland::shell::xdg::{
     PopupSurface, PositionerState, ToplevelSurface, XdgPopupSurfaceData, XdgShellHandler,
-    XdgShellState, XdgToplevelSurfaceData, XdgToplevelSurfaceRoleAttributes,
+    XdgShellState, XdgToplevelSurfaceData,
 };
+use smithay::wayland::xdg_activation::XdgActivationTokenData;
 use smithay::{delegate_kde_decoration, delegate_xdg_decoration, delegate_xdg_shell};
 
-use crate::layout::workspace::ColumnWidth;
-use crate::niri::{PopupGrabState, State};
-use crate::utils::clone2;
-
-#[derive(Debug, Default)]
-pub struct ResolvedWindowRule<'a> {
-    /// Default width for this window.
-    ///
-    /// - `None`: unset.
-    /// - `Some(None)`: set to empty.
-    /// - `Some(Some(width))`: set to a particular width.
-    pub default_width: Option<Option<ColumnWidth>>,
+use crate::input::resize_grab::{resize_cursor_icon, ResizeGrab};
+use crate::layout::workspace::{ColumnWidth, Workspace, WorkspaceId};
+use crate::layout::LayoutElement;
+use crate::niri::{
+    push_popup_grab, take_popup_grabs_of_root, AlwaysOnTopOverride, PopupGrabState,
+    ScratchpadWindow, State, WindowBlockOut, WindowMapTime, WindowRulesState, WindowUrgency,
+};
+use crate::utils::{clone2, get_monotonic_time, send_scale_transform};
+use crate::window::rules::{
+    matching_window_rules, resolve_window_rules, store_current_output, store_initial_title,
+    title_and_app_id, window_matches, ResolvedWindowRule, WindowRuleSubject,
+};
 
-    /// Output to open this window on.
-    pub open_on_output: Option<&'a str>,
+/// Location of a window before a fullscreen request moved it to another output.
+///
+/// Stored in the window user data.
+#[derive(Default)]
+struct PreFullscreenLocation(RefCell<Option<FullscreenMove>>);
+
+struct FullscreenMove {
+    /// Output and workspace that the window was on before going fullscreen.
+    from: (Output, WorkspaceId),
+    /// Output and workspace that the window was moved to for fullscreen.
+    to: (Output, WorkspaceId),
 }
 
-fn window_matches(role: &XdgToplevelSurfaceRoleAttributes, m: &Match) -> bool {
-    if let Some(app_id_re) = &m.app_id {
-        let Some(app_id) = &role.app_id else {
-            return false;
-        };
-        if !app_id_re.is_match(app_id) {
-            return false;
-        }
-    }
+/// Fullscreen request held back during the fullscreen grace period.
+///
+/// Stored in the window user data together with the timer that will fulfill it.
+#[derive(Default)]
+struct PendingFullscreen(RefCell<Option<(RegistrationToken, Option<wl_output::WlOutput>)>>);
+
+/// Output that the popups of a window were last positioned for.
+///
+/// Stored in the window user data.
+#[derive(Default)]
+struct PopupsOutput(RefCell<Option<Output>>);
+
+/// Whether an unmapped window was configured while there was no workspace to size it for.
+///
+/// Stored in the window user data.
+#[derive(Default)]
+struct WaitingForOutput(Cell<bool>);
+
+/// Workspace that the popups of a window were last positioned for.
+///
+/// Stored in the window user data.
+#[derive(Default)]
+struct PopupsWorkspace(Cell<Option<WorkspaceId>>);
+
+/// Layer geometry and output size that the popups of a layer surface were last positioned for.
+///
+/// Stored in the layer surface user data.
+#[derive(Default)]
+struct PopupsLayerGeometry(Cell<Option<(Rectangle<i32, Logical>, Size<i32, Logical>)>>);
+
+/// Identity of a window in the IPC event stream.
+///
+/// Stored in the window user data.
+struct IpcWindow {
+    id: u64,
+    /// Title and app ID last reported to event stream clients, while the window is open.
+    reported: RefCell<Option<(Option<String>, Option<String>)>>,
+}
 
-    if let Some(title_re) = &m.title {
-        let Some(title) = &role.title else {
-            return false;
-        };
-        if !title_re.is_match(title) {
-            return false;
+impl Default for IpcWindow {
+    fn default() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self {
+            id: COUNTER.fetch_add(1, Ordering::Relaxed),
+            reported: RefCell::new(None),
         }
     }
-
-    true
 }
 
-pub fn resolve_window_rules<'a>(
-    rules: &'a [WindowRule],
-    toplevel: &ToplevelSurface,
-) -> ResolvedWindowRule<'a> {
-    let _span = tracy_client::span!("resolve_window_rules");
-
-    let mut resolved = ResolvedWindowRule::default();
+/// Outcome of re-resolving the window rules of a window that changed.
+struct ReResolved {
+    /// Whether the app ID differs from the one the rules were previously resolved for.
+    app_id_changed: bool,
+}
 
-    with_states(toplevel.wl_surface(), |states| {
+/// Re-resolves and applies the window rules if the window changed since the last time.
+///
+/// The title, app ID, parent and output of the window are cached in its [`WindowRulesState`], so
+/// this is cheap to call often. Returns `None` if nothing changed.
+fn re_resolve_window_rules(
+    config: &Config,
+    window: &Window,
+    output: Option<&Output>,
+    display_handle: &DisplayHandle,
+) -> Option<ReResolved> {
+    let _span = tracy_client::span!("re_resolve_window_rules");
+
+    // Window rules can match on the output, so moving the window re-resolves them.
+    let output_changed = store_current_output(window.toplevel(), output);
+
+    let state = window.user_data().get_or_insert(WindowRulesState::default);
+    let mut resolved_for = state.resolved_for.borrow_mut();
+
+    let current = with_states(window.toplevel().wl_surface(), |states| {
         let role = states
             .data_map
             .get::<XdgToplevelSurfaceData>()
@@ -79,30 +148,30 @@ pub fn resolve_window_rules<'a>(
             .lock()
             .unwrap();
 
-        for rule in rules {
-            if !(rule.matches.is_empty() || rule.matches.iter().any(|m| window_matches(&role, m))) {
-                continue;
-            }
-
-            if rule.excludes.iter().any(|m| window_matches(&role, m)) {
-                continue;
-            }
-
-            if let Some(x) = rule
-                .default_column_width
-                .as_ref()
-                .map(|d| d.0.first().copied().map(ColumnWidth::from))
+        let has_parent = role.parent.is_some();
+        if let Some((title, app_id, had_parent)) = &*resolved_for {
+            if !output_changed
+                && *title == role.title
+                && *app_id == role.app_id
+                && *had_parent == has_parent
             {
-                resolved.default_width = Some(x);
-            }
-
-            if let Some(x) = rule.open_on_output.as_deref() {
-                resolved.open_on_output = Some(x);
+                return None;
             }
         }
+
+        Some((role.title.clone(), role.app_id.clone(), has_parent))
     });
 
-    resolved
+    let current = current?;
+    let app_id_changed = resolved_for
+        .as_ref()
+        .is_some_and(|(_, app_id, _)| *app_id != current.1);
+    *resolved_for = Some(current);
+    drop(resolved_for);
+
+    let rules = resolve_window_rules(config, window.toplevel(), display_handle);
+    apply_window_rules(window, &rules);
+    Some(ReResolved { app_id_changed })
 }
 
 impl XdgShellHandler for State {
@@ -133,12 +202,55 @@ impl XdgShellHandler for State {
 
     fn resize_request(
         &mut self,
-        _surface: ToplevelSurface,
+        surface: ToplevelSurface,
         _seat: WlSeat,
-        _serial: Serial,
-        _edges: ResizeEdge,
+        serial: Serial,
+        edges: ResizeEdge,
     ) {
-        // FIXME
+        let pointer = self.niri.seat.get_pointer().unwrap();
+        if !pointer.has_grab(serial) {
+            return;
+        }
+
+        let Some(start_data) = pointer.grab_start_data() else {
+            return;
+        };
+
+        // The grab must have been started by a click on this client.
+        let Some((focus, _)) = &start_data.focus else {
+            return;
+        };
+        if !focus.id().same_client_as(&surface.wl_surface().id()) {
+            return;
+        }
+
+        let Some((window, _)) = self
+            .niri
+            .layout
+            .find_window_and_output(surface.wl_surface())
+        else {
+            return;
+        };
+        let window = window.clone();
+
+        if !self.niri.layout.interactive_resize_begin(&window, edges) {
+            return;
+        }
+
+        surface.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Resizing);
+        });
+
+        let grab = ResizeGrab::new(start_data, window);
+        pointer.set_grab(self, grab, serial, Focus::Clear);
+
+        // Nothing has pointer focus during the grab, so show the resize cursor ourselves.
+        let icon = resize_cursor_icon(edges);
+        self.niri
+            .cursor_manager
+            .set_cursor_image(CursorImageStatus::Named(icon));
+        // FIXME: granular.
+        self.niri.queue_redraw_all();
     }
 
     fn reposition_request(
@@ -157,25 +269,34 @@ impl XdgShellHandler for State {
     }
 
     fn grab(&mut self, surface: PopupSurface, _seat: WlSeat, serial: Serial) {
+        let popup = PopupKind::Xdg(surface);
+        let Ok(root) = find_popup_root_surface(&popup) else {
+            return;
+        };
+
         // HACK: ignore grabs (pretend they work without actually grabbing) if the input method has
         // a grab. It will likely need refactors in Smithay to support properly since grabs just
-        // replace each other.
+        // replace each other. Without the grab, clicks outside of the popups won't dismiss them,
+        // so remember the root to do that manually.
         // FIXME: do this properly.
         if self.niri.seat.input_method().keyboard_grabbed() {
             trace!("ignoring popup grab because IME has keyboard grabbed");
+            self.niri.popup_grab_blocked_by_ime = Some(root);
             return;
         }
 
-        let popup = PopupKind::Xdg(surface);
-        let Ok(root) = find_popup_root_surface(&popup) else {
-            return;
-        };
-
         // We need to hand out the grab in a way consistent with what update_keyboard_focus()
         // thinks the current focus is, otherwise it will desync and cause weird issues with
         // keyboard focus being at the wrong place.
         if self.niri.is_locked() {
-            if Some(&root) != self.niri.lock_surface_focus().as_ref() {
+            // Each output has its own lock surface, so check the one where the popup is rather
+            // than the one under the cursor.
+            let output = self.output_for_popup(&popup);
+            let lock_surface = output.and_then(|output| {
+                let state = self.niri.output_state.get(&output)?;
+                state.lock_surface.as_ref().map(|s| s.wl_surface().clone())
+            });
+            if Some(&root) != lock_surface.as_ref() {
                 let _ = PopupManager::dismiss_popup(&root, &popup);
                 return;
             }
@@ -234,12 +355,23 @@ impl XdgShellHandler for State {
         let keyboard = seat.get_keyboard().unwrap();
         let pointer = seat.get_pointer().unwrap();
 
+        // Window rules can leave the pointer out of the grab, so that clicks go through to other
+        // surfaces while the popup holds the keyboard.
+        let grab_pointer = self
+            .niri
+            .layout
+            .find_window_and_output(&root)
+            .and_then(|(window, _)| window.user_data().get::<WindowRulesState>())
+            .and_then(|state| state.popup_pointer_grab.get())
+            .unwrap_or(true);
+
         let keyboard_grab_mismatches = keyboard.is_grabbed()
             && !(keyboard.has_grab(serial)
                 || grab
                     .previous_serial()
                     .map_or(true, |s| keyboard.has_grab(s)));
-        let pointer_grab_mismatches = pointer.is_grabbed()
+        let pointer_grab_mismatches = grab_pointer
+            && pointer.is_grabbed()
             && !(pointer.has_grab(serial)
                 || grab.previous_serial().map_or(true, |s| pointer.has_grab(s)));
         if keyboard_grab_mismatches || pointer_grab_mismatches {
@@ -250,20 +382,78 @@ impl XdgShellHandler for State {
         trace!("new grab for root {:?}", root);
         keyboard.set_focus(self, grab.current_grab(), serial);
         keyboard.set_grab(PopupKeyboardGrab::new(&grab), serial);
-        pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
-        self.niri.popup_grab = Some(PopupGrabState { root, grab });
+        if grab_pointer {
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
+
+        // A grab of another root, for example through a keyboard-only grab that let the pointer
+        // reach other surfaces, nests on top of the active one. The active grab comes back once
+        // the new one ends.
+        let state = PopupGrabState {
+            root,
+            grab,
+            grab_pointer,
+        };
+        push_popup_grab(&mut self.niri.popup_grab, state, |top, new| {
+            top.root == new.root
+        });
     }
 
     fn maximize_request(&mut self, surface: ToplevelSurface) {
-        // FIXME
+        if let Some((window, _)) = self
+            .niri
+            .layout
+            .find_window_and_output(surface.wl_surface())
+        {
+            let window = window.clone();
+            match self.maximize_target(&surface) {
+                MaximizeTarget::Column => self.niri.layout.set_maximized(&window, true),
+                MaximizeTarget::Monitor => {
+                    self.niri
+                        .layout
+                        .set_maximized_to_working_area(&window, true);
+                }
+            }
+
+            // The layout may refuse to maximize the window, e.g. while it's fullscreen.
+            let is_maximized = self.niri.layout.is_maximized(&window);
+            surface.with_pending_state(|state| {
+                if is_maximized {
+                    state.states.set(xdg_toplevel::State::Maximized);
+                } else {
+                    state.states.unset(xdg_toplevel::State::Maximized);
+                }
+            });
+        } else {
+            // For unmapped windows, the maximized state is picked up when the window is mapped.
+            surface.with_pending_state(|state| {
+                state.states.set(xdg_toplevel::State::Maximized);
+            });
+        }
 
         // The protocol demands us to always reply with a configure,
         // regardless of we fulfilled the request or not
-        surface.send_configure();
+        send_configure_unless_initial_pending(&surface);
     }
 
-    fn unmaximize_request(&mut self, _surface: ToplevelSurface) {
-        // FIXME
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some((window, _)) = self
+            .niri
+            .layout
+            .find_window_and_output(surface.wl_surface())
+        {
+            let window = window.clone();
+            self.niri.layout.set_maximized(&window, false);
+            self.niri
+                .layout
+                .set_maximized_to_working_area(&window, false);
+        }
+
+        surface.with_pending_state(|state| {
+            state.states.unset(xdg_toplevel::State::Maximized);
+        });
+
+        send_configure_unless_initial_pending(&surface);
     }
 
     fn fullscreen_request(
@@ -271,43 +461,21 @@ impl XdgShellHandler for State {
         surface: ToplevelSurface,
         wl_output: Option<wl_output::WlOutput>,
     ) {
-        if surface
-            .current_state()
-            .capabilities
-            .contains(xdg_toplevel::WmCapabilities::Fullscreen)
-        {
-            if let Some((window, current_output)) = self
-                .niri
-                .layout
-                .find_window_and_output(surface.wl_surface())
-            {
-                let window = window.clone();
-
-                if let Some(requested_output) = wl_output.as_ref().and_then(Output::from_resource) {
-                    if &requested_output != current_output {
-                        self.niri
-                            .layout
-                            .move_window_to_output(window.clone(), &requested_output);
-                    }
-                }
-
-                self.niri.layout.set_fullscreen(&window, true);
-            } else if let Some(window) = self.niri.unmapped_windows.get(surface.wl_surface()) {
-                if let Some(ws) = self.niri.layout.active_workspace() {
-                    window.toplevel().with_pending_state(|state| {
-                        state.size = Some(ws.view_size());
-                        state.states.set(xdg_toplevel::State::Fullscreen);
-                    });
-                }
-            }
+        if !self.defer_fullscreen_request(&surface, &wl_output) {
+            self.set_fullscreen_from_request(&surface, wl_output);
         }
 
         // The protocol demands us to always reply with a configure,
         // regardless of we fulfilled the request or not
-        surface.send_configure();
+        send_configure_unless_initial_pending(&surface);
     }
 
     fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        // Leaving fullscreen within the grace period means the window never goes fullscreen.
+        if self.cancel_pending_fullscreen(&surface) {
+            return;
+        }
+
         if let Some((window, _)) = self
             .niri
             .layout
@@ -315,28 +483,54 @@ impl XdgShellHandler for State {
         {
             let window = window.clone();
             self.niri.layout.set_fullscreen(&window, false);
+            self.restore_pre_fullscreen_location(&window);
         } else if let Some(window) = self.niri.unmapped_windows.get(surface.wl_surface()) {
             let config = self.niri.config.borrow();
-            let rules = resolve_window_rules(&config.window_rules, window.toplevel());
+            let rules = resolve_window_rules(&config, window.toplevel(), &self.niri.display_handle);
 
-            let output = rules
-                .open_on_output
-                .and_then(|name| self.niri.output_by_name.get(name));
+            let output = rules.open_on_connected_output(&self.niri.output_by_name);
             let mon = output.map(|o| self.niri.layout.monitor_for_output(o).unwrap());
             let ws = mon
                 .map(|mon| mon.active_workspace_ref())
                 .or_else(|| self.niri.layout.active_workspace());
 
-            if let Some(ws) = ws {
-                window.toplevel().with_pending_state(|state| {
-                    state.size = Some(ws.new_window_size(rules.default_width));
-                    state.states.unset(xdg_toplevel::State::Fullscreen);
-                });
-            }
+            set_waiting_for_output(window, ws.is_none());
+            window.toplevel().with_pending_state(|state| {
+                // Go back to the tiled size, keeping in mind that maximized windows are
+                // full-width.
+                if let Some(ws) = ws {
+                    let default_width = if state.states.contains(xdg_toplevel::State::Maximized) {
+                        Some(Some(ColumnWidth::Proportion(1.)))
+                    } else {
+                        rules.default_width_for_output(ws.output())
+                    };
+
+                    state.size =
+                        Some(ws.new_window_size(window, default_width, rules.default_height));
+                }
+                state.states.unset(xdg_toplevel::State::Fullscreen);
+            });
+            clamp_pending_width(window.toplevel(), &rules);
         }
     }
 
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        self.cancel_pending_fullscreen(&surface);
+
+        // Clean up everything that refers to the surface first, so that nothing is left behind
+        // even if the window can't be found below.
+        self.dismiss_popups_for_root(surface.wl_surface());
+        self.niri
+            .window_marks
+            .retain(|_, win| win.toplevel().wl_surface() != surface.wl_surface());
+
+        // Dialogs of the toplevel now belong to its own parent.
+        for child in self.niri.xdg_shell_state.toplevel_surfaces() {
+            if child.parent().as_ref() == Some(surface.wl_surface()) {
+                child.set_parent(surface.parent().as_ref());
+            }
+        }
+
         if self
             .niri
             .unmapped_windows
@@ -347,6 +541,17 @@ impl XdgShellHandler for State {
             return;
         }
 
+        let scratchpad = &mut self.niri.scratchpad;
+        if let Some(idx) = scratchpad
+            .iter()
+            .position(|win| win.toplevel().wl_surface() == surface.wl_surface())
+        {
+            // A toplevel hidden in the scratchpad got destroyed.
+            let window = scratchpad.remove(idx);
+            self.ipc_window_closed(&window);
+            return;
+        }
+
         let win_out = self
             .niri
             .layout
@@ -354,18 +559,21 @@ impl XdgShellHandler for State {
 
         let Some((window, output)) = win_out.map(clone2) else {
             // I have no idea how this can happen, but I saw it happen once, in a weird interaction
-            // involving laptop going to sleep and resuming.
-            error!("toplevel missing from both unmapped_windows and layout");
+            // involving laptop going to sleep and resuming. There's nothing left to remove at
+            // this point, but redraw in case some output still shows the window.
+            warn!("toplevel missing from both unmapped_windows and layout");
+            self.niri.queue_redraw_all();
             return;
         };
 
+        self.ipc_window_closed(&window);
         self.niri.layout.remove_window(&window);
         self.niri.queue_redraw(output);
     }
 
     fn popup_destroyed(&mut self, surface: PopupSurface) {
         if let Some(output) = self.output_for_popup(&PopupKind::Xdg(surface)) {
-            self.niri.queue_redraw(output.clone());
+            self.niri.queue_redraw(output);
         }
     }
 }
@@ -374,9 +582,9 @@ delegate_xdg_shell!(State);
 
 impl XdgDecorationHandler for State {
     fn new_decoration(&mut self, toplevel: ToplevelSurface) {
-        // If we want CSD, we hide this global altogether.
+        let mode = self.default_decoration_mode(&toplevel);
         toplevel.with_pending_state(|state| {
-            state.decoration_mode = Some(zxdg_toplevel_decoration_v1::Mode::ServerSide);
+            state.decoration_mode = Some(mode);
         });
     }
 
@@ -388,144 +596,1523 @@ impl XdgDecorationHandler for State {
         // https://github.com/libsdl-org/SDL/issues/8173
         //
         // The bug has been fixed, but there's a ton of apps which will use the buggy version for a
-        // long while...
-        toplevel.with_pending_state(|state| {
+        // long while... So we only override the request for windows with a force-decoration rule.
+        let mode = self.forced_decoration_mode(&toplevel).unwrap_or(mode);
+        let changed = toplevel.with_pending_state(|state| {
+            let changed = state.decoration_mode != Some(mode);
             state.decoration_mode = Some(mode);
+            changed
         });
 
         // A configure is required in response to this event. However, if an initial configure
         // wasn't sent, then we will send this as part of the initial configure later.
-        if initial_configure_sent(&toplevel) {
-            toplevel.send_configure();
+        send_configure_unless_initial_pending(&toplevel);
+
+        if changed {
+            self.queue_redraw_for_decoration_change(&toplevel);
         }
     }
 
     fn unset_mode(&mut self, toplevel: ToplevelSurface) {
-        // If we want CSD, we hide this global altogether.
-        toplevel.with_pending_state(|state| {
-            state.decoration_mode = Some(zxdg_toplevel_decoration_v1::Mode::ServerSide);
+        let mode = self.default_decoration_mode(&toplevel);
+        let changed = toplevel.with_pending_state(|state| {
+            let changed = state.decoration_mode != Some(mode);
+            state.decoration_mode = Some(mode);
+            changed
         });
 
         // A configure is required in response to this event. However, if an initial configure
         // wasn't sent, then we will send this as part of the initial configure later.
-        if initial_configure_sent(&toplevel) {
-            toplevel.send_configure();
+        send_configure_unless_initial_pending(&toplevel);
+
+        if changed {
+            self.queue_redraw_for_decoration_change(&toplevel);
         }
     }
 }
 delegate_xdg_decoration!(State);
 
-impl KdeDecorationHandler for State {
-    fn kde_decoration_state(&self) -> &KdeDecorationState {
-        &self.niri.kde_decoration_state
-    }
-}
-
-delegate_kde_decoration!(State);
-
-pub fn initial_configure_sent(toplevel: &ToplevelSurface) -> bool {
-    with_states(toplevel.wl_surface(), |states| {
-        states
-            .data_map
-            .get::<XdgToplevelSurfaceData>()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .initial_configure_sent
-    })
-}
-
 impl State {
-    pub fn send_initial_configure_if_needed(&mut self, window: &Window) {
-        let toplevel = window.toplevel();
-        if initial_configure_sent(toplevel) {
-            return;
-        }
-
-        let _span = tracy_client::span!("State::send_initial_configure_if_needed");
-
-        let config = self.niri.config.borrow();
-        let rules = resolve_window_rules(&config.window_rules, toplevel);
-
-        let output = rules
-            .open_on_output
-            .and_then(|name| self.niri.output_by_name.get(name));
-        let mon = output.map(|o| self.niri.layout.monitor_for_output(o).unwrap());
-        let ws = mon
-            .map(|mon| mon.active_workspace_ref())
-            .or_else(|| self.niri.layout.active_workspace());
+    /// Fulfills a fullscreen request from a client.
+    fn set_fullscreen_from_request(
+        &mut self,
+        surface: &ToplevelSurface,
+        wl_output: Option<wl_output::WlOutput>,
+    ) {
+        if surface
+            .current_state()
+            .capabilities
+            .contains(xdg_toplevel::WmCapabilities::Fullscreen)
+        {
+            if let Some((window, current_output)) = self
+                .niri
+                .layout
+                .find_window_and_output(surface.wl_surface())
+            {
+                let window = window.clone();
+                let current_output = current_output.clone();
+
+                let requested_output = wl_output.as_ref().and_then(Output::from_resource);
+                let output = requested_output.unwrap_or_else(|| current_output.clone());
+
+                // Windows can go fullscreen on a workspace of their own, out of the way of the
+                // other windows. The empty workspace is always the last one. Windows that are
+                // alone already, including ones that went fullscreen this way, stay in place.
+                let on_empty_workspace = self.niri.config.borrow().fullscreen_on_empty_workspace;
+                let is_alone = self
+                    .niri
+                    .layout
+                    .monitor_for_output(&current_output)
+                    .and_then(|mon| mon.workspaces.iter().find(|ws| ws.has_window(&window)))
+                    .map_or(false, |ws| ws.windows().count() == 1);
+                let workspace_id = if on_empty_workspace && !is_alone {
+                    self.niri
+                        .layout
+                        .empty_workspace(Some(&output))
+                        .map(|ws| ws.id())
+                } else {
+                    None
+                };
+
+                if output != current_output || workspace_id.is_some() {
+                    let from = self
+                        .niri
+                        .layout
+                        .window_location(&window)
+                        .map(|(o, id)| (o.clone(), id));
+                    self.niri.layout.move_window_to_workspace(
+                        window.clone(),
+                        &output,
+                        workspace_id,
+                    );
+                    let to = self
+                        .niri
+                        .layout
+                        .window_location(&window)
+                        .map(|(o, id)| (o.clone(), id));
+
+                    // Remember where the window came from to move it back on unfullscreen.
+                    if let (Some(from), Some(to)) = (from, to) {
+                        let pre_fullscreen = window
+                            .user_data()
+                            .get_or_insert(PreFullscreenLocation::default);
+                        pre_fullscreen.0.replace(Some(FullscreenMove { from, to }));
+                    }
+                }
 
-        // Tell the surface the preferred size and bounds for its likely output.
-        if let Some(ws) = ws {
-            ws.configure_new_window(window, rules.default_width);
+                self.niri.layout.set_fullscreen(&window, true);
+            } else if let Some(window) = self.niri.unmapped_windows.get(surface.wl_surface()) {
+                let config = self.niri.config.borrow();
+                let rules =
+                    resolve_window_rules(&config, window.toplevel(), &self.niri.display_handle);
+
+                // The window will open on the output from the rules, so use its size.
+                let output = rules.open_on_connected_output(&self.niri.output_by_name);
+                let mon = output.map(|o| self.niri.layout.monitor_for_output(o).unwrap());
+                let ws = mon
+                    .map(|mon| mon.active_workspace_ref())
+                    .or_else(|| self.niri.layout.active_workspace());
+
+                set_waiting_for_output(window, ws.is_none());
+                window.toplevel().with_pending_state(|state| {
+                    if let Some(ws) = ws {
+                        state.size = Some(ws.view_size());
+                    }
+                    state.states.set(xdg_toplevel::State::Fullscreen);
+                });
+            }
         }
+    }
 
-        // If the user prefers no CSD, it's a reasonable assumption that they would prefer to get
-        // rid of the various client-side rounded corners also by using the tiled state.
-        if config.prefer_no_csd {
-            toplevel.with_pending_state(|state| {
-                state.states.set(xdg_toplevel::State::TiledLeft);
-                state.states.set(xdg_toplevel::State::TiledRight);
-                state.states.set(xdg_toplevel::State::TiledTop);
-                state.states.set(xdg_toplevel::State::TiledBottom);
-            });
+    /// Holds back a fullscreen request during the fullscreen grace period.
+    ///
+    /// Returns `true` if the request was deferred.
+    fn defer_fullscreen_request(
+        &mut self,
+        surface: &ToplevelSurface,
+        wl_output: &Option<wl_output::WlOutput>,
+    ) -> bool {
+        let grace_period =
+            Duration::from_millis(self.niri.config.borrow().fullscreen_grace_period_ms.into());
+        if grace_period.is_zero() {
+            return false;
         }
 
-        toplevel.send_configure();
-    }
+        let wl_surface = surface.wl_surface();
+        let window = if let Some(window) = self.niri.unmapped_windows.get(wl_surface) {
+            window
+        } else if let Some((window, _)) = self.niri.layout.find_window_and_output(wl_surface) {
+            let map_time = window
+                .user_data()
+                .get::<WindowMapTime>()
+                .and_then(|time| time.0.get());
+            let just_mapped =
+                map_time.map_or(false, |time| get_monotonic_time() < time + grace_period);
+            if !just_mapped {
+                return false;
+            }
+            window
+        } else {
+            return false;
+        };
 
-    /// Should be called on `WlSurface::commit`
-    pub fn popups_handle_commit(&mut self, surface: &WlSurface) {
-        self.niri.popups.commit(surface);
+        let pending = window.user_data().get_or_insert(PendingFullscreen::default);
+        let mut pending = pending.0.borrow_mut();
 
-        if let Some(popup) = self.niri.popups.find_popup(surface) {
-            match popup {
-                PopupKind::Xdg(ref popup) => {
-                    let initial_configure_sent = with_states(surface, |states| {
-                        states
-                            .data_map
-                            .get::<XdgPopupSurfaceData>()
-                            .unwrap()
-                            .lock()
-                            .unwrap()
-                            .initial_configure_sent
-                    });
-                    if !initial_configure_sent {
-                        if let Some(output) = self.output_for_popup(&PopupKind::Xdg(popup.clone()))
-                        {
-                            let scale = output.current_scale().integer_scale();
-                            let transform = output.current_transform();
-                            with_states(surface, |data| {
-                                send_surface_state(surface, data, scale, transform);
-                            });
-                        }
-                        popup.send_configure().expect("initial configure failed");
-                    }
-                }
-                // Input method popups don't require a configure.
-                PopupKind::InputMethod(_) => (),
-            }
+        // A repeated request only updates the output, keeping the original deadline.
+        if let Some((_, output)) = &mut *pending {
+            *output = wl_output.clone();
+            return true;
         }
-    }
 
-    pub fn output_for_popup(&self, popup: &PopupKind) -> Option<&Output> {
-        let root = find_popup_root_surface(popup).ok()?;
-        self.niri.output_for_root(&root)
+        let timer = Timer::from_duration(grace_period);
+        let surface = surface.clone();
+        let token = self
+            .niri
+            .event_loop
+            .insert_source(timer, move |_, _, state| {
+                state.finish_pending_fullscreen(&surface);
+                TimeoutAction::Drop
+            })
+            .unwrap();
+        *pending = Some((token, wl_output.clone()));
+
+        true
     }
 
-    pub fn unconstrain_popup(&self, popup: &PopupSurface) {
-        let _span = tracy_client::span!("Niri::unconstrain_popup");
+    /// Fulfills a fullscreen request once the fullscreen grace period is over.
+    fn finish_pending_fullscreen(&mut self, surface: &ToplevelSurface) {
+        let wl_surface = surface.wl_surface();
+        let window = self.niri.unmapped_windows.get(wl_surface).or_else(|| {
+            self.niri
+                .layout
+                .find_window_and_output(wl_surface)
+                .map(|(window, _)| window)
+        });
+        let Some(window) = window else {
+            return;
+        };
 
-        // Popups with a NULL parent will get repositioned in their respective protocol handlers
-        // (i.e. layer-shell).
-        let Ok(root) = find_popup_root_surface(&PopupKind::Xdg(popup.clone())) else {
+        let Some(pending) = window.user_data().get::<PendingFullscreen>() else {
+            return;
+        };
+        let Some((_, wl_output)) = pending.0.take() else {
             return;
         };
 
-        // Figure out if the root is a window or a layer surface.
-        if let Some((window, output)) = self.niri.layout.find_window_and_output(&root) {
-            self.unconstrain_window_popup(popup, window, output);
-        } else if let Some((layer_surface, output)) = self.niri.layout.outputs().find_map(|o| {
+        self.set_fullscreen_from_request(surface, wl_output);
+        send_configure_unless_initial_pending(surface);
+    }
+
+    /// Drops a fullscreen request held back during the fullscreen grace period.
+    ///
+    /// Returns `true` if there was such a request.
+    fn cancel_pending_fullscreen(&mut self, surface: &ToplevelSurface) -> bool {
+        let wl_surface = surface.wl_surface();
+        let window = self.niri.unmapped_windows.get(wl_surface).or_else(|| {
+            self.niri
+                .layout
+                .find_window_and_output(wl_surface)
+                .map(|(window, _)| window)
+        });
+        let Some(window) = window else {
+            return false;
+        };
+
+        let pending = window
+            .user_data()
+            .get::<PendingFullscreen>()
+            .and_then(|pending| pending.0.take());
+        let Some((token, _)) = pending else {
+            return false;
+        };
+
+        self.niri.event_loop.remove(token);
+        true
+    }
+
+    /// Returns the toplevel of a mapped or unmapped window with this surface.
+    fn toplevel_for_surface(&self, surface: &WlSurface) -> Option<ToplevelSurface> {
+        if let Some(window) = self.niri.unmapped_windows.get(surface) {
+            return Some(window.toplevel().clone());
+        }
+
+        let (window, _) = self.niri.layout.find_window_and_output(surface)?;
+        Some(window.toplevel().clone())
+    }
+
+    /// Redraws the output of a mapped window, so that its borders follow a decoration mode change
+    /// right away instead of on the next client commit.
+    fn queue_redraw_for_decoration_change(&mut self, toplevel: &ToplevelSurface) {
+        let surface = toplevel.wl_surface();
+        if let Some((_, output)) = self.niri.layout.find_window_and_output(surface) {
+            let output = output.clone();
+            self.niri.queue_redraw(output);
+        }
+    }
+}
+
+impl KdeDecorationHandler for State {
+    fn kde_decoration_state(&self) -> &KdeDecorationState {
+        &self.niri.kde_decoration_state
+    }
+
+    fn new_decoration(&mut self, surface: &WlSurface, decoration: &OrgKdeKwinServerDecoration) {
+        // Tell the client our preference, same as for xdg-decoration.
+        let Some(toplevel) = self.toplevel_for_surface(surface) else {
+            return;
+        };
+
+        let mode = match self.default_decoration_mode(&toplevel) {
+            zxdg_toplevel_decoration_v1::Mode::ClientSide => KdeDecorationsMode::Client,
+            _ => KdeDecorationsMode::Server,
+        };
+        decoration.mode(mode);
+    }
+
+    fn request_mode(
+        &mut self,
+        surface: &WlSurface,
+        decoration: &OrgKdeKwinServerDecoration,
+        mode: WEnum<KdeDecorationsMode>,
+    ) {
+        let WEnum::Value(mode) = mode else {
+            return;
+        };
+
+        let toplevel = self.toplevel_for_surface(surface);
+
+        // Set whatever the client wants unless a rule forces a mode, same as for xdg-decoration.
+        let forced = toplevel
+            .as_ref()
+            .and_then(|toplevel| self.forced_decoration_mode(toplevel));
+        let mode = match forced {
+            Some(zxdg_toplevel_decoration_v1::Mode::ClientSide) => KdeDecorationsMode::Client,
+            Some(_) => KdeDecorationsMode::Server,
+            None => mode,
+        };
+        decoration.mode(mode);
+
+        let Some(toplevel) = toplevel else {
+            return;
+        };
+
+        // Keep the toplevel state in sync so that borders follow the KDE mode too.
+        let xdg_mode = match mode {
+            KdeDecorationsMode::Server => zxdg_toplevel_decoration_v1::Mode::ServerSide,
+            _ => zxdg_toplevel_decoration_v1::Mode::ClientSide,
+        };
+        let changed = toplevel.with_pending_state(|state| {
+            let changed = state.decoration_mode != Some(xdg_mode);
+            state.decoration_mode = Some(xdg_mode);
+            changed
+        });
+
+        if changed {
+            send_configure_unless_initial_pending(&toplevel);
+            self.queue_redraw_for_decoration_change(&toplevel);
+        }
+    }
+}
+
+delegate_kde_decoration!(State);
+
+/// Applies the dynamic window rule properties to a mapped window.
+///
+/// Initial properties are handled by the code that opens the window, so they are ignored here.
+pub fn apply_window_rules(window: &Window, rules: &ResolvedWindowRule) {
+    window
+        .user_data()
+        .get_or_insert(WindowBlockOut::default)
+        .mode
+        .set(rules.block_out_from);
+
+    let state = window.user_data().get_or_insert(WindowRulesState::default);
+    state.opacity.set(rules.opacity);
+    state.border_active_color.set(rules.border_active_color);
+    state.border_inactive_color.set(rules.border_inactive_color);
+    state.min_width.set(rules.min_width);
+    state.max_width.set(rules.max_width);
+    state.scale_override.set(rules.scale_override);
+    state
+        .max_windows_per_column
+        .set(rules.max_windows_per_column);
+    state.always_on_top.set(rules.always_on_top);
+    state.focus_follows_mouse.set(rules.focus_follows_mouse);
+    state.popup_pointer_grab.set(rules.popup_pointer_grab);
+    state.aspect_ratio.set(rules.aspect_ratio);
+    state.max_auto_width.set(rules.max_auto_width);
+}
+
+fn set_waiting_for_output(window: &Window, waiting: bool) {
+    window
+        .user_data()
+        .get_or_insert(WaitingForOutput::default)
+        .0
+        .set(waiting);
+}
+
+fn is_waiting_for_output(window: &Window) -> bool {
+    window
+        .user_data()
+        .get::<WaitingForOutput>()
+        .map_or(false, |waiting| waiting.0.get())
+}
+
+/// Clamps the pending width of a toplevel to the window rule width bounds.
+fn clamp_pending_width(toplevel: &ToplevelSurface, rules: &ResolvedWindowRule) {
+    toplevel.with_pending_state(|state| {
+        let Some(size) = &mut state.size else {
+            return;
+        };
+
+        // Zero width means the window picks its own width, which the layout will clamp later.
+        if size.w == 0 {
+            return;
+        }
+
+        if let Some(max_width) = rules.max_width {
+            size.w = min(size.w, max_width);
+        }
+        if let Some(min_width) = rules.min_width {
+            size.w = max(size.w, min_width);
+        }
+    });
+}
+
+pub fn initial_configure_sent(toplevel: &ToplevelSurface) -> bool {
+    with_states(toplevel.wl_surface(), |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .initial_configure_sent
+    })
+}
+
+/// Returns whether a new column for the window would go past the column limit of the workspace.
+fn is_workspace_full(config: &Config, rules: &ResolvedWindowRule, ws: &Workspace<Window>) -> bool {
+    let max_columns = rules.max_columns.or(config.layout.max_columns);
+    max_columns.map_or(false, |max| ws.columns.len() >= usize::from(max))
+}
+
+/// Sends a configure in reply to a client request, unless the initial configure is still due.
+///
+/// Before the initial configure, the pending state goes out as part of it instead, so that the
+/// window gets a single configure with its decoration mode, states, size and bounds.
+fn send_configure_unless_initial_pending(toplevel: &impl ConfigureToplevel) {
+    if toplevel.initial_configure_sent() {
+        toplevel.send_configure();
+    }
+}
+
+/// The parts of a toplevel used when replying to client requests with a configure.
+trait ConfigureToplevel {
+    fn initial_configure_sent(&self) -> bool;
+    fn send_configure(&self);
+}
+
+impl ConfigureToplevel for ToplevelSurface {
+    fn initial_configure_sent(&self) -> bool {
+        initial_configure_sent(self)
+    }
+
+    fn send_configure(&self) {
+        ToplevelSurface::send_configure(self);
+    }
+}
+
+impl State {
+    /// Repositions popups of windows that moved to a different output or workspace.
+    ///
+    /// The new output has a different geometry and possibly scale, so the popups need to be
+    /// constrained against it, told about its preferred scale, and moved over to it with
+    /// wl_surface enter and leave.
+    ///
+    /// On another workspace of the same output, the window can end up at a different position,
+    /// e.g. in a column with other windows, so reactive popups need to follow it.
+    pub fn refresh_popups_output(&mut self) {
+        let _span = tracy_client::span!("State::refresh_popups_output");
+
+        let mut changed = Vec::new();
+        let mut moved = Vec::new();
+
+        self.niri.layout.with_windows(|window, output, ws_id| {
+            let popups_ws = window.user_data().get_or_insert(PopupsWorkspace::default);
+            let prev_ws_id = popups_ws.0.replace(Some(ws_id));
+
+            let popups_output = window.user_data().get_or_insert(PopupsOutput::default);
+            let mut popups_output = popups_output.0.borrow_mut();
+            if popups_output.as_ref() == output {
+                if prev_ws_id.map_or(false, |id| id != ws_id) {
+                    moved.push(window.clone());
+                }
+                return;
+            }
+
+            let is_first = popups_output.is_none();
+            *popups_output = output.cloned();
+
+            // Popups of a newly mapped window are positioned for its output already.
+            if !is_first {
+                changed.push(window.clone());
+            }
+        });
+
+        for window in changed {
+            for (popup, _) in PopupManager::popups_for_surface(window.toplevel().wl_surface()) {
+                self.send_popup_surface_state(&popup);
+                self.update_popup_outputs(&popup);
+            }
+            self.update_reactive_popups(&window);
+        }
+
+        for window in moved {
+            self.update_reactive_popups(&window);
+        }
+    }
+
+    /// Repositions reactive popups of layer surfaces whose geometry changed.
+    ///
+    /// Layer surfaces get rearranged when they resize, when exclusive zones change and when the
+    /// output changes size, and popups like bar menus should stay attached to their surface.
+    pub fn refresh_layer_popups(&mut self) {
+        let _span = tracy_client::span!("State::refresh_layer_popups");
+
+        let mut changed = Vec::new();
+
+        for output in self.niri.layout.outputs() {
+            let Some(output_geo) = self.niri.global_space.output_geometry(output) else {
+                continue;
+            };
+
+            let map = layer_map_for_output(output);
+            for layer in map.layers() {
+                let Some(layer_geo) = map.layer_geometry(layer) else {
+                    continue;
+                };
+
+                let current = (layer_geo, output_geo.size);
+                let popups_geo = layer
+                    .user_data()
+                    .get_or_insert(PopupsLayerGeometry::default);
+                let prev = popups_geo.0.replace(Some(current));
+
+                // Popups of a new layer surface are positioned for its geometry already.
+                if prev.map_or(false, |prev| prev != current) {
+                    changed.push(layer.wl_surface().clone());
+                }
+            }
+        }
+
+        for surface in changed {
+            self.update_reactive_popups_for_root(&surface);
+        }
+    }
+
+    /// Re-resolves the window rules for mapped windows whose title, app ID, parent or output has
+    /// changed.
+    ///
+    /// This is the live pass: only the dynamic properties are applied to the windows.
+    pub fn refresh_window_rules(&mut self) {
+        let _span = tracy_client::span!("State::refresh_window_rules");
+
+        let config = self.niri.config.borrow();
+        let mut changed = Vec::new();
+
+        self.niri.layout.with_windows(|window, output, _| {
+            if re_resolve_window_rules(&config, window, output, &self.niri.display_handle).is_some()
+            {
+                changed.push(window.clone());
+            }
+        });
+
+        drop(config);
+
+        if !changed.is_empty() {
+            for window in &changed {
+                // The width bounds might have changed, so let the layout re-clamp the windows.
+                self.niri.layout.update_window(window);
+
+                // The scale override might have changed, so send the preferred scale again.
+                let surface = window.toplevel().wl_surface();
+                if let Some((_, output)) = self.niri.layout.find_window_and_output(surface) {
+                    let scale = output.current_scale();
+                    window.set_preferred_scale_transform(scale, output.current_transform());
+                }
+            }
+
+            // FIXME: granular.
+            self.niri.queue_redraw_all();
+        }
+    }
+
+    /// Re-resolves the window rules for a single mapped window on commit.
+    ///
+    /// This way, rules matching on the title or app ID apply in the same frame as the commit
+    /// rather than in the next refresh. The caller updates the window in the layout and redraws
+    /// its output afterwards.
+    pub fn refresh_window_rules_on_commit(&mut self, window: &Window, output: &Output) {
+        let config = self.niri.config.borrow();
+        let re_resolved =
+            re_resolve_window_rules(&config, window, Some(output), &self.niri.display_handle);
+        drop(config);
+
+        let Some(re_resolved) = re_resolved else {
+            return;
+        };
+
+        // The scale override might have changed, so send the preferred scale again.
+        let scale = output.current_scale();
+        window.set_preferred_scale_transform(scale, output.current_transform());
+
+        if re_resolved.app_id_changed {
+            self.reapply_open_on_output(window, output);
+        }
+    }
+
+    /// Moves a window that changed its app ID to the output from the rules matching the new one.
+    ///
+    /// This only happens with reapply-open-on-output, since moving windows around after they
+    /// opened can be surprising.
+    fn reapply_open_on_output(&mut self, window: &Window, current: &Output) {
+        let config = self.niri.config.borrow();
+        let rules = resolve_window_rules(&config, window.toplevel(), &self.niri.display_handle);
+
+        let Some(target) = rules.open_on_connected_output(&self.niri.output_by_name) else {
+            return;
+        };
+        if target == current {
+            return;
+        }
+
+        let target = target.clone();
+        let reapply = rules.reapply_open_on_output == Some(true);
+        drop(config);
+
+        let (_, app_id) = title_and_app_id(window.toplevel());
+        if !reapply {
+            debug!(
+                ?app_id,
+                "window changed its app ID and now matches open-on-output {}, \
+                 but reapply-open-on-output is not set, not moving it",
+                target.name()
+            );
+            return;
+        }
+
+        debug!(
+            ?app_id,
+            "window changed its app ID, moving it to {}",
+            target.name()
+        );
+        self.niri
+            .layout
+            .move_window_to_output(window.clone(), &target);
+        self.niri.queue_redraw(&target);
+    }
+
+    /// Dry-runs the window rules against the focused window.
+    pub fn focused_window_rules(&self) -> Option<niri_ipc::WindowRules> {
+        let window = self.niri.layout.focus()?;
+        let toplevel = window.toplevel();
+
+        let config = self.niri.config.borrow();
+        let matched = matching_window_rules(&config, toplevel, &self.niri.display_handle);
+        let resolved = resolve_window_rules(&config, toplevel, &self.niri.display_handle);
+
+        let (title, app_id) = title_and_app_id(toplevel);
+
+        Some(niri_ipc::WindowRules {
+            title,
+            app_id,
+            matched,
+            resolved: resolved.to_ipc(),
+        })
+    }
+
+    /// Returns what maximizing the window does.
+    pub fn maximize_target(&self, toplevel: &ToplevelSurface) -> MaximizeTarget {
+        let config = self.niri.config.borrow();
+        let rules = resolve_window_rules(&config, toplevel, &self.niri.display_handle);
+        rules.maximize_to.unwrap_or(config.maximize_to)
+    }
+
+    /// Decides whether a window requesting activation should receive focus.
+    pub fn should_focus_on_activation(
+        &self,
+        window: &Window,
+        token_data: &XdgActivationTokenData,
+    ) -> bool {
+        let config = self.niri.config.borrow();
+        let rules = resolve_window_rules(&config, window.toplevel(), &self.niri.display_handle);
+        let policy = rules.activation_focus.unwrap_or(config.activation_focus);
+
+        match policy {
+            ActivationFocus::Always => true,
+            ActivationFocus::Never => false,
+            ActivationFocus::SameApp => {
+                // Like with popup grabs, the layout focus is what the user is interacting with.
+                let Some(focused) = self.niri.layout.focus() else {
+                    return true;
+                };
+                let Some(focused_client) = focused.toplevel().wl_surface().client() else {
+                    return false;
+                };
+
+                // Either the focused app requested the token, or it activates its own window.
+                let requester = token_data.surface.as_ref().and_then(|s| s.client());
+                let target = window.toplevel().wl_surface().client();
+                requester.as_ref() == Some(&focused_client)
+                    || target.as_ref() == Some(&focused_client)
+            }
+        }
+    }
+
+    /// Returns the windows that requested attention.
+    pub fn urgent_windows(&self) -> Vec<niri_ipc::Window> {
+        let mut windows = Vec::new();
+        self.niri.layout.with_windows(|window, output, _| {
+            if !window.is_urgent() {
+                return;
+            }
+
+            let (title, app_id) = title_and_app_id(window.toplevel());
+            windows.push(niri_ipc::Window {
+                title,
+                app_id,
+                output: output.map(|output| output.name()),
+            });
+        });
+        windows
+    }
+
+    /// Summarizes the active popup grab for debugging.
+    pub fn popup_grab_info(&self) -> Option<niri_ipc::PopupGrab> {
+        let PopupGrabState { root, grab, .. } = self.niri.popup_grab.last()?;
+
+        let (root_title, root_app_id) = self
+            .toplevel_for_surface(root)
+            .map(|toplevel| title_and_app_id(&toplevel))
+            .unwrap_or_default();
+        let root_layer_namespace = self.niri.layout.outputs().find_map(|output| {
+            let map = layer_map_for_output(output);
+            let layer = map.layer_for_surface(root, WindowSurfaceType::TOPLEVEL)?;
+            Some(layer.namespace().to_owned())
+        });
+
+        Some(niri_ipc::PopupGrab {
+            root_title,
+            root_app_id,
+            root_layer_namespace,
+            serial: grab.serial().into(),
+            previous_serial: grab.previous_serial().map(u32::from),
+            has_ended: grab.has_ended(),
+        })
+    }
+
+    /// Asks all windows matching `m` to close.
+    ///
+    /// Only the title and app ID are checked. Returns how many windows were asked to close.
+    pub fn close_matching_windows(&self, m: &Match) -> usize {
+        let mut count = 0;
+        self.niri.layout.with_windows(|window, _, _| {
+            let toplevel = window.toplevel();
+            let (title, app_id) = title_and_app_id(toplevel);
+            let subject = WindowRuleSubject {
+                title,
+                app_id,
+                ..Default::default()
+            };
+
+            if window_matches(&subject, m) {
+                toplevel.send_close();
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Tells event stream clients that the window opened.
+    pub fn ipc_window_opened(&self, window: &Window) {
+        let info = self.ipc_window_info(window);
+        self.niri
+            .ipc_send_event(niri_ipc::Event::WindowOpened(info));
+    }
+
+    /// Tells event stream clients if the title or the app ID of an open window changed.
+    pub fn ipc_window_changed(&self, window: &Window) {
+        let ipc = window.user_data().get_or_insert(IpcWindow::default);
+        let current = title_and_app_id(window.toplevel());
+        match &*ipc.reported.borrow() {
+            Some(reported) if *reported != current => (),
+            _ => return,
+        }
+
+        let info = self.ipc_window_info(window);
+        self.niri
+            .ipc_send_event(niri_ipc::Event::WindowChanged(info));
+    }
+
+    /// Tells event stream clients that the window closed.
+    pub fn ipc_window_closed(&self, window: &Window) {
+        let ipc = window.user_data().get_or_insert(IpcWindow::default);
+        if ipc.reported.take().is_some() {
+            self.niri
+                .ipc_send_event(niri_ipc::Event::WindowClosed { id: ipc.id });
+        }
+    }
+
+    fn ipc_window_info(&self, window: &Window) -> niri_ipc::WindowInfo {
+        let ipc = window.user_data().get_or_insert(IpcWindow::default);
+        let (title, app_id) = title_and_app_id(window.toplevel());
+        ipc.reported.replace(Some((title.clone(), app_id.clone())));
+
+        let workspace_id = self
+            .niri
+            .layout
+            .window_location(window)
+            .map(|(_, id)| u64::from(id.get()));
+
+        niri_ipc::WindowInfo {
+            id: ipc.id,
+            title,
+            app_id,
+            workspace_id,
+        }
+    }
+
+    /// Moves a window that was moved to another output for fullscreen back where it came from.
+    ///
+    /// Does nothing if the window has been moved since, or if its original output is gone.
+    fn restore_pre_fullscreen_location(&mut self, window: &Window) {
+        let Some(pre_fullscreen) = window.user_data().get::<PreFullscreenLocation>() else {
+            return;
+        };
+        let Some(FullscreenMove { from, to }) = pre_fullscreen.0.take() else {
+            return;
+        };
+
+        let current = self
+            .niri
+            .layout
+            .window_location(window)
+            .map(|(o, id)| (o.clone(), id));
+        if current.as_ref() != Some(&to) {
+            return;
+        }
+
+        let (output, workspace_id) = from;
+        if self.niri.layout.monitor_for_output(&output).is_none() {
+            return;
+        }
+
+        self.niri
+            .layout
+            .move_window_to_workspace(window.clone(), &output, Some(workspace_id));
+    }
+
+    /// Dismisses the popups of a toplevel that is going away, releasing their grab.
+    fn dismiss_popups_for_root(&mut self, root: &WlSurface) {
+        let (ended, was_active) =
+            take_popup_grabs_of_root(&mut self.niri.popup_grab, |grab| &grab.root == root);
+        let active_serial = ended
+            .last()
+            .filter(|_| was_active)
+            .map(|grab| grab.grab.serial());
+
+        for mut grab in ended {
+            trace!("grab root {root:?} was destroyed, ungrabbing");
+            grab.grab.ungrab(PopupUngrabStrategy::All);
+        }
+
+        // The grab below the destroyed one becomes active again.
+        if let Some(serial) = active_serial {
+            self.restore_popup_grab(serial);
+        }
+
+        if self.niri.popup_grab_blocked_by_ime.as_ref() == Some(root) {
+            self.niri.popup_grab_blocked_by_ime = None;
+        }
+
+        for (popup, _) in PopupManager::popups_for_surface(root) {
+            let _ = PopupManager::dismiss_popup(root, &popup);
+        }
+    }
+
+    /// Dismisses popups whose grab was ignored because of the input method, unless the pointer
+    /// is over one of them.
+    ///
+    /// Called on pointer button presses, since that's what a real grab would dismiss them on.
+    pub fn dismiss_ime_blocked_popups_on_click(&mut self) {
+        let Some(root) = self.niri.popup_grab_blocked_by_ime.take() else {
+            return;
+        };
+
+        let clicked_popup_root = self.niri.pointer_focus.as_ref().and_then(|focus| {
+            let mut surface = focus.surface.0.clone();
+            while let Some(parent) = get_parent(&surface) {
+                surface = parent;
+            }
+
+            let popup = self.niri.popups.find_popup(&surface)?;
+            find_popup_root_surface(&popup).ok()
+        });
+
+        // Clicks inside the popups keep them open.
+        if clicked_popup_root.as_ref() == Some(&root) {
+            self.niri.popup_grab_blocked_by_ime = Some(root);
+            return;
+        }
+
+        for (popup, _) in PopupManager::popups_for_surface(&root) {
+            let _ = PopupManager::dismiss_popup(&root, &popup);
+        }
+    }
+
+    /// Marks the focused window with a key, taking the mark away from any other window.
+    pub fn mark_focused_window(&mut self, mark: String) {
+        if let Some(window) = self.niri.layout.focus() {
+            let window = window.clone();
+            self.niri.window_marks.insert(mark, window);
+        }
+    }
+
+    /// Toggles whether the focused window stays above other floating windows.
+    pub fn toggle_focused_window_always_on_top(&mut self) {
+        let Some(window) = self.niri.layout.focus() else {
+            return;
+        };
+
+        let on_top = window.is_always_on_top();
+        window
+            .user_data()
+            .get_or_insert(AlwaysOnTopOverride::default)
+            .0
+            .set(Some(!on_top));
+
+        // FIXME: granular
+        self.niri.queue_redraw_all();
+    }
+
+    /// Focuses the window marked with a key, wherever it is.
+    pub fn focus_marked_window(&mut self, mark: &str) {
+        let Some(window) = self.niri.window_marks.get(mark) else {
+            return;
+        };
+
+        let window = window.clone();
+        self.niri.layout.activate_window(&window);
+        self.niri.queue_redraw_all();
+    }
+
+    /// Hides the focused scratchpad window, or shows the most recently hidden one.
+    pub fn toggle_scratchpad(&mut self) {
+        if let Some(window) = self.niri.layout.focus() {
+            let is_scratchpad = window
+                .user_data()
+                .get::<ScratchpadWindow>()
+                .map_or(false, |x| x.0.get());
+            if is_scratchpad {
+                let window = window.clone();
+                self.dismiss_popups_for_root(window.toplevel().wl_surface());
+                self.niri.layout.remove_window(&window);
+                self.niri.scratchpad.push(window);
+                // FIXME: granular.
+                self.niri.queue_redraw_all();
+                return;
+            }
+        }
+
+        let Some(window) = self.niri.scratchpad.pop() else {
+            return;
+        };
+
+        // Scratchpad windows show up floating on the active output.
+        if let Some(output) = self.niri.layout.add_floating_window(window, None, true) {
+            let output = output.clone();
+            self.niri.queue_redraw(output);
+        }
+    }
+
+    /// Marks a window as requesting attention, unless it already has keyboard focus.
+    pub fn mark_urgent(&mut self, window: &Window) {
+        if self.niri.keyboard_focus.as_ref() == Some(window.toplevel().wl_surface()) {
+            return;
+        }
+
+        window
+            .user_data()
+            .get_or_insert(WindowUrgency::default)
+            .is_urgent
+            .set(true);
+
+        // FIXME: granular.
+        self.niri.queue_redraw_all();
+    }
+
+    /// Returns the decoration mode to use when the client has no preference.
+    fn default_decoration_mode(
+        &self,
+        toplevel: &ToplevelSurface,
+    ) -> zxdg_toplevel_decoration_v1::Mode {
+        // If we want CSD globally, we hide the decoration globals altogether, so here we only need
+        // to check the window rules.
+        if let Some(mode) = self.forced_decoration_mode(toplevel) {
+            return mode;
+        }
+
+        let config = self.niri.config.borrow();
+        let rules = resolve_window_rules(&config, toplevel, &self.niri.display_handle);
+
+        if rules.prefer_csd == Some(true) {
+            zxdg_toplevel_decoration_v1::Mode::ClientSide
+        } else {
+            zxdg_toplevel_decoration_v1::Mode::ServerSide
+        }
+    }
+
+    /// Returns the decoration mode that a force-decoration rule sets for the window, if any.
+    fn forced_decoration_mode(
+        &self,
+        toplevel: &ToplevelSurface,
+    ) -> Option<zxdg_toplevel_decoration_v1::Mode> {
+        let config = self.niri.config.borrow();
+        let rules = resolve_window_rules(&config, toplevel, &self.niri.display_handle);
+
+        rules.force_decoration.map(|mode| match mode {
+            DecorationMode::ServerSide => zxdg_toplevel_decoration_v1::Mode::ServerSide,
+            DecorationMode::ClientSide => zxdg_toplevel_decoration_v1::Mode::ClientSide,
+        })
+    }
+
+    /// Adds a toplevel that got its first buffer to the layout.
+    ///
+    /// This is the map-time pass: the window rules are resolved once more and everything that
+    /// happens when a window opens, from placing it in the layout to the open animation, is done
+    /// here in one go.
+    pub fn map_window(&mut self, window: Window) {
+        let _span = tracy_client::span!("State::map_window");
+
+        // Remember the title at map time for window rules that match on it.
+        store_initial_title(window.toplevel());
+
+        // Remember the map time for the fullscreen grace period.
+        window
+            .user_data()
+            .get_or_insert(WindowMapTime::default)
+            .0
+            .set(Some(get_monotonic_time()));
+
+        let parent = window
+            .toplevel()
+            .parent()
+            .and_then(|parent| self.niri.layout.find_window_and_output(&parent))
+            .map(|(win, _)| win.clone());
+
+        let (
+            width,
+            height,
+            output,
+            workspace_name,
+            open_floating,
+            floating_position,
+            open_as_tab,
+            open_below_parent,
+            open_replacing_focused,
+            open_focused,
+            focus_across_workspace,
+            column_position,
+            maximize_to,
+            workspace_is_full,
+            in_scratchpad,
+        ) = {
+            let config = self.niri.config.borrow();
+            let rules = resolve_window_rules(&config, window.toplevel(), &self.niri.display_handle);
+            apply_window_rules(&window, &rules);
+
+            let in_scratchpad = rules.open_in_scratchpad == Some(true);
+            window
+                .user_data()
+                .get_or_insert(ScratchpadWindow::default)
+                .0
+                .set(in_scratchpad);
+
+            if let Some(mark) = rules.mark {
+                self.niri
+                    .window_marks
+                    .insert(mark.to_owned(), window.clone());
+            }
+
+            let output = rules
+                .open_on_connected_output(&self.niri.output_by_name)
+                .cloned();
+            let workspace_name = rules.open_on_workspace.and_then(|name| {
+                if self.niri.layout.find_workspace_by_name(name).is_some() {
+                    Some(name.to_owned())
+                } else {
+                    // The rule was already reported when the config was loaded.
+                    debug!(
+                        "workspace {name:?} from a window rule does not exist, \
+                         opening on the active workspace"
+                    );
+                    None
+                }
+            });
+
+            // Default widths can depend on the output, so find where the window will
+            // open, in the same order as when adding it below.
+            let layout = &self.niri.layout;
+            // Tabs and replacements open where the focused window is.
+            let next_to_focus =
+                rules.open_as_tab == Some(true) || rules.open_replacing_focused == Some(true);
+            let target_output = if let Some(name) = &workspace_name {
+                layout
+                    .find_workspace_by_name(name)
+                    .and_then(|ws| ws.output())
+            } else if next_to_focus {
+                layout.active_output()
+            } else if let Some(p) = &parent {
+                layout.window_location(p).map(|(output, _)| output)
+            } else {
+                output.as_ref().or_else(|| layout.active_output())
+            };
+
+            // Crowded workspaces send new columns to an empty workspace.
+            let target_ws = output
+                .as_ref()
+                .and_then(|o| layout.monitor_for_output(o))
+                .map(|mon| mon.active_workspace_ref())
+                .or_else(|| layout.active_workspace());
+            let workspace_is_full =
+                target_ws.map_or(false, |ws| is_workspace_full(&config, &rules, ws));
+
+            (
+                rules.default_width_for_output(target_output),
+                rules.default_height,
+                output,
+                workspace_name,
+                rules.open_floating,
+                rules.default_floating_position,
+                rules.open_as_tab,
+                rules.open_below_parent,
+                rules.open_replacing_focused,
+                rules.open_focused,
+                rules.focus_on_open_across_workspace,
+                rules.default_column_position,
+                rules.maximize_to.unwrap_or(config.maximize_to),
+                workspace_is_full,
+                in_scratchpad,
+            )
+        };
+
+        // Scratchpad windows stay out of the layout until toggled.
+        if in_scratchpad {
+            self.ipc_window_opened(&window);
+            self.niri.scratchpad.push(window);
+            return;
+        }
+
+        // Fullscreen windows go into the tiling layout.
+        let is_fullscreen = window
+            .toplevel()
+            .with_pending_state(|state| state.states.contains(xdg_toplevel::State::Fullscreen));
+        let is_floating = open_floating == Some(true) && !is_fullscreen;
+
+        // Windows can't take the place of fullscreen or floating windows.
+        let replaced = if open_replacing_focused == Some(true) && !is_floating {
+            let layout = &self.niri.layout;
+            layout
+                .focus()
+                .filter(|focus| !focus.is_fullscreen() && !layout.is_floating(focus))
+                .cloned()
+        } else {
+            None
+        };
+
+        let win = window.clone();
+        let activate = open_focused != Some(false);
+        let sent_elsewhere = workspace_name.is_some() || output.is_some();
+
+        let layout = &mut self.niri.layout;
+        let output = if let Some(name) = &workspace_name {
+            if is_floating {
+                layout.add_floating_window_to_named_workspace(name, win, activate)
+            } else {
+                layout.add_window_to_named_workspace(name, win, width, false, activate)
+            }
+        } else if is_floating {
+            if let Some(p) = &parent {
+                // Float dialogs over their parent.
+                layout.add_floating_window_over(p, win, activate)
+            } else {
+                layout.add_floating_window(win, output.as_ref(), activate)
+            }
+        } else if open_as_tab == Some(true) {
+            layout.add_window_as_tab(win, width, false, activate)
+        } else if let Some(replaced) = &replaced {
+            layout.add_window_right_of(replaced, win, width, false, activate)
+        } else if let Some(p) = parent.filter(|p| !layout.is_floating(p)) {
+            if open_below_parent == Some(true) {
+                // Stack the window below its parent, when the rule asks for it.
+                layout.add_window_below(&p, win, width, false, activate)
+            } else {
+                // Open dialogs immediately to the right of their parent window.
+                layout.add_window_right_of(&p, win, width, false, activate)
+            }
+        } else if workspace_is_full {
+            layout.add_window_on_empty_workspace(output.as_ref(), win, width, false, activate)
+        } else if let Some(output) = &output {
+            layout.add_window_on_output(output, win, width, false, activate);
+            Some(output)
+        } else {
+            layout.add_window(win, width, false, activate)
+        };
+
+        // The window might have requested to be maximized before mapping.
+        let is_maximized = window
+            .toplevel()
+            .with_pending_state(|state| state.states.contains(xdg_toplevel::State::Maximized));
+        let output = output.cloned();
+
+        // The new window sits right of the replaced one, and moves into its place once it closes.
+        if let Some(replaced) = replaced {
+            replaced.toplevel().send_close();
+        }
+        if is_maximized {
+            match maximize_to {
+                MaximizeTarget::Column => {
+                    self.niri.layout.set_maximized(&window, true);
+                }
+                MaximizeTarget::Monitor => {
+                    self.niri
+                        .layout
+                        .set_maximized_to_working_area(&window, true);
+                }
+            }
+        }
+
+        // New columns open after the focused one unless a rule places them elsewhere.
+        if let Some(position) = column_position {
+            if !is_floating && !is_fullscreen {
+                self.niri
+                    .layout
+                    .set_new_window_column_position(&window, position);
+            }
+        }
+
+        // Floating windows open centered unless a rule places them elsewhere.
+        let floating_position = floating_position.filter(|_| is_floating);
+        if let (Some(position), Some(output)) = (floating_position, &output) {
+            let pos = match position {
+                FloatingPosition::Center => None,
+                FloatingPosition::Fixed(x, y) => Some(Point::from((x, y))),
+                FloatingPosition::Cursor => {
+                    let output_geo = self.niri.global_space.output_geometry(output).unwrap();
+                    let pointer = self.niri.seat.get_pointer().unwrap();
+                    let pos = pointer.current_location() - output_geo.loc.to_f64();
+                    Some(pos.to_i32_round())
+                }
+            };
+
+            if let Some(pos) = pos {
+                self.niri.layout.set_floating_window_position(&window, pos);
+            }
+        }
+
+        if !is_floating && !is_fullscreen {
+            self.niri.layout.set_new_window_height(&window, height);
+        }
+
+        self.ipc_window_opened(&window);
+
+        // Windows sent to another workspace or output stay out of view unless a rule asks to
+        // follow them there.
+        if activate && sent_elsewhere && focus_across_workspace == Some(true) && output.is_some() {
+            self.niri.layout.activate_window(&window);
+        }
+
+        if let Some(output) = output {
+            self.niri.layout.start_open_animation_for_window(&window);
+            self.niri.queue_redraw(output);
+        }
+    }
+
+    pub fn send_initial_configure_if_needed(&mut self, window: &Window) {
+        if initial_configure_sent(window.toplevel()) {
+            return;
+        }
+
+        let _span = tracy_client::span!("State::send_initial_configure_if_needed");
+        self.configure_unmapped_window(window);
+    }
+
+    /// Configures unmapped windows that were configured while there was no workspace to size
+    /// them for.
+    ///
+    /// Called once an output appears, e.g. when a laptop with the lid closed gets docked.
+    pub fn configure_windows_waiting_for_output(&mut self) {
+        let windows: Vec<_> = self
+            .niri
+            .unmapped_windows
+            .values()
+            .filter(|window| is_waiting_for_output(window))
+            .cloned()
+            .collect();
+
+        for window in windows {
+            self.configure_unmapped_window(&window);
+        }
+    }
+
+    /// Computes and sends the configure of an unmapped window from its window rules.
+    fn configure_unmapped_window(&mut self, window: &Window) {
+        let toplevel = window.toplevel();
+
+        // This is the initial pass, which decides where and how the window opens.
+        let config = self.niri.config.borrow();
+        let rules = resolve_window_rules(&config, toplevel, &self.niri.display_handle);
+
+        let output = rules.open_on_connected_output(&self.niri.output_by_name);
+        let mon = output.map(|o| self.niri.layout.monitor_for_output(o).unwrap());
+        let ws = rules
+            .open_on_workspace
+            .and_then(|name| self.niri.layout.find_workspace_by_name(name))
+            .or_else(|| {
+                let ws = mon
+                    .map(|mon| mon.active_workspace_ref())
+                    .or_else(|| self.niri.layout.active_workspace())?;
+
+                // Crowded workspaces send new columns to an empty workspace.
+                if is_workspace_full(&config, &rules, ws) {
+                    self.niri.layout.empty_workspace(output)
+                } else {
+                    Some(ws)
+                }
+            });
+
+        // The window rule overrides whatever the client requested before the initial configure.
+        // Fullscreen takes precedence over maximized.
+        let (is_fullscreen, is_maximized) = toplevel.with_pending_state(|state| {
+            let is_fullscreen = rules
+                .open_fullscreen
+                .unwrap_or_else(|| state.states.contains(xdg_toplevel::State::Fullscreen));
+            let is_maximized = rules
+                .open_maximized
+                .unwrap_or_else(|| state.states.contains(xdg_toplevel::State::Maximized));
+            (is_fullscreen, is_maximized && !is_fullscreen)
+        });
+
+        // Fullscreen also takes precedence over floating, and floating windows have no column to
+        // maximize to.
+        let maximize_to = rules.maximize_to.unwrap_or(config.maximize_to);
+        let is_floating = rules.open_floating == Some(true) && !is_fullscreen;
+
+        // Scratchpad windows show up floating, so they are configured the same way.
+        let in_scratchpad = rules.open_in_scratchpad == Some(true);
+        let is_fullscreen = is_fullscreen && !in_scratchpad;
+        let is_floating = is_floating || in_scratchpad;
+        let is_maximized = is_maximized && (!is_floating || maximize_to == MaximizeTarget::Monitor);
+
+        // Maximized windows open as a full-width column.
+        let default_width = if is_maximized && maximize_to == MaximizeTarget::Column {
+            Some(Some(ColumnWidth::Proportion(1.)))
+        } else {
+            rules.default_width_for_output(ws.and_then(|ws| ws.output()))
+        };
+
+        // The scale override goes out with the preferred scale in the initial configure.
+        window
+            .user_data()
+            .get_or_insert(WindowRulesState::default)
+            .scale_override
+            .set(rules.scale_override);
+
+        // Without any workspace, there's nothing to size the window for yet, so configure it again
+        // once an output appears.
+        set_waiting_for_output(window, ws.is_none());
+
+        // Tell the surface the preferred size and bounds for its likely output.
+        if let Some(ws) = ws {
+            ws.configure_new_window(window, default_width, rules.default_height);
+            clamp_pending_width(toplevel, &rules);
+
+            // Fullscreen windows take up the whole view.
+            if is_fullscreen {
+                toplevel.with_pending_state(|state| {
+                    state.size = Some(ws.view_size());
+                });
+            }
+        }
+
+        // Tabs take the size of the active column, which is on the active workspace.
+        if rules.open_as_tab == Some(true) && !is_floating && !is_fullscreen {
+            if let Some(size) = self
+                .niri
+                .layout
+                .active_workspace()
+                .and_then(|ws| ws.new_tab_size())
+            {
+                toplevel.with_pending_state(|state| {
+                    state.size = Some(size);
+                });
+            }
+        }
+
+        // Floating windows pick their own size.
+        if is_floating {
+            toplevel.with_pending_state(|state| {
+                state.size = None;
+            });
+        }
+
+        // Windows maximized to the monitor cover its working area.
+        if is_maximized && maximize_to == MaximizeTarget::Monitor {
+            if let Some(ws) = ws {
+                toplevel.with_pending_state(|state| {
+                    state.size = Some(ws.maximized_window_size());
+                });
+            }
+        }
+
+        toplevel.with_pending_state(|state| {
+            if is_fullscreen {
+                state.states.set(xdg_toplevel::State::Fullscreen);
+            } else {
+                state.states.unset(xdg_toplevel::State::Fullscreen);
+            }
+
+            if is_maximized {
+                state.states.set(xdg_toplevel::State::Maximized);
+            } else {
+                state.states.unset(xdg_toplevel::State::Maximized);
+            }
+        });
+
+        // If the user prefers no CSD, it's a reasonable assumption that they would prefer to get
+        // rid of the various client-side rounded corners also by using the tiled state. Some apps
+        // look bad when tiled though, so the window rule can override this either way.
+        let tiled = rules
+            .tiled_state
+            .unwrap_or(config.prefer_no_csd && !is_floating);
+        toplevel.with_pending_state(|state| {
+            for tiled_state in [
+                xdg_toplevel::State::TiledLeft,
+                xdg_toplevel::State::TiledRight,
+                xdg_toplevel::State::TiledTop,
+                xdg_toplevel::State::TiledBottom,
+            ] {
+                if tiled {
+                    state.states.set(tiled_state);
+                } else {
+                    state.states.unset(tiled_state);
+                }
+            }
+        });
+
+        toplevel.send_configure();
+    }
+
+    /// Should be called on `WlSurface::commit`
+    pub fn popups_handle_commit(&mut self, surface: &WlSurface) {
+        self.niri.popups.commit(surface);
+
+        if let Some(popup) = self.niri.popups.find_popup(surface) {
+            match popup {
+                PopupKind::Xdg(ref popup) => {
+                    let initial_configure_sent = with_states(surface, |states| {
+                        states
+                            .data_map
+                            .get::<XdgPopupSurfaceData>()
+                            .unwrap()
+                            .lock()
+                            .unwrap()
+                            .initial_configure_sent
+                    });
+                    if !initial_configure_sent {
+                        self.send_popup_surface_state(&PopupKind::Xdg(popup.clone()));
+                        popup.send_configure().expect("initial configure failed");
+                    }
+                }
+                // Input method popups don't require a configure.
+                PopupKind::InputMethod(_) => (),
+            }
+
+            self.update_popup_outputs(&popup);
+        }
+    }
+
+    /// Sends wl_surface enter and leave events so that the popup knows which output it's on.
+    ///
+    /// The popup tree is considered to be fully on the popup's output.
+    pub fn update_popup_outputs(&self, popup: &PopupKind) {
+        let popup_output = self.output_for_popup(popup);
+
+        let surface = popup.wl_surface();
+        let bbox = bbox_from_surface_tree(surface, (0, 0));
+        for output in self.niri.global_space.outputs() {
+            if popup_output.as_ref() == Some(output) {
+                output_update(output, Some(bbox), surface);
+            } else {
+                output_update(output, None, surface);
+            }
+        }
+    }
+
+    pub fn output_for_popup(&self, popup: &PopupKind) -> Option<Output> {
+        let root = find_popup_root_surface(popup).ok();
+        if let Some(output) = root.and_then(|root| self.niri.output_for_root(&root)) {
+            return Some(output.clone());
+        }
+
+        // The root is unknown for popups with a NULL parent that hasn't been assigned yet (as
+        // layer-shell does), and for roots that aren't mapped anywhere. Such popups open next to
+        // the pointer, so its output is the best guess.
+        self.niri
+            .output_under_cursor()
+            .or_else(|| self.niri.layout.active_output().cloned())
+    }
+
+    /// Sends the scale and transform of the popup's output as preferred to the popup.
+    pub fn send_popup_surface_state(&self, popup: &PopupKind) {
+        let Some(output) = self.output_for_popup(popup) else {
+            return;
+        };
+
+        let scale = output.current_scale();
+        let transform = output.current_transform();
+        let surface = popup.wl_surface();
+        with_states(surface, |data| {
+            send_scale_transform(surface, data, scale, transform);
+        });
+    }
+
+    /// Repositions an open popup and sends it the new geometry.
+    ///
+    /// This is for when the compositor moves a popup, for example after its parent moved. Uses the
+    /// same unconstraining as reposition requests, so that the two never disagree.
+    pub fn reposition_popup(&self, popup: &PopupSurface) {
+        self.unconstrain_popup(popup);
+        if let Err(err) = popup.send_pending_configure() {
+            warn!("error re-configuring popup: {err:?}");
+        }
+    }
+
+    pub fn unconstrain_popup(&self, popup: &PopupSurface) {
+        let _span = tracy_client::span!("Niri::unconstrain_popup");
+
+        // Popups with a NULL parent will get repositioned in their respective protocol handlers
+        // (i.e. layer-shell).
+        let Ok(root) = find_popup_root_surface(&PopupKind::Xdg(popup.clone())) else {
+            return;
+        };
+
+        // Figure out if the root is a window or a layer surface.
+        if let Some((window, output)) = self.niri.layout.find_window_and_output(&root) {
+            self.unconstrain_window_popup(popup, window, output);
+        } else if let Some((layer_surface, output)) = self.niri.layout.outputs().find_map(|o| {
             let map = layer_map_for_output(o);
             let layer_surface = map.layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)?;
             Some((layer_surface.clone(), o))
@@ -536,21 +2123,62 @@ impl State {
 
     fn unconstrain_window_popup(&self, popup: &PopupSurface, window: &Window, output: &Output) {
         let window_geo = window.geometry();
+        let window_output_geo = self.niri.global_space.output_geometry(output).unwrap();
+        let window_loc = window_output_geo.loc
+            + Point::from((
+                self.niri.layout.window_x(window).unwrap(),
+                self.niri.layout.window_y(window).unwrap(),
+            ));
+        let popup_loc = get_popup_toplevel_coords(&PopupKind::Xdg(popup.clone()));
+
+        // A window can extend past the edge of its output, so keep the popup on the output where
+        // it is anchored, e.g. where a right click happened.
+        let anchor_rect = popup.with_pending_state(|state| state.positioner.anchor_rect);
+        let anchor_center = window_loc
+            + popup_loc
+            + anchor_rect.loc
+            + Point::from((anchor_rect.size.w / 2, anchor_rect.size.h / 2));
+        let root = window.toplevel().wl_surface();
+        let output = self
+            .niri
+            .output_for_root_at(root, anchor_center.to_f64())
+            .unwrap_or(output);
+
         let output_geo = self.niri.global_space.output_geometry(output).unwrap();
+        let window_x = window_loc.x - output_geo.loc.x;
+        let window_y = window_loc.y - output_geo.loc.y;
 
         // The target geometry for the positioner should be relative to its parent's geometry, so
         // we will compute that here.
         //
-        // We try to keep regular window popups within the window itself horizontally (since the
-        // window can be scrolled to both edges of the screen), but within the whole monitor's
-        // height.
+        // We try to keep regular window popups within the visible part of the window itself
+        // horizontally (since the window can be scrolled to both edges of the screen), but within
+        // the whole monitor's height.
+        let left = max(0, -window_x);
+        let right = min(window_geo.size.w, output_geo.size.w - window_x);
         let mut target =
-            Rectangle::from_loc_and_size((0, 0), (window_geo.size.w, output_geo.size.h));
-        target.loc.y -= self.niri.layout.window_y(window).unwrap();
-        target.loc -= get_popup_toplevel_coords(&PopupKind::Xdg(popup.clone()));
+            Rectangle::from_loc_and_size((left, -window_y), (right - left, output_geo.size.h));
+        target.loc -= popup_loc;
 
+        // Popups that don't fit there, like large completion lists or context menus of a window
+        // scrolled partially off-screen, may overflow onto the rest of the monitor instead.
+        let mut output_target =
+            Rectangle::from_loc_and_size((-window_x, -window_y), output_geo.size);
+        output_target.loc -= popup_loc;
+
+        let padding = i32::from(self.niri.config.borrow().popups.window_padding());
         popup.with_pending_state(|state| {
-            state.geometry = unconstrain_with_padding(state.positioner, target);
+            let mut geo = None;
+            if target.size.w > 0 {
+                let within_window = unconstrain_with_padding(state.positioner, target, padding);
+                if fits_horizontally(within_window, target) {
+                    geo = Some(within_window);
+                }
+            }
+
+            state.geometry = geo.unwrap_or_else(|| {
+                unconstrain_with_padding(state.positioner, output_target, padding)
+            });
         });
     }
 
@@ -572,22 +2200,27 @@ impl State {
         target.loc -= layer_geo.loc;
         target.loc -= get_popup_toplevel_coords(&PopupKind::Xdg(popup.clone()));
 
+        let padding = self.niri.config.borrow().popups.layer_shell_padding();
         popup.with_pending_state(|state| {
-            state.geometry = unconstrain_with_padding(state.positioner, target);
+            // The layer surface can extend past the output edge, so anchor to the visible part.
+            let positioner = clamp_anchor_rect(state.positioner, target);
+            state.geometry = unconstrain_with_padding(positioner, target, padding.into());
         });
     }
 
-    pub fn update_reactive_popups(&self, window: &Window, output: &Output) {
+    pub fn update_reactive_popups(&self, window: &Window) {
+        self.update_reactive_popups_for_root(window.toplevel().wl_surface());
+    }
+
+    /// Re-unconstrains the reactive popups of a window or a layer surface.
+    fn update_reactive_popups_for_root(&self, root: &WlSurface) {
         let _span = tracy_client::span!("Niri::update_reactive_popups");
 
-        for (popup, _) in PopupManager::popups_for_surface(window.toplevel().wl_surface()) {
+        for (popup, _) in PopupManager::popups_for_surface(root) {
             match popup {
                 PopupKind::Xdg(ref popup) => {
                     if popup.with_pending_state(|state| state.positioner.reactive) {
-                        self.unconstrain_window_popup(popup, window, output);
-                        if let Err(err) = popup.send_pending_configure() {
-                            warn!("error re-configuring reactive popup: {err:?}");
-                        }
+                        self.reposition_popup(popup);
                     }
                 }
                 PopupKind::InputMethod(_) => (),
@@ -596,22 +2229,25 @@ impl State {
     }
 }
 
+fn fits_horizontally(geo: Rectangle<i32, Logical>, target: Rectangle<i32, Logical>) -> bool {
+    target.loc.x <= geo.loc.x && geo.loc.x + geo.size.w <= target.loc.x + target.size.w
+}
+
 fn unconstrain_with_padding(
     positioner: PositionerState,
     target: Rectangle<i32, Logical>,
+    padding: i32,
 ) -> Rectangle<i32, Logical> {
     // Try unconstraining with a small padding first which looks nicer, then if it doesn't fit try
     // unconstraining without padding.
-    const PADDING: i32 = 8;
-
     let mut padded = target;
-    if PADDING * 2 < padded.size.w {
-        padded.loc.x += PADDING;
-        padded.size.w -= PADDING * 2;
+    if padding * 2 < padded.size.w {
+        padded.loc.x += padding;
+        padded.size.w -= padding * 2;
     }
-    if PADDING * 2 < padded.size.h {
-        padded.loc.y += PADDING;
-        padded.size.h -= PADDING * 2;
+    if padding * 2 < padded.size.h {
+        padded.loc.y += padding;
+        padded.size.h -= padding * 2;
     }
 
     // No padding, so just unconstrain with the original target.
@@ -619,6 +2255,11 @@ fn unconstrain_with_padding(
         return positioner.get_unconstrained_geometry(target);
     }
 
+    // Decide on flipping once against the real target, so that the padded and the regular passes
+    // don't put the popup on different sides. Otherwise a submenu that fits next to its parent
+    // menu could flip over to the other side just because it doesn't fit the padded target.
+    let positioner = resolve_flips(positioner, target);
+
     // Do not try to resize to fit the padded target rectangle.
     let mut no_resize = positioner;
     no_resize
@@ -636,3 +2277,212 @@ fn unconstrain_with_padding(
     // Could not unconstrain into the padded target, so resort to the regular one.
     positioner.get_unconstrained_geometry(target)
 }
+
+/// Clamps the anchor rectangle of the positioner to `target`.
+///
+/// An anchor rectangle entirely outside of `target` is collapsed onto its closest edge.
+fn clamp_anchor_rect(
+    mut positioner: PositionerState,
+    target: Rectangle<i32, Logical>,
+) -> PositionerState {
+    let rect = positioner.anchor_rect;
+    let (min_x, max_x) = (target.loc.x, target.loc.x + target.size.w);
+    let (min_y, max_y) = (target.loc.y, target.loc.y + target.size.h);
+
+    let x1 = rect.loc.x.clamp(min_x, max_x);
+    let y1 = rect.loc.y.clamp(min_y, max_y);
+    let x2 = (rect.loc.x + rect.size.w).clamp(min_x, max_x);
+    let y2 = (rect.loc.y + rect.size.h).clamp(min_y, max_y);
+
+    positioner.anchor_rect = Rectangle::from_loc_and_size((x1, y1), (x2 - x1, y2 - y1));
+    positioner
+}
+
+/// Applies the flips that unconstraining into `target` would do and removes the flip adjustments.
+fn resolve_flips(
+    mut positioner: PositionerState,
+    target: Rectangle<i32, Logical>,
+) -> PositionerState {
+    let geo = positioner.get_geometry();
+
+    if positioner
+        .constraint_adjustment
+        .contains(ConstraintAdjustment::FlipX)
+    {
+        let mut flip_only = positioner;
+        flip_only.constraint_adjustment = ConstraintAdjustment::FlipX;
+        if flip_only.get_unconstrained_geometry(target).loc.x != geo.loc.x {
+            positioner.anchor_edges = invert_anchor_x(positioner.anchor_edges);
+            positioner.gravity = invert_gravity_x(positioner.gravity);
+        }
+    }
+
+    if positioner
+        .constraint_adjustment
+        .contains(ConstraintAdjustment::FlipY)
+    {
+        let mut flip_only = positioner;
+        flip_only.constraint_adjustment = ConstraintAdjustment::FlipY;
+        if flip_only.get_unconstrained_geometry(target).loc.y != geo.loc.y {
+            positioner.anchor_edges = invert_anchor_y(positioner.anchor_edges);
+            positioner.gravity = invert_gravity_y(positioner.gravity);
+        }
+    }
+
+    positioner
+        .constraint_adjustment
+        .remove(ConstraintAdjustment::FlipX | ConstraintAdjustment::FlipY);
+    positioner
+}
+
+fn invert_anchor_x(anchor: Anchor) -> Anchor {
+    match anchor {
+        Anchor::Left => Anchor::Right,
+        Anchor::Right => Anchor::Left,
+        Anchor::TopLeft => Anchor::TopRight,
+        Anchor::TopRight => Anchor::TopLeft,
+        Anchor::BottomLeft => Anchor::BottomRight,
+        Anchor::BottomRight => Anchor::BottomLeft,
+        x => x,
+    }
+}
+
+fn invert_anchor_y(anchor: Anchor) -> Anchor {
+    match anchor {
+        Anchor::Top => Anchor::Bottom,
+        Anchor::Bottom => Anchor::Top,
+        Anchor::TopLeft => Anchor::BottomLeft,
+        Anchor::BottomLeft => Anchor::TopLeft,
+        Anchor::TopRight => Anchor::BottomRight,
+        Anchor::BottomRight => Anchor::TopRight,
+        x => x,
+    }
+}
+
+fn invert_gravity_x(gravity: Gravity) -> Gravity {
+    match gravity {
+        Gravity::Left => Gravity::Right,
+        Gravity::Right => Gravity::Left,
+        Gravity::TopLeft => Gravity::TopRight,
+        Gravity::TopRight => Gravity::TopLeft,
+        Gravity::BottomLeft => Gravity::BottomRight,
+        Gravity::BottomRight => Gravity::BottomLeft,
+        x => x,
+    }
+}
+
+fn invert_gravity_y(gravity: Gravity) -> Gravity {
+    match gravity {
+        Gravity::Top => Gravity::Bottom,
+        Gravity::Bottom => Gravity::Top,
+        Gravity::TopLeft => Gravity::BottomLeft,
+        Gravity::BottomLeft => Gravity::TopLeft,
+        Gravity::TopRight => Gravity::BottomRight,
+        Gravity::BottomRight => Gravity::TopRight,
+        x => x,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submenu_positioner(anchor_x: i32) -> PositionerState {
+        // A submenu opening to the right of a 200 px wide parent menu item.
+        PositionerState {
+            rect_size: (115, 300).into(),
+            anchor_rect: Rectangle::from_loc_and_size((anchor_x, 100), (200, 30)),
+            anchor_edges: Anchor::TopRight,
+            gravity: Gravity::BottomRight,
+            constraint_adjustment: ConstraintAdjustment::FlipX
+                | ConstraintAdjustment::SlideY
+                | ConstraintAdjustment::ResizeX,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn submenu_at_right_edge_does_not_flip_for_padding() {
+        let target = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+
+        // The submenu fits the target, but not the padded target. Flipping it to the left would
+        // fit the padded target, but the submenu should stay where it fits on the right.
+        let geo = unconstrain_with_padding(submenu_positioner(1600), target, 8);
+        assert_eq!(geo.loc.x, 1800);
+
+        // The submenu doesn't fit the target on the right, so it flips in both passes.
+        let geo = unconstrain_with_padding(submenu_positioner(1700), target, 8);
+        assert_eq!(geo.loc.x, 1700 - 115);
+    }
+
+    #[derive(Default)]
+    struct TestToplevel {
+        initial_configure_sent: Cell<bool>,
+        pending_decoration_mode: Cell<Option<zxdg_toplevel_decoration_v1::Mode>>,
+        // Decoration modes of the configures sent so far.
+        configures: RefCell<Vec<Option<zxdg_toplevel_decoration_v1::Mode>>>,
+    }
+
+    impl ConfigureToplevel for TestToplevel {
+        fn initial_configure_sent(&self) -> bool {
+            self.initial_configure_sent.get()
+        }
+
+        fn send_configure(&self) {
+            self.initial_configure_sent.set(true);
+            self.configures
+                .borrow_mut()
+                .push(self.pending_decoration_mode.get());
+        }
+    }
+
+    #[test]
+    fn decoration_request_before_initial_configure() {
+        use zxdg_toplevel_decoration_v1::Mode;
+
+        let toplevel = TestToplevel::default();
+
+        // A server-side decoration request before the initial configure goes out with it.
+        toplevel.pending_decoration_mode.set(Some(Mode::ServerSide));
+        send_configure_unless_initial_pending(&toplevel);
+        assert!(toplevel.configures.borrow().is_empty());
+
+        toplevel.send_configure();
+        assert_eq!(*toplevel.configures.borrow(), [Some(Mode::ServerSide)]);
+
+        // After the initial configure, requests get a reply right away.
+        toplevel.pending_decoration_mode.set(Some(Mode::ClientSide));
+        send_configure_unless_initial_pending(&toplevel);
+        assert_eq!(
+            *toplevel.configures.borrow(),
+            [Some(Mode::ServerSide), Some(Mode::ClientSide)]
+        );
+    }
+
+    #[test]
+    fn anchor_rect_clamped_to_target() {
+        let target = Rectangle::from_loc_and_size((0, -1040), (1920, 1080));
+        let positioner = |loc: (i32, i32)| PositionerState {
+            anchor_rect: Rectangle::from_loc_and_size(loc, (100, 60)),
+            ..Default::default()
+        };
+
+        // A bar button extending past the bottom edge anchors to its visible part.
+        let clamped = clamp_anchor_rect(positioner((10, 0)), target);
+        assert_eq!(
+            clamped.anchor_rect,
+            Rectangle::from_loc_and_size((10, 0), (100, 40))
+        );
+
+        // A button fully past the right edge collapses onto the edge.
+        let clamped = clamp_anchor_rect(positioner((2000, -100)), target);
+        assert_eq!(
+            clamped.anchor_rect,
+            Rectangle::from_loc_and_size((1920, -100), (0, 60))
+        );
+
+        // A fully visible button is left alone.
+        let clamped = clamp_anchor_rect(positioner((10, -100)), target);
+        assert_eq!(clamped.anchor_rect, positioner((10, -100)).anchor_rect);
+    }
+}
@@ -22,10 +22,12 @@ use smithay::utils::{Logical, Point, SERIAL_COUNTER};
 use smithay::wayland::pointer_constraints::{with_pointer_constraint, PointerConstraint};
 use smithay::wayland::tablet_manager::{TabletDescriptor, TabletSeatTrait};
 
-use crate::niri::State;
+use crate::niri::{State, WindowRulesState};
 use crate::screenshot_ui::ScreenshotUi;
 use crate::utils::{center, get_monotonic_time, spawn};
 
+pub mod resize_grab;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompositorMod {
     Super,
@@ -314,6 +316,9 @@ impl State {
                 self.backend.toggle_debug_tint();
                 self.niri.queue_redraw_all();
             }
+            Action::ReleaseGrabs => {
+                self.release_grabs();
+            }
             Action::Spawn(command) => {
                 spawn(command);
             }
@@ -358,6 +363,12 @@ impl State {
                 self.backend.with_primary_renderer(|renderer| {
                     self.niri.open_screenshot_ui(renderer);
                 });
+
+                // Like the lock screen, the screenshot UI takes over input, so a menu that was
+                // open can't keep its grab.
+                if self.niri.screenshot_ui.is_open() {
+                    self.ungrab_popups();
+                }
             }
             Action::ScreenshotWindow => {
                 let active = self.niri.layout.active_window();
@@ -382,6 +393,9 @@ impl State {
                     self.niri.queue_redraw_all();
                 }
             }
+            Action::ToggleWindowAlwaysOnTop => {
+                self.toggle_focused_window_always_on_top();
+            }
             Action::SwitchLayout(action) => {
                 self.niri.seat.get_keyboard().unwrap().with_xkb_state(
                     self,
@@ -557,6 +571,30 @@ impl State {
                 // FIXME: granular
                 self.niri.queue_redraw_all();
             }
+            Action::ToggleColumnTabbedDisplay => {
+                self.niri.layout.toggle_column_tabbed_display();
+                // FIXME: granular
+                self.niri.queue_redraw_all();
+            }
+            Action::FocusTabNext => {
+                self.niri.layout.focus_tab_next();
+                // FIXME: granular
+                self.niri.queue_redraw_all();
+            }
+            Action::FocusTabPrevious => {
+                self.niri.layout.focus_tab_previous();
+                // FIXME: granular
+                self.niri.queue_redraw_all();
+            }
+            Action::ToggleScratchpad => {
+                self.toggle_scratchpad();
+            }
+            Action::MarkWindow(mark) => {
+                self.mark_focused_window(mark);
+            }
+            Action::FocusMarkedWindow(mark) => {
+                self.focus_marked_window(&mark);
+            }
             Action::MaximizeColumn => {
                 self.niri.layout.toggle_full_width();
             }
@@ -852,6 +890,8 @@ impl State {
 
         pointer.frame(self);
 
+        self.focus_window_entered_by_pointer(pos, new_pos);
+
         // Redraw to update the cursor position.
         // FIXME: redraw only outputs overlapping the cursor.
         self.niri.queue_redraw_all();
@@ -893,6 +933,7 @@ impl State {
         self.niri.pointer_focus = under.clone();
         let under = under.map(|u| u.surface);
 
+        let old_pos = pointer.current_location();
         pointer.motion(
             self,
             under,
@@ -905,6 +946,8 @@ impl State {
 
         pointer.frame(self);
 
+        self.focus_window_entered_by_pointer(old_pos, pos);
+
         // We moved the regular pointer, so show it now.
         self.niri.tablet_cursor_location = None;
 
@@ -913,6 +956,45 @@ impl State {
         self.niri.queue_redraw_all();
     }
 
+    /// Focuses the window that the pointer moved onto, if focus follows the mouse for it.
+    ///
+    /// Only moving onto a different window focuses it, so that windows focused with the keyboard
+    /// keep focus while the pointer moves around inside another window.
+    fn focus_window_entered_by_pointer(
+        &mut self,
+        old_pos: Point<f64, Logical>,
+        new_pos: Point<f64, Logical>,
+    ) {
+        // Don't take focus away from open menus or from drags.
+        if !self.niri.popup_grab.is_empty() || self.niri.seat.get_pointer().unwrap().is_grabbed() {
+            return;
+        }
+
+        let Some(window) = self.niri.window_under(new_pos) else {
+            return;
+        };
+        if self.niri.window_under(old_pos) == Some(window)
+            || self.niri.layout.focus() == Some(window)
+        {
+            return;
+        }
+
+        let enabled = window
+            .user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.focus_follows_mouse.get())
+            .unwrap_or(self.niri.config.borrow().input.focus_follows_mouse);
+        if !enabled {
+            return;
+        }
+
+        let window = window.clone();
+        self.niri.layout.activate_window(&window);
+
+        // FIXME: granular.
+        self.niri.queue_redraw_all();
+    }
+
     fn on_pointer_button<I: InputBackend>(&mut self, event: I::PointerButtonEvent) {
         let pointer = self.niri.seat.get_pointer().unwrap();
 
@@ -939,6 +1021,10 @@ impl State {
 
         self.update_pointer_focus();
 
+        if ButtonState::Pressed == button_state {
+            self.dismiss_ime_blocked_popups_on_click();
+        }
+
         if let Some(button) = event.button() {
             let pos = pointer.current_location();
             if let Some((output, _)) = self.niri.output_under(pos) {
@@ -1,10 +1,13 @@
 use std::env;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Write};
 use std::net::Shutdown;
 use std::os::unix::net::UnixStream;
 
 use anyhow::{anyhow, bail, Context};
-use niri_ipc::{Mode, Output, Reply, Request, Response};
+use niri_ipc::{
+    Event, Mode, Output, PopupGrab, Reply, Request, ResolvedWindowRule, Response, RuleSize, Window,
+    WindowInfo, WindowMatch, WindowRules,
+};
 
 use crate::cli::Msg;
 
@@ -22,8 +25,16 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
     let request = match &msg {
         Msg::Outputs => Request::Outputs,
         Msg::Action { action } => Request::Action(action.clone()),
+        Msg::FocusedWindowRules => Request::FocusedWindowRules,
+        Msg::UrgentWindows => Request::UrgentWindows,
+        Msg::CloseWindows { app_id, title } => Request::CloseWindows(WindowMatch {
+            app_id: app_id.clone(),
+            title: title.clone(),
+        }),
+        Msg::EventStream => Request::EventStream,
+        Msg::PopupGrab => Request::PopupGrab,
     };
-    let mut buf = serde_json::to_vec(&request).unwrap();
+    let buf = serde_json::to_vec(&request).unwrap();
     stream
         .write_all(&buf)
         .context("error writing IPC request")?;
@@ -31,12 +42,14 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
         .shutdown(Shutdown::Write)
         .context("error closing IPC stream for writing")?;
 
-    buf.clear();
-    stream
-        .read_to_end(&mut buf)
+    // The reply comes on its own line, possibly followed by more lines with events.
+    let mut reader = BufReader::new(stream);
+    let mut buf = String::new();
+    reader
+        .read_line(&mut buf)
         .context("error reading IPC response")?;
 
-    let reply: Reply = serde_json::from_slice(&buf).context("error parsing IPC reply")?;
+    let reply: Reply = serde_json::from_str(&buf).context("error parsing IPC reply")?;
 
     let response = reply
         .map_err(|msg| anyhow!(msg))
@@ -109,7 +122,320 @@ pub fn handle_msg(msg: Msg, json: bool) -> anyhow::Result<()> {
                 bail!("unexpected response: expected Handled, got {response:?}");
             };
         }
+        Msg::FocusedWindowRules => {
+            let Response::FocusedWindowRules(rules) = response else {
+                bail!("unexpected response: expected FocusedWindowRules, got {response:?}");
+            };
+
+            if json {
+                let rules = serde_json::to_string(&rules).context("error formatting response")?;
+                println!("{rules}");
+                return Ok(());
+            }
+
+            let Some(rules) = rules else {
+                println!("No window is focused.");
+                return Ok(());
+            };
+
+            let WindowRules {
+                title,
+                app_id,
+                matched,
+                resolved,
+            } = rules;
+
+            let title = title.as_deref().unwrap_or("(unset)");
+            let app_id = app_id.as_deref().unwrap_or("(unset)");
+            println!(r#"Window "{title}" (app-id "{app_id}")"#);
+
+            if matched.is_empty() {
+                println!("  No window rules matched");
+            } else {
+                // Count from one to match how people refer to rules in the config.
+                let matched = matched
+                    .into_iter()
+                    .map(|idx| format!("#{}", idx + 1))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                println!("  Matched window rules: {matched}");
+            }
+
+            print_resolved_window_rule(&resolved);
+        }
+        Msg::UrgentWindows => {
+            let Response::UrgentWindows(windows) = response else {
+                bail!("unexpected response: expected UrgentWindows, got {response:?}");
+            };
+
+            if json {
+                let windows =
+                    serde_json::to_string(&windows).context("error formatting response")?;
+                println!("{windows}");
+                return Ok(());
+            }
+
+            if windows.is_empty() {
+                println!("No windows requested attention.");
+                return Ok(());
+            }
+
+            for window in windows {
+                let Window {
+                    title,
+                    app_id,
+                    output,
+                } = window;
+
+                let title = title.as_deref().unwrap_or("(unset)");
+                let app_id = app_id.as_deref().unwrap_or("(unset)");
+                println!(r#"Window "{title}" (app-id "{app_id}")"#);
+
+                if let Some(output) = output {
+                    println!("  Output: {output}");
+                }
+            }
+        }
+        Msg::CloseWindows { .. } => {
+            let Response::ClosedWindows(count) = response else {
+                bail!("unexpected response: expected ClosedWindows, got {response:?}");
+            };
+
+            if json {
+                let count = serde_json::to_string(&count).context("error formatting response")?;
+                println!("{count}");
+                return Ok(());
+            }
+
+            println!("Asked {count} window(s) to close.");
+        }
+        Msg::EventStream => {
+            let Response::Handled = response else {
+                bail!("unexpected response: expected Handled, got {response:?}");
+            };
+
+            for line in reader.lines() {
+                let line = line.context("error reading IPC event")?;
+
+                if json {
+                    println!("{line}");
+                    continue;
+                }
+
+                let event: Event = serde_json::from_str(&line).context("error parsing event")?;
+                match event {
+                    Event::WindowOpened(window) => print_window_event("opened", &window),
+                    Event::WindowChanged(window) => print_window_event("changed", &window),
+                    Event::WindowClosed { id } => println!("Window {id} closed"),
+                }
+            }
+        }
+        Msg::PopupGrab => {
+            let Response::PopupGrab(grab) = response else {
+                bail!("unexpected response: expected PopupGrab, got {response:?}");
+            };
+
+            if json {
+                let grab = serde_json::to_string(&grab).context("error formatting response")?;
+                println!("{grab}");
+                return Ok(());
+            }
+
+            let Some(grab) = grab else {
+                println!("No popup grab is active.");
+                return Ok(());
+            };
+
+            let PopupGrab {
+                root_title,
+                root_app_id,
+                root_layer_namespace,
+                serial,
+                previous_serial,
+                has_ended,
+            } = grab;
+
+            if let Some(namespace) = root_layer_namespace {
+                println!(r#"Popup grab on layer surface "{namespace}""#);
+            } else {
+                let title = root_title.as_deref().unwrap_or("(unset)");
+                let app_id = root_app_id.as_deref().unwrap_or("(unset)");
+                println!(r#"Popup grab on window "{title}" (app-id "{app_id}")"#);
+            }
+
+            println!("  Serial: {serial}");
+            if let Some(previous_serial) = previous_serial {
+                println!("  Previous serial: {previous_serial}");
+            }
+            if has_ended {
+                println!("  Ended, waiting to be cleaned up");
+            }
+        }
     }
 
     Ok(())
 }
+
+fn print_window_event(what: &str, window: &WindowInfo) {
+    let WindowInfo {
+        id,
+        title,
+        app_id,
+        workspace_id,
+    } = window;
+
+    let title = title.as_deref().unwrap_or("(unset)");
+    let app_id = app_id.as_deref().unwrap_or("(unset)");
+    print!(r#"Window {id} {what}: "{title}" (app-id "{app_id}")"#);
+    match workspace_id {
+        Some(workspace_id) => println!(" on workspace {workspace_id}"),
+        None => println!(),
+    }
+}
+
+fn print_resolved_window_rule(rule: &ResolvedWindowRule) {
+    let ResolvedWindowRule {
+        default_column_width,
+        max_auto_width,
+        default_window_height,
+        open_on_output,
+        open_on_workspace,
+        reapply_open_on_output,
+        open_maximized,
+        open_fullscreen,
+        open_floating,
+        default_floating_position,
+        open_as_tab,
+        open_below_parent,
+        open_replacing_focused,
+        open_in_scratchpad,
+        open_focused,
+        focus_on_open_across_workspace,
+        default_column_position,
+        max_columns,
+        max_windows_per_column,
+        mark,
+        tiled_state,
+        prefer_csd,
+        force_decoration,
+        activation_focus,
+        maximize_to,
+        color_scheme,
+        block_out_from,
+        opacity,
+        border_active_color,
+        border_inactive_color,
+        min_width,
+        max_width,
+        scale_override,
+        always_on_top,
+        focus_follows_mouse,
+        popup_pointer_grab,
+        aspect_ratio,
+    } = rule;
+
+    let size = |size: &RuleSize| match size {
+        RuleSize::WindowChosen => String::from("chosen by the window"),
+        RuleSize::Proportion(p) => format!("{}%", p * 100.),
+        RuleSize::Fixed(f) => format!("{f} px"),
+    };
+    let color = |[r, g, b, a]: &[u8; 4]| format!("{r} {g} {b} {a}");
+
+    // Use the config names so that the properties can be copied into new rules.
+    let properties = [
+        (
+            "default-column-width",
+            default_column_width.as_ref().map(size),
+        ),
+        ("max-auto-width", max_auto_width.map(|x| x.to_string())),
+        (
+            "default-window-height",
+            default_window_height.as_ref().map(size),
+        ),
+        (
+            "open-on-output",
+            open_on_output.as_ref().map(|x| x.join(" ")),
+        ),
+        ("open-on-workspace", open_on_workspace.clone()),
+        (
+            "reapply-open-on-output",
+            reapply_open_on_output.map(|x| x.to_string()),
+        ),
+        ("open-maximized", open_maximized.map(|x| x.to_string())),
+        ("open-fullscreen", open_fullscreen.map(|x| x.to_string())),
+        ("open-floating", open_floating.map(|x| x.to_string())),
+        (
+            "default-floating-position",
+            default_floating_position.clone(),
+        ),
+        ("open-as-tab", open_as_tab.map(|x| x.to_string())),
+        (
+            "open-below-parent",
+            open_below_parent.map(|x| x.to_string()),
+        ),
+        (
+            "open-replacing-focused",
+            open_replacing_focused.map(|x| x.to_string()),
+        ),
+        (
+            "open-in-scratchpad",
+            open_in_scratchpad.map(|x| x.to_string()),
+        ),
+        ("open-focused", open_focused.map(|x| x.to_string())),
+        (
+            "focus-on-open-across-workspace",
+            focus_on_open_across_workspace.map(|x| x.to_string()),
+        ),
+        ("default-column-position", default_column_position.clone()),
+        ("max-columns", max_columns.map(|x| x.to_string())),
+        (
+            "max-windows-per-column",
+            max_windows_per_column.map(|x| x.to_string()),
+        ),
+        ("mark", mark.clone()),
+        ("tiled-state", tiled_state.map(|x| x.to_string())),
+        ("prefer-csd", prefer_csd.map(|x| x.to_string())),
+        ("force-decoration", force_decoration.clone()),
+        ("activation-focus", activation_focus.clone()),
+        ("maximize-to", maximize_to.clone()),
+        ("color-scheme", color_scheme.clone()),
+        ("block-out-from", block_out_from.clone()),
+        ("opacity", opacity.map(|x| x.to_string())),
+        (
+            "border-active-color",
+            border_active_color.as_ref().map(color),
+        ),
+        (
+            "border-inactive-color",
+            border_inactive_color.as_ref().map(color),
+        ),
+        ("min-width", min_width.map(|x| x.to_string())),
+        ("max-width", max_width.map(|x| x.to_string())),
+        ("scale-override", scale_override.map(|x| x.to_string())),
+        ("always-on-top", always_on_top.map(|x| x.to_string())),
+        (
+            "focus-follows-mouse",
+            focus_follows_mouse.map(|x| x.to_string()),
+        ),
+        (
+            "popup-pointer-grab",
+            popup_pointer_grab.map(|x| x.to_string()),
+        ),
+        ("aspect-ratio", aspect_ratio.map(|x| x.to_string())),
+    ];
+
+    let mut any = false;
+    for (name, value) in properties {
+        let Some(value) = value else { continue };
+
+        if !any {
+            println!("  Resolved properties:");
+            any = true;
+        }
+        println!("    {name}: {value}");
+    }
+
+    if !any {
+        println!("  No properties set");
+    }
+}
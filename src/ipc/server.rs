@@ -5,25 +5,32 @@ use std::path::PathBuf;
 use std::rc::Rc;
 use std::{env, io, process};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use calloop::io::Async;
 use directories::BaseDirs;
 use futures_util::io::{AsyncReadExt, BufReader};
 use futures_util::{AsyncBufReadExt, AsyncWriteExt};
-use niri_ipc::{Request, Response};
+use niri_config::Match;
+use niri_ipc::{Event, Request, Response, WindowMatch};
+use regex::Regex;
 use smithay::reexports::calloop::generic::Generic;
 use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
 use smithay::reexports::rustix::fs::unlink;
 
 use crate::niri::State;
 
+/// How many events can queue up for an event stream client before it gets disconnected.
+const EVENT_STREAM_BUFFER_SIZE: usize = 64;
+
 pub struct IpcServer {
     pub socket_path: PathBuf,
+    event_streams: Rc<RefCell<Vec<async_channel::Sender<Event>>>>,
 }
 
 struct ClientCtx {
     event_loop: LoopHandle<'static, State>,
     ipc_outputs: Rc<RefCell<HashMap<String, niri_ipc::Output>>>,
+    event_streams: Rc<RefCell<Vec<async_channel::Sender<Event>>>>,
 }
 
 impl IpcServer {
@@ -55,7 +62,26 @@ impl IpcServer {
             })
             .unwrap();
 
-        Ok(Self { socket_path })
+        Ok(Self {
+            socket_path,
+            event_streams: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+
+    /// Sends an event to all event stream clients.
+    pub fn send_event(&self, event: Event) {
+        self.event_streams.borrow_mut().retain(|tx| {
+            match tx.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(async_channel::TrySendError::Full(_)) => {
+                    // Don't let a stuck client make the events pile up.
+                    warn!("IPC event stream client is too slow, disconnecting");
+                    tx.close();
+                    false
+                }
+                Err(async_channel::TrySendError::Closed(_)) => false,
+            }
+        });
     }
 }
 
@@ -85,9 +111,13 @@ fn on_new_ipc_client(state: &mut State, stream: UnixStream) {
         }
     };
 
+    // The client connected through the server, so it exists.
+    let ipc_server = state.niri.ipc_server.as_ref().unwrap();
+
     let ctx = ClientCtx {
         event_loop: state.niri.event_loop.clone(),
         ipc_outputs: state.backend.ipc_outputs(),
+        event_streams: ipc_server.event_streams.clone(),
     };
 
     let future = async move {
@@ -110,20 +140,49 @@ async fn handle_client(ctx: ClientCtx, stream: Async<'_, UnixStream>) -> anyhow:
         .await
         .context("error reading request")?;
 
-    let reply = process(&ctx, &buf).map_err(|err| {
+    let request = serde_json::from_str(&buf).context("error parsing request");
+    let is_event_stream = matches!(request, Ok(Request::EventStream));
+
+    let reply = match request {
+        Ok(request) => process(&ctx, request).await,
+        Err(err) => Err(err),
+    };
+    let reply = reply.map_err(|err| {
         warn!("error processing IPC request: {err:?}");
         err.to_string()
     });
 
-    let buf = serde_json::to_vec(&reply).context("error formatting reply")?;
+    // Subscribe before replying, so that no events are missed in between.
+    let events = if is_event_stream {
+        let (tx, rx) = async_channel::bounded(EVENT_STREAM_BUFFER_SIZE);
+        ctx.event_streams.borrow_mut().push(tx);
+        Some(rx)
+    } else {
+        None
+    };
+
+    let mut buf = serde_json::to_vec(&reply).context("error formatting reply")?;
+    buf.push(b'\n');
     write.write_all(&buf).await.context("error writing reply")?;
 
+    let Some(events) = events else {
+        return Ok(());
+    };
+
+    while let Ok(event) = events.recv().await {
+        let mut buf = serde_json::to_vec(&event).context("error formatting event")?;
+        buf.push(b'\n');
+        if let Err(err) = write.write_all(&buf).await {
+            // The client went away.
+            debug!("error writing IPC event: {err:?}");
+            break;
+        }
+    }
+
     Ok(())
 }
 
-fn process(ctx: &ClientCtx, buf: &str) -> anyhow::Result<Response> {
-    let request: Request = serde_json::from_str(buf).context("error parsing request")?;
-
+async fn process(ctx: &ClientCtx, request: Request) -> anyhow::Result<Response> {
     let response = match request {
         Request::Outputs => {
             let ipc_outputs = ctx.ipc_outputs.borrow().clone();
@@ -136,7 +195,68 @@ fn process(ctx: &ClientCtx, buf: &str) -> anyhow::Result<Response> {
             });
             Response::Handled
         }
+        Request::FocusedWindowRules => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let rules = state.focused_window_rules();
+                let _ = tx.send_blocking(rules);
+            });
+            let rules = rx
+                .recv()
+                .await
+                .context("error getting focused window rules")?;
+            Response::FocusedWindowRules(rules)
+        }
+        Request::UrgentWindows => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let windows = state.urgent_windows();
+                let _ = tx.send_blocking(windows);
+            });
+            let windows = rx.recv().await.context("error getting urgent windows")?;
+            Response::UrgentWindows(windows)
+        }
+        Request::CloseWindows(window_match) => {
+            let m = parse_window_match(window_match)?;
+
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let count = state.close_matching_windows(&m);
+                let _ = tx.send_blocking(count);
+            });
+            let count = rx.recv().await.context("error closing windows")?;
+            Response::ClosedWindows(count)
+        }
+        Request::EventStream => Response::Handled,
+        Request::PopupGrab => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                let grab = state.popup_grab_info();
+                let _ = tx.send_blocking(grab);
+            });
+            let grab = rx.recv().await.context("error getting popup grab")?;
+            Response::PopupGrab(grab)
+        }
     };
 
     Ok(response)
 }
+
+fn parse_window_match(window_match: WindowMatch) -> anyhow::Result<Match> {
+    let WindowMatch { app_id, title } = window_match;
+    if app_id.is_none() && title.is_none() {
+        bail!("the match must have an app ID or a title");
+    }
+
+    let parse = |re: Option<String>| {
+        re.map(|re| Regex::new(&re))
+            .transpose()
+            .context("invalid regular expression")
+    };
+
+    Ok(Match {
+        app_id: parse(app_id)?.into_iter().collect(),
+        title: parse(title)?.into_iter().collect(),
+        ..Default::default()
+    })
+}
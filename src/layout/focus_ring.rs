@@ -15,6 +15,10 @@ pub struct FocusRing {
     width: i32,
     active_color: Color,
     inactive_color: Color,
+    urgent_color: Color,
+    active_color_override: Option<Color>,
+    inactive_color_override: Option<Color>,
+    is_urgent: bool,
 }
 
 pub type FocusRingRenderElement = SolidColorRenderElement;
@@ -29,6 +33,10 @@ impl FocusRing {
             width: config.width.into(),
             active_color: config.active_color,
             inactive_color: config.inactive_color,
+            urgent_color: config.urgent_color,
+            active_color_override: None,
+            inactive_color_override: None,
+            is_urgent: false,
         }
     }
 
@@ -37,6 +45,7 @@ impl FocusRing {
         self.width = config.width.into();
         self.active_color = config.active_color;
         self.inactive_color = config.inactive_color;
+        self.urgent_color = config.urgent_color;
     }
 
     pub fn update(
@@ -64,12 +73,25 @@ impl FocusRing {
         self.is_border = is_border;
     }
 
+    /// Sets colors that take precedence over the ones from the config.
+    pub fn set_color_overrides(&mut self, active: Option<Color>, inactive: Option<Color>) {
+        self.active_color_override = active;
+        self.inactive_color_override = inactive;
+    }
+
+    pub fn set_urgent(&mut self, is_urgent: bool) {
+        self.is_urgent = is_urgent;
+    }
+
     pub fn set_active(&mut self, is_active: bool) {
-        let color = if is_active {
-            self.active_color.into()
+        let color = if self.is_urgent && !is_active {
+            self.urgent_color
+        } else if is_active {
+            self.active_color_override.unwrap_or(self.active_color)
         } else {
-            self.inactive_color.into()
+            self.inactive_color_override.unwrap_or(self.inactive_color)
         };
+        let color = color.into();
 
         for buf in &mut self.buffers {
             buf.set_color(color);
@@ -29,37 +29,44 @@
 //! compromise we only keep the first workspace there, and move the rest to the primary output,
 //! making the primary output their original output.
 
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::mem;
 use std::rc::Rc;
 use std::time::Duration;
 
-use niri_config::{self, CenterFocusedColumn, Config, Struts};
+use niri_config::{self, CenterFocusedColumn, Color, ColumnPosition, Config, Struts};
 use niri_ipc::SizeChange;
 use smithay::backend::renderer::element::solid::SolidColorRenderElement;
 use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
-use smithay::backend::renderer::element::{AsRenderElements, Id};
+use smithay::backend::renderer::element::{Id, Kind};
 use smithay::desktop::space::SpaceElement;
 use smithay::desktop::Window;
-use smithay::output::Output;
+use smithay::output::{self, Output};
 use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1;
-use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::{self, ResizeEdge};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::utils::{Logical, Point, Rectangle, Scale, Size, Transform};
-use smithay::wayland::compositor::{send_surface_state, with_states};
+use smithay::wayland::compositor::with_states;
 use smithay::wayland::shell::xdg::SurfaceCachedState;
 
 pub use self::monitor::MonitorRenderElement;
 use self::monitor::{Monitor, WorkspaceSwitch, WorkspaceSwitchGesture};
-use self::workspace::{compute_working_area, Column, ColumnWidth, OutputId, Workspace};
+use self::workspace::{
+    compute_working_area, Column, ColumnWidth, OutputId, WindowHeight, Workspace, WorkspaceId,
+};
 use crate::animation::Animation;
-use crate::niri::WindowOffscreenId;
+use crate::niri::{
+    AlwaysOnTopOverride, WindowBlockOut, WindowOffscreenId, WindowRulesState, WindowUrgency,
+};
 use crate::niri_render_elements;
 use crate::render_helpers::renderer::NiriRenderer;
-use crate::utils::output_size;
+use crate::render_helpers::{render_surface_with_popups, RenderTarget};
+use crate::utils::{output_size, send_scale_transform};
 
 pub mod focus_ring;
 pub mod monitor;
+pub mod tab_indicator;
 pub mod tile;
 pub mod workspace;
 
@@ -90,12 +97,13 @@ pub trait LayoutElement: PartialEq {
     /// Renders the element at the given visual location.
     ///
     /// The element should be rendered in such a way that its visual geometry ends up at the given
-    /// location.
+    /// location. Depending on the render target, the element may be rendered blocked out.
     fn render<R: NiriRenderer>(
         &self,
         renderer: &mut R,
         location: Point<i32, Logical>,
         scale: Scale<f64>,
+        target: RenderTarget,
     ) -> Vec<LayoutElementRenderElement<R>>;
 
     fn request_size(&self, size: Size<i32, Logical>);
@@ -104,7 +112,7 @@ pub trait LayoutElement: PartialEq {
     fn max_size(&self) -> Size<i32, Logical>;
     fn is_wl_surface(&self, wl_surface: &WlSurface) -> bool;
     fn has_ssd(&self) -> bool;
-    fn set_preferred_scale_transform(&self, scale: i32, transform: Transform);
+    fn set_preferred_scale_transform(&self, scale: output::Scale, transform: Transform);
     fn output_enter(&self, output: &Output);
     fn output_leave(&self, output: &Output);
     fn set_offscreen_element_id(&self, id: Option<Id>);
@@ -118,6 +126,27 @@ pub trait LayoutElement: PartialEq {
     ///
     /// This *will* switch immediately after a [`LayoutElement::request_fullscreen()`] call.
     fn is_pending_fullscreen(&self) -> bool;
+
+    /// Border colors that override the ones from the config, as `(active, inactive)`.
+    fn border_color_overrides(&self) -> (Option<Color>, Option<Color>);
+
+    /// Whether the element requested attention and has not been focused since.
+    fn is_urgent(&self) -> bool;
+
+    /// Whether the element is a transient child, like a dialog, of `parent`.
+    fn is_child_of(&self, parent: &Self) -> bool;
+
+    /// Maximum number of windows that can stack in a column with this element.
+    fn max_windows_per_column(&self) -> Option<usize>;
+
+    /// Whether the element, when floating, stays above the other floating elements.
+    fn is_always_on_top(&self) -> bool;
+
+    /// Maximum width of a new column when the element chooses its own width.
+    fn max_auto_width(&self) -> Option<i32>;
+
+    /// Width-to-height ratio to keep when the layout sizes the element.
+    fn aspect_ratio(&self) -> Option<f64>;
 }
 
 #[derive(Debug)]
@@ -159,6 +188,12 @@ pub struct Options {
     pub preset_widths: Vec<ColumnWidth>,
     /// Initial width for new columns.
     pub default_width: Option<ColumnWidth>,
+    /// Initial width for new columns on particular outputs, by output name.
+    pub output_default_widths: HashMap<String, Option<ColumnWidth>>,
+    /// Maximum number of windows stacked in a column.
+    pub max_windows_per_column: Option<usize>,
+    /// Whether interactive resizes snap the column width to nearby preset widths.
+    pub snap_to_preset_widths: bool,
     pub animations: niri_config::Animations,
 }
 
@@ -176,6 +211,9 @@ impl Default for Options {
                 ColumnWidth::Proportion(2. / 3.),
             ],
             default_width: None,
+            output_default_widths: HashMap::new(),
+            max_windows_per_column: None,
+            snap_to_preset_widths: false,
             animations: Default::default(),
         }
     }
@@ -204,6 +242,16 @@ impl Options {
             .map(|w| w.0.first().copied().map(ColumnWidth::from))
             .unwrap_or(Some(ColumnWidth::Proportion(0.5)));
 
+        let output_default_widths = config
+            .outputs
+            .iter()
+            .filter_map(|output| {
+                let width = output.default_column_width.as_ref()?;
+                let width = width.0.first().copied().map(ColumnWidth::from);
+                Some((output.name.clone(), width))
+            })
+            .collect();
+
         Self {
             gaps: layout.gaps.into(),
             struts: layout.struts,
@@ -212,9 +260,20 @@ impl Options {
             center_focused_column: layout.center_focused_column,
             preset_widths,
             default_width,
+            output_default_widths,
+            max_windows_per_column: layout.max_windows_per_column.map(usize::from),
+            snap_to_preset_widths: layout.snap_to_preset_column_widths,
             animations: config.animations,
         }
     }
+
+    /// Returns the initial width for new columns on `output`.
+    pub fn default_width_for_output(&self, output: Option<&Output>) -> Option<ColumnWidth> {
+        output
+            .and_then(|output| self.output_default_widths.get(&output.name()))
+            .copied()
+            .unwrap_or(self.default_width)
+    }
 }
 
 impl LayoutElement for Window {
@@ -236,13 +295,38 @@ impl LayoutElement for Window {
         renderer: &mut R,
         location: Point<i32, Logical>,
         scale: Scale<f64>,
+        target: RenderTarget,
     ) -> Vec<LayoutElementRenderElement<R>> {
-        let buf_pos = location - self.geometry().loc;
-        self.render_elements(
+        if let Some(block_out) = self.user_data().get::<WindowBlockOut>() {
+            if target.should_block_out(block_out.mode.get()) {
+                let mut buffer = block_out.buffer.borrow_mut();
+                buffer.resize(self.size());
+                let elem = SolidColorRenderElement::from_buffer(
+                    &buffer,
+                    location.to_physical_precise_round(scale),
+                    scale,
+                    1.,
+                    Kind::Unspecified,
+                );
+                return vec![elem.into()];
+            }
+        }
+
+        // Fullscreen windows ignore the opacity to avoid compositing surprises.
+        let opacity = self
+            .user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.opacity.get())
+            .filter(|_| !LayoutElement::is_fullscreen(self))
+            .unwrap_or(1.);
+
+        render_surface_with_popups(
             renderer,
-            buf_pos.to_physical_precise_round(scale),
+            self.toplevel().wl_surface(),
+            location,
+            self.geometry().loc,
             scale,
-            1.,
+            opacity,
         )
     }
 
@@ -261,26 +345,56 @@ impl LayoutElement for Window {
     }
 
     fn min_size(&self) -> Size<i32, Logical> {
-        with_states(self.toplevel().wl_surface(), |state| {
+        let mut size = with_states(self.toplevel().wl_surface(), |state| {
             let curr = state.cached_state.current::<SurfaceCachedState>();
             curr.min_size
-        })
+        });
+
+        let rules = self.user_data().get::<WindowRulesState>();
+        if let Some(min_width) = rules.and_then(|rules| rules.min_width.get()) {
+            size.w = max(size.w, min_width);
+        }
+
+        size
     }
 
     fn max_size(&self) -> Size<i32, Logical> {
-        with_states(self.toplevel().wl_surface(), |state| {
+        let mut size = with_states(self.toplevel().wl_surface(), |state| {
             let curr = state.cached_state.current::<SurfaceCachedState>();
             curr.max_size
-        })
+        });
+
+        // Zero max width means unlimited.
+        let rules = self.user_data().get::<WindowRulesState>();
+        if let Some(max_width) = rules.and_then(|rules| rules.max_width.get()) {
+            size.w = if size.w == 0 {
+                max_width
+            } else {
+                min(size.w, max_width)
+            };
+        }
+
+        size
     }
 
     fn is_wl_surface(&self, wl_surface: &WlSurface) -> bool {
         self.toplevel().wl_surface() == wl_surface
     }
 
-    fn set_preferred_scale_transform(&self, scale: i32, transform: Transform) {
+    fn set_preferred_scale_transform(&self, scale: output::Scale, transform: Transform) {
+        // The window rule scale takes precedence over the output scale. wp-fractional-scale gets
+        // the exact value, while the integer buffer scale is rounded up to keep the window sharp.
+        let scale = self
+            .user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.scale_override.get())
+            .map_or(scale, |x| output::Scale::Custom {
+                advertised_integer: max(x.ceil() as i32, 1),
+                fractional: x,
+            });
+
         self.with_surfaces(|surface, data| {
-            send_surface_state(surface, data, scale, transform);
+            send_scale_transform(surface, data, scale, transform);
         });
     }
 
@@ -314,11 +428,66 @@ impl LayoutElement for Window {
         self.toplevel()
             .with_pending_state(|state| state.states.contains(xdg_toplevel::State::Fullscreen))
     }
+
+    fn border_color_overrides(&self) -> (Option<Color>, Option<Color>) {
+        self.user_data()
+            .get::<WindowRulesState>()
+            .map(|state| {
+                (
+                    state.border_active_color.get(),
+                    state.border_inactive_color.get(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    fn is_urgent(&self) -> bool {
+        self.user_data()
+            .get::<WindowUrgency>()
+            .map_or(false, |urgency| urgency.is_urgent.get())
+    }
+
+    fn is_child_of(&self, parent: &Self) -> bool {
+        self.toplevel().parent().as_ref() == Some(parent.toplevel().wl_surface())
+    }
+
+    fn max_windows_per_column(&self) -> Option<usize> {
+        self.user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.max_windows_per_column.get())
+            .map(usize::from)
+    }
+
+    fn is_always_on_top(&self) -> bool {
+        let toggled = self
+            .user_data()
+            .get::<AlwaysOnTopOverride>()
+            .and_then(|toggled| toggled.0.get());
+        let rule = self
+            .user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.always_on_top.get());
+        toggled.or(rule).unwrap_or(false)
+    }
+
+    fn max_auto_width(&self) -> Option<i32> {
+        self.user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.max_auto_width.get())
+    }
+
+    fn aspect_ratio(&self) -> Option<f64> {
+        self.user_data()
+            .get::<WindowRulesState>()
+            .and_then(|state| state.aspect_ratio.get())
+    }
 }
 
 impl<W: LayoutElement> Layout<W> {
     pub fn new(config: &Config) -> Self {
-        Self::with_options(Options::from_config(config))
+        let mut layout = Self::with_options(Options::from_config(config));
+        layout.update_named_workspaces(&config.workspaces);
+        layout
     }
 
     pub fn with_options(options: Options) -> Self {
@@ -357,7 +526,7 @@ impl<W: LayoutElement> Layout<W> {
                         // The user could've closed a window while remaining on this workspace, on
                         // another monitor. However, we will add an empty workspace in the end
                         // instead.
-                        if ws.has_windows() {
+                        if ws.has_windows_or_name() {
                             workspaces.push(ws);
                         }
 
@@ -427,7 +596,7 @@ impl<W: LayoutElement> Layout<W> {
                 }
 
                 // Get rid of empty workspaces.
-                workspaces.retain(|ws| ws.has_windows());
+                workspaces.retain(|ws| ws.has_windows_or_name());
 
                 if monitors.is_empty() {
                     // Removed the last monitor.
@@ -502,6 +671,29 @@ impl<W: LayoutElement> Layout<W> {
         }
     }
 
+    pub fn add_floating_window_by_idx(
+        &mut self,
+        monitor_idx: usize,
+        workspace_idx: usize,
+        window: W,
+        activate: bool,
+    ) {
+        let MonitorSet::Normal {
+            monitors,
+            active_monitor_idx,
+            ..
+        } = &mut self.monitor_set
+        else {
+            panic!()
+        };
+
+        monitors[monitor_idx].add_floating_window(workspace_idx, window, activate);
+
+        if activate {
+            *active_monitor_idx = monitor_idx;
+        }
+    }
+
     pub fn add_column_by_idx(
         &mut self,
         monitor_idx: usize,
@@ -525,21 +717,38 @@ impl<W: LayoutElement> Layout<W> {
         }
     }
 
+    /// Picks the width of a new column on `output`.
+    fn new_column_width(
+        &self,
+        output: Option<&Output>,
+        width: Option<Option<ColumnWidth>>,
+        window: &W,
+    ) -> ColumnWidth {
+        match width {
+            Some(Some(width)) => Some(width),
+            Some(None) => None,
+            None => self.options.default_width_for_output(output),
+        }
+        .unwrap_or_else(|| {
+            let width = window.size().w;
+            let width = window.max_auto_width().map_or(width, |max| min(width, max));
+            ColumnWidth::Fixed(width)
+        })
+    }
+
     /// Adds a new window to the layout.
     ///
+    /// If `activate` is `false`, the window is added without taking focus from the active window.
+    ///
     /// Returns an output that the window was added to, if there were any outputs.
     pub fn add_window(
         &mut self,
         window: W,
         width: Option<Option<ColumnWidth>>,
         is_full_width: bool,
+        activate: bool,
     ) -> Option<&Output> {
-        let width = match width {
-            Some(Some(width)) => Some(width),
-            Some(None) => None,
-            None => self.options.default_width,
-        }
-        .unwrap_or_else(|| ColumnWidth::Fixed(window.size().w));
+        let width = self.new_column_width(self.active_output(), width, &window);
 
         match &mut self.monitor_set {
             MonitorSet::Normal {
@@ -550,7 +759,7 @@ impl<W: LayoutElement> Layout<W> {
                 let mon = &mut monitors[*active_monitor_idx];
 
                 // Don't steal focus from an active fullscreen window.
-                let mut activate = true;
+                let mut activate = activate;
                 let ws = &mon.workspaces[mon.active_workspace_idx];
                 if !ws.columns.is_empty() && ws.columns[ws.active_column_idx].is_fullscreen {
                     activate = false;
@@ -572,7 +781,88 @@ impl<W: LayoutElement> Layout<W> {
                     workspaces.push(Workspace::new_no_outputs(self.options.clone()));
                     &mut workspaces[0]
                 };
-                ws.add_window(window, true, width, is_full_width);
+                ws.add_window(window, activate, width, is_full_width);
+                None
+            }
+        }
+    }
+
+    /// Adds a new window as a tab in the active column of the active workspace.
+    ///
+    /// Falls back to adding a new column if there's no suitable active column.
+    ///
+    /// Returns an output that the window was added to, if there were any outputs.
+    pub fn add_window_as_tab(
+        &mut self,
+        window: W,
+        width: Option<Option<ColumnWidth>>,
+        is_full_width: bool,
+        activate: bool,
+    ) -> Option<&Output> {
+        let can_add = self
+            .active_workspace()
+            .map_or(false, Workspace::can_add_window_as_tab);
+        if !can_add {
+            return self.add_window(window, width, is_full_width, activate);
+        }
+
+        let MonitorSet::Normal {
+            monitors,
+            active_monitor_idx,
+            ..
+        } = &mut self.monitor_set
+        else {
+            unreachable!()
+        };
+
+        let mon = &mut monitors[*active_monitor_idx];
+        mon.add_window_as_tab(window, activate);
+        Some(&mon.output)
+    }
+
+    /// Adds a new floating window to the layout.
+    ///
+    /// The window is added to the given output, or to the active output if `output` is `None`.
+    ///
+    /// Returns an output that the window was added to, if there were any outputs.
+    pub fn add_floating_window(
+        &mut self,
+        window: W,
+        output: Option<&Output>,
+        activate: bool,
+    ) -> Option<&Output> {
+        match &mut self.monitor_set {
+            MonitorSet::Normal {
+                monitors,
+                active_monitor_idx,
+                ..
+            } => {
+                let mon_idx = output
+                    .and_then(|output| monitors.iter().position(|mon| mon.output == *output))
+                    .unwrap_or(*active_monitor_idx);
+                let mon = &mut monitors[mon_idx];
+
+                // Don't steal focus from an active fullscreen window.
+                let mut activate = activate;
+                let ws = &mon.workspaces[mon.active_workspace_idx];
+                if mon_idx == *active_monitor_idx
+                    && !ws.columns.is_empty()
+                    && ws.columns[ws.active_column_idx].is_fullscreen
+                {
+                    activate = false;
+                }
+
+                mon.add_floating_window(mon.active_workspace_idx, window, activate);
+                Some(&mon.output)
+            }
+            MonitorSet::NoOutputs { workspaces } => {
+                let ws = if let Some(ws) = workspaces.get_mut(0) {
+                    ws
+                } else {
+                    workspaces.push(Workspace::new_no_outputs(self.options.clone()));
+                    &mut workspaces[0]
+                };
+                ws.add_floating_window(window, activate);
                 None
             }
         }
@@ -580,7 +870,7 @@ impl<W: LayoutElement> Layout<W> {
 
     /// Adds a new window to the layout immediately to the right of another window.
     ///
-    /// If that another window was active, activates the new window.
+    /// If that another window was active and `activate` is `true`, activates the new window.
     ///
     /// Returns an output that the window was added to, if there were any outputs.
     pub fn add_window_right_of(
@@ -589,13 +879,10 @@ impl<W: LayoutElement> Layout<W> {
         window: W,
         width: Option<Option<ColumnWidth>>,
         is_full_width: bool,
+        activate: bool,
     ) -> Option<&Output> {
-        let width = match width {
-            Some(Some(width)) => Some(width),
-            Some(None) => None,
-            None => self.options.default_width,
-        }
-        .unwrap_or_else(|| ColumnWidth::Fixed(window.size().w));
+        let output = self.window_location(right_of).map(|(output, _)| output);
+        let width = self.new_column_width(output, width, &window);
 
         match &mut self.monitor_set {
             MonitorSet::Normal { monitors, .. } => {
@@ -604,7 +891,7 @@ impl<W: LayoutElement> Layout<W> {
                     .find(|mon| mon.workspaces.iter().any(|ws| ws.has_window(right_of)))
                     .unwrap();
 
-                mon.add_window_right_of(right_of, window, width, is_full_width);
+                mon.add_window_right_of(right_of, window, width, is_full_width, activate);
                 Some(&mon.output)
             }
             MonitorSet::NoOutputs { workspaces } => {
@@ -612,7 +899,54 @@ impl<W: LayoutElement> Layout<W> {
                     .iter_mut()
                     .find(|ws| ws.has_window(right_of))
                     .unwrap();
-                ws.add_window_right_of(right_of, window, width, is_full_width);
+                ws.add_window_right_of(right_of, window, width, is_full_width, activate);
+                None
+            }
+        }
+    }
+
+    /// Adds a new window to the column of another window, right below it.
+    ///
+    /// Falls back to adding a new column to the right if that column can't take the window.
+    ///
+    /// Returns an output that the window was added to, if there were any outputs.
+    pub fn add_window_below(
+        &mut self,
+        below: &W,
+        window: W,
+        width: Option<Option<ColumnWidth>>,
+        is_full_width: bool,
+        activate: bool,
+    ) -> Option<&Output> {
+        let can_add = match &self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => monitors
+                .iter()
+                .flat_map(|mon| &mon.workspaces)
+                .any(|ws| ws.can_add_window_below(below, &window)),
+            MonitorSet::NoOutputs { workspaces } => workspaces
+                .iter()
+                .any(|ws| ws.can_add_window_below(below, &window)),
+        };
+        if !can_add {
+            return self.add_window_right_of(below, window, width, is_full_width, activate);
+        }
+
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                let mon = monitors
+                    .iter_mut()
+                    .find(|mon| mon.workspaces.iter().any(|ws| ws.has_window(below)))
+                    .unwrap();
+
+                mon.add_window_below(below, window, activate);
+                Some(&mon.output)
+            }
+            MonitorSet::NoOutputs { workspaces } => {
+                let ws = workspaces
+                    .iter_mut()
+                    .find(|ws| ws.has_window(below))
+                    .unwrap();
+                ws.add_window_below(below, window, activate);
                 None
             }
         }
@@ -625,13 +959,9 @@ impl<W: LayoutElement> Layout<W> {
         window: W,
         width: Option<Option<ColumnWidth>>,
         is_full_width: bool,
+        activate: bool,
     ) {
-        let width = match width {
-            Some(Some(width)) => Some(width),
-            Some(None) => None,
-            None => self.options.default_width,
-        }
-        .unwrap_or_else(|| ColumnWidth::Fixed(window.size().w));
+        let width = self.new_column_width(Some(output), width, &window);
 
         let MonitorSet::Normal {
             monitors,
@@ -649,7 +979,7 @@ impl<W: LayoutElement> Layout<W> {
             .unwrap();
 
         // Don't steal focus from an active fullscreen window.
-        let mut activate = true;
+        let mut activate = activate;
         let ws = &mon.workspaces[mon.active_workspace_idx];
         if mon_idx == *active_monitor_idx
             && !ws.columns.is_empty()
@@ -667,64 +997,277 @@ impl<W: LayoutElement> Layout<W> {
         );
     }
 
-    pub fn remove_window(&mut self, window: &W) {
-        match &mut self.monitor_set {
-            MonitorSet::Normal { monitors, .. } => {
-                for mon in monitors {
-                    for (idx, ws) in mon.workspaces.iter_mut().enumerate() {
-                        if ws.has_window(window) {
-                            ws.remove_window(window);
-
-                            // Clean up empty workspaces that are not active and not last.
-                            if !ws.has_windows()
-                                && idx != mon.active_workspace_idx
-                                && idx != mon.workspaces.len() - 1
-                                && mon.workspace_switch.is_none()
-                            {
-                                mon.workspaces.remove(idx);
+    /// Adds a new window to the empty workspace at the end of an output.
+    ///
+    /// The window is added to the given output, or to the active output if `output` is `None`.
+    /// Activating the window switches to its workspace.
+    ///
+    /// Returns an output that the window was added to, if there were any outputs.
+    pub fn add_window_on_empty_workspace(
+        &mut self,
+        output: Option<&Output>,
+        window: W,
+        width: Option<Option<ColumnWidth>>,
+        is_full_width: bool,
+        activate: bool,
+    ) -> Option<&Output> {
+        // Without outputs, there's nothing to switch to anyway.
+        let Some(output) = output.or_else(|| self.active_output()).cloned() else {
+            return self.add_window(window, width, is_full_width, activate);
+        };
+        let width = self.new_column_width(Some(&output), width, &window);
 
-                                if idx < mon.active_workspace_idx {
-                                    mon.active_workspace_idx -= 1;
-                                }
-                            }
+        let MonitorSet::Normal { monitors, .. } = &mut self.monitor_set else {
+            unreachable!()
+        };
 
-                            break;
-                        }
-                    }
-                }
-            }
-            MonitorSet::NoOutputs { workspaces, .. } => {
-                for (idx, ws) in workspaces.iter_mut().enumerate() {
-                    if ws.has_window(window) {
-                        ws.remove_window(window);
+        let mon = monitors
+            .iter_mut()
+            .find(|mon| mon.output == output)
+            .unwrap();
 
-                        // Clean up empty workspaces.
-                        if !ws.has_windows() {
-                            workspaces.remove(idx);
-                        }
+        // The last workspace on a monitor is always empty.
+        let ws_idx = mon.workspaces.len() - 1;
+        mon.add_window(ws_idx, window, activate, width, is_full_width);
+        Some(&mon.output)
+    }
 
-                        break;
-                    }
-                }
-            }
-        }
+    /// Returns the empty workspace at the end of an output.
+    ///
+    /// Uses the given output, or the active output if `output` is `None`.
+    pub fn empty_workspace(&self, output: Option<&Output>) -> Option<&Workspace<W>> {
+        let output = output.or_else(|| self.active_output())?;
+        let mon = self.monitor_for_output(output)?;
+        mon.workspaces.last()
     }
 
-    pub fn update_window(&mut self, window: &W) {
+    /// Adds a new window to the layout on the workspace with the given name.
+    ///
+    /// The window is only activated if its workspace is the active one on its output.
+    ///
+    /// Returns an output that the window was added to, if there were any outputs.
+    pub fn add_window_to_named_workspace(
+        &mut self,
+        workspace_name: &str,
+        window: W,
+        width: Option<Option<ColumnWidth>>,
+        is_full_width: bool,
+        activate: bool,
+    ) -> Option<&Output> {
+        let output = self
+            .find_workspace_by_name(workspace_name)
+            .and_then(|ws| ws.output());
+        let width = self.new_column_width(output, width, &window);
+
+        let name = Some(workspace_name);
+
         match &mut self.monitor_set {
             MonitorSet::Normal { monitors, .. } => {
-                for mon in monitors {
-                    for ws in &mut mon.workspaces {
-                        if ws.has_window(window) {
-                            ws.update_window(window);
-                            return;
-                        }
-                    }
+                let (mon, ws_idx) = monitors
+                    .iter_mut()
+                    .find_map(|mon| {
+                        let idx = mon
+                            .workspaces
+                            .iter()
+                            .position(|ws| ws.name.as_deref() == name);
+                        idx.map(|idx| (mon, idx))
+                    })
+                    .unwrap();
+
+                // Don't switch away from the current workspace.
+                let mut activate = activate && ws_idx == mon.active_workspace_idx;
+
+                // Don't steal focus from an active fullscreen window.
+                let ws = &mon.workspaces[ws_idx];
+                if !ws.columns.is_empty() && ws.columns[ws.active_column_idx].is_fullscreen {
+                    activate = false;
                 }
+
+                mon.add_window(ws_idx, window, activate, width, is_full_width);
+                Some(&mon.output)
             }
-            MonitorSet::NoOutputs { workspaces, .. } => {
-                for ws in workspaces {
-                    if ws.has_window(window) {
+            MonitorSet::NoOutputs { workspaces } => {
+                let ws = workspaces
+                    .iter_mut()
+                    .find(|ws| ws.name.as_deref() == name)
+                    .unwrap();
+                ws.add_window(window, activate, width, is_full_width);
+                None
+            }
+        }
+    }
+
+    /// Adds a new floating window to the layout on the workspace with the given name.
+    ///
+    /// The window is only activated if its workspace is the active one on its output.
+    ///
+    /// Returns an output that the window was added to, if there were any outputs.
+    pub fn add_floating_window_to_named_workspace(
+        &mut self,
+        workspace_name: &str,
+        window: W,
+        activate: bool,
+    ) -> Option<&Output> {
+        let name = Some(workspace_name);
+
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                let (mon, ws_idx) = monitors
+                    .iter_mut()
+                    .find_map(|mon| {
+                        let idx = mon
+                            .workspaces
+                            .iter()
+                            .position(|ws| ws.name.as_deref() == name);
+                        idx.map(|idx| (mon, idx))
+                    })
+                    .unwrap();
+
+                // Don't switch away from the current workspace.
+                let mut activate = activate && ws_idx == mon.active_workspace_idx;
+
+                // Don't steal focus from an active fullscreen window.
+                let ws = &mon.workspaces[ws_idx];
+                if !ws.columns.is_empty() && ws.columns[ws.active_column_idx].is_fullscreen {
+                    activate = false;
+                }
+
+                mon.add_floating_window(ws_idx, window, activate);
+                Some(&mon.output)
+            }
+            MonitorSet::NoOutputs { workspaces } => {
+                let ws = workspaces
+                    .iter_mut()
+                    .find(|ws| ws.name.as_deref() == name)
+                    .unwrap();
+                ws.add_floating_window(window, activate);
+                None
+            }
+        }
+    }
+
+    /// Adds a new floating window over its parent window, on the parent's workspace.
+    pub fn add_floating_window_over(
+        &mut self,
+        parent: &W,
+        window: W,
+        activate: bool,
+    ) -> Option<&Output> {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                let (mon, ws_idx) = monitors
+                    .iter_mut()
+                    .find_map(|mon| {
+                        let idx = mon.workspaces.iter().position(|ws| ws.has_window(parent));
+                        idx.map(|idx| (mon, idx))
+                    })
+                    .unwrap();
+
+                // Don't switch away from the current workspace.
+                let mut activate = activate && ws_idx == mon.active_workspace_idx;
+
+                // Don't steal focus from an active fullscreen window.
+                let ws = &mon.workspaces[ws_idx];
+                if !ws.columns.is_empty() && ws.columns[ws.active_column_idx].is_fullscreen {
+                    activate = false;
+                }
+
+                mon.add_floating_window(ws_idx, window, activate);
+                Some(&mon.output)
+            }
+            MonitorSet::NoOutputs { workspaces } => {
+                let ws = workspaces
+                    .iter_mut()
+                    .find(|ws| ws.has_window(parent))
+                    .unwrap();
+                ws.add_floating_window(window, activate);
+                None
+            }
+        }
+    }
+
+    pub fn remove_window(&mut self, window: &W) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for (idx, ws) in mon.workspaces.iter_mut().enumerate() {
+                        if ws.has_window(window) {
+                            ws.remove_window(window);
+
+                            // Clean up empty workspaces that are not active and not last.
+                            if !ws.has_windows_or_name()
+                                && idx != mon.active_workspace_idx
+                                && idx != mon.workspaces.len() - 1
+                                && mon.workspace_switch.is_none()
+                            {
+                                mon.workspaces.remove(idx);
+
+                                if idx < mon.active_workspace_idx {
+                                    mon.active_workspace_idx -= 1;
+                                }
+                            }
+
+                            break;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for (idx, ws) in workspaces.iter_mut().enumerate() {
+                    if ws.has_window(window) {
+                        ws.remove_window(window);
+
+                        // Clean up empty workspaces.
+                        if !ws.has_windows_or_name() {
+                            workspaces.remove(idx);
+                        }
+
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves a floating window to `pos` relative to its output, keeping it on screen.
+    pub fn set_floating_window_position(&mut self, window: &W, pos: Point<i32, Logical>) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.set_floating_window_position(window, pos);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        ws.set_floating_window_position(window, pos);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn update_window(&mut self, window: &W) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.update_window(window);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
                         ws.update_window(window);
                         return;
                     }
@@ -757,6 +1300,12 @@ impl<W: LayoutElement> Layout<W> {
                                 return Some(col.window_y(idx));
                             }
                         }
+
+                        for floating in &ws.floating {
+                            if floating.tile.window() == window {
+                                return Some(floating.pos.y + floating.tile.window_loc().y);
+                            }
+                        }
                     }
                 }
             }
@@ -767,6 +1316,12 @@ impl<W: LayoutElement> Layout<W> {
                             return Some(col.window_y(idx));
                         }
                     }
+
+                    for floating in &ws.floating {
+                        if floating.tile.window() == window {
+                            return Some(floating.pos.y + floating.tile.window_loc().y);
+                        }
+                    }
                 }
             }
         }
@@ -774,6 +1329,19 @@ impl<W: LayoutElement> Layout<W> {
         None
     }
 
+    /// Returns the X of the window relative to the view of its workspace.
+    pub fn window_x(&self, window: &W) -> Option<i32> {
+        match &self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => monitors
+                .iter()
+                .flat_map(|mon| &mon.workspaces)
+                .find_map(|ws| ws.window_x(window)),
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                workspaces.iter().find_map(|ws| ws.window_x(window))
+            }
+        }
+    }
+
     pub fn update_output_size(&mut self, output: &Output) {
         let _span = tracy_client::span!("Layout::update_output_size");
 
@@ -867,6 +1435,20 @@ impl<W: LayoutElement> Layout<W> {
         Some(&mon.workspaces[mon.active_workspace_idx])
     }
 
+    pub fn find_workspace_by_name(&self, name: &str) -> Option<&Workspace<W>> {
+        let name = Some(name);
+
+        match &self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => monitors
+                .iter()
+                .flat_map(|mon| &mon.workspaces)
+                .find(|ws| ws.name.as_deref() == name),
+            MonitorSet::NoOutputs { workspaces } => {
+                workspaces.iter().find(|ws| ws.name.as_deref() == name)
+            }
+        }
+    }
+
     pub fn active_window(&self) -> Option<(&W, &Output)> {
         let MonitorSet::Normal {
             monitors,
@@ -879,13 +1461,7 @@ impl<W: LayoutElement> Layout<W> {
 
         let mon = &monitors[*active_monitor_idx];
         let ws = &mon.workspaces[mon.active_workspace_idx];
-
-        if ws.columns.is_empty() {
-            return None;
-        }
-
-        let col = &ws.columns[ws.active_column_idx];
-        Some((&col.tiles[col.active_tile_idx].window(), &mon.output))
+        ws.active_window().map(|win| (win, &mon.output))
     }
 
     pub fn windows_for_output(&self, output: &Output) -> impl Iterator<Item = &W> + '_ {
@@ -897,13 +1473,13 @@ impl<W: LayoutElement> Layout<W> {
         mon.workspaces.iter().flat_map(|ws| ws.windows())
     }
 
-    pub fn with_windows(&self, mut f: impl FnMut(&W, Option<&Output>)) {
+    pub fn with_windows(&self, mut f: impl FnMut(&W, Option<&Output>, WorkspaceId)) {
         match &self.monitor_set {
             MonitorSet::Normal { monitors, .. } => {
                 for mon in monitors {
                     for ws in &mon.workspaces {
                         for win in ws.windows() {
-                            f(win, Some(&mon.output));
+                            f(win, Some(&mon.output), ws.id());
                         }
                     }
                 }
@@ -911,7 +1487,7 @@ impl<W: LayoutElement> Layout<W> {
             MonitorSet::NoOutputs { workspaces } => {
                 for ws in workspaces {
                     for win in ws.windows() {
-                        f(win, None);
+                        f(win, None, ws.id());
                     }
                 }
             }
@@ -1159,6 +1735,27 @@ impl<W: LayoutElement> Layout<W> {
         monitor.center_column();
     }
 
+    pub fn toggle_column_tabbed_display(&mut self) {
+        let Some(monitor) = self.active_monitor() else {
+            return;
+        };
+        monitor.toggle_column_tabbed_display();
+    }
+
+    pub fn focus_tab_next(&mut self) {
+        let Some(monitor) = self.active_monitor() else {
+            return;
+        };
+        monitor.focus_tab_next();
+    }
+
+    pub fn focus_tab_previous(&mut self) {
+        let Some(monitor) = self.active_monitor() else {
+            return;
+        };
+        monitor.focus_tab_previous();
+    }
+
     pub fn focus(&self) -> Option<&W> {
         let MonitorSet::Normal {
             monitors,
@@ -1203,8 +1800,8 @@ impl<W: LayoutElement> Layout<W> {
             MonitorSet::NoOutputs { workspaces } => {
                 for workspace in workspaces {
                     assert!(
-                        workspace.has_windows(),
-                        "with no outputs there cannot be empty workspaces"
+                        workspace.has_windows_or_name(),
+                        "with no outputs there cannot be empty unnamed workspaces"
                     );
 
                     assert_eq!(
@@ -1270,8 +1867,8 @@ impl<W: LayoutElement> Layout<W> {
             }
 
             assert!(
-                monitor.workspaces.last().unwrap().columns.is_empty(),
-                "monitor must have an empty workspace in the end"
+                !monitor.workspaces.last().unwrap().has_windows_or_name(),
+                "monitor must have an empty unnamed workspace in the end"
             );
 
             // If there's no workspace switch in progress, there can't be any non-last non-active
@@ -1280,8 +1877,8 @@ impl<W: LayoutElement> Layout<W> {
                 for (idx, ws) in monitor.workspaces.iter().enumerate().rev().skip(1) {
                     if idx != monitor.active_workspace_idx {
                         assert!(
-                            !ws.columns.is_empty(),
-                            "non-active workspace can't be empty except the last one"
+                            ws.has_windows_or_name(),
+                            "non-active unnamed workspace can't be empty except the last one"
                         );
                     }
                 }
@@ -1339,6 +1936,88 @@ impl<W: LayoutElement> Layout<W> {
         }
 
         self.options = options;
+
+        self.update_named_workspaces(&config.workspaces);
+    }
+
+    /// Makes the named workspaces match the ones declared in the config.
+    ///
+    /// Missing named workspaces are created on the primary monitor. Workspaces whose names are no
+    /// longer declared become regular workspaces.
+    pub fn update_named_workspaces(&mut self, configs: &[niri_config::Workspace]) {
+        let is_declared = |name: &str| configs.iter().any(|c| c.name == name);
+
+        match &mut self.monitor_set {
+            MonitorSet::Normal {
+                monitors,
+                primary_idx,
+                ..
+            } => {
+                for mon in monitors.iter_mut() {
+                    let mut unnamed_any = false;
+                    for ws in &mut mon.workspaces {
+                        if ws.name.as_deref().is_some_and(|name| !is_declared(name)) {
+                            ws.name = None;
+                            unnamed_any = true;
+                        }
+                    }
+
+                    // If there's a workspace switch in progress, the clean up will happen once
+                    // it ends.
+                    if unnamed_any && mon.workspace_switch.is_none() {
+                        mon.clean_up_workspaces();
+                    }
+                }
+
+                for config in configs {
+                    let exists = monitors
+                        .iter()
+                        .flat_map(|mon| &mon.workspaces)
+                        .any(|ws| ws.name.as_ref() == Some(&config.name));
+                    if exists {
+                        continue;
+                    }
+
+                    let primary = &mut monitors[*primary_idx];
+
+                    // Inserting a workspace shifts the indices that the switch refers to.
+                    primary.workspace_switch = None;
+
+                    let mut ws = Workspace::new(primary.output.clone(), self.options.clone());
+                    ws.name = Some(config.name.clone());
+
+                    // Insert right before the last, empty, workspace.
+                    let idx = primary.workspaces.len() - 1;
+                    primary.workspaces.insert(idx, ws);
+                    if primary.active_workspace_idx >= idx {
+                        primary.active_workspace_idx += 1;
+                    }
+
+                    primary.clean_up_workspaces();
+                }
+            }
+            MonitorSet::NoOutputs { workspaces } => {
+                for ws in workspaces.iter_mut() {
+                    if ws.name.as_deref().is_some_and(|name| !is_declared(name)) {
+                        ws.name = None;
+                    }
+                }
+                workspaces.retain(|ws| ws.has_windows_or_name());
+
+                for config in configs {
+                    if workspaces
+                        .iter()
+                        .any(|ws| ws.name.as_ref() == Some(&config.name))
+                    {
+                        continue;
+                    }
+
+                    let mut ws = Workspace::new_no_outputs(self.options.clone());
+                    ws.name = Some(config.name.clone());
+                    workspaces.push(ws);
+                }
+            }
+        }
     }
 
     pub fn toggle_width(&mut self) {
@@ -1399,7 +2078,13 @@ impl<W: LayoutElement> Layout<W> {
 
             let current = &mut monitors[*active_monitor_idx];
             let ws = current.active_workspace();
-            if !ws.has_windows() {
+            if let Some(window) = ws.remove_active_floating_window() {
+                let workspace_idx = monitors[new_idx].active_workspace_idx;
+                self.add_floating_window_by_idx(new_idx, workspace_idx, window, true);
+                return;
+            }
+
+            if ws.columns.is_empty() {
                 return;
             }
             let column = &ws.columns[ws.active_column_idx];
@@ -1426,7 +2111,7 @@ impl<W: LayoutElement> Layout<W> {
 
             let current = &mut monitors[*active_monitor_idx];
             let ws = current.active_workspace();
-            if !ws.has_windows() {
+            if ws.columns.is_empty() {
                 return;
             }
             let column = ws.remove_column_by_idx(ws.active_column_idx);
@@ -1437,39 +2122,106 @@ impl<W: LayoutElement> Layout<W> {
     }
 
     pub fn move_window_to_output(&mut self, window: W, output: &Output) {
-        if !matches!(&self.monitor_set, MonitorSet::Normal { .. }) {
-            return;
-        }
+        self.move_window_to_workspace(window, output, None);
+    }
 
-        self.remove_window(&window);
+    /// Moves the window to a workspace on the given output.
+    ///
+    /// If `workspace_id` is `None` or there's no such workspace on the output, the window goes to
+    /// the output's active workspace.
+    pub fn move_window_to_workspace(
+        &mut self,
+        window: W,
+        output: &Output,
+        workspace_id: Option<WorkspaceId>,
+    ) {
+        let MonitorSet::Normal { monitors, .. } = &self.monitor_set else {
+            return;
+        };
 
-        if let MonitorSet::Normal { monitors, .. } = &mut self.monitor_set {
-            let mut width = None;
-            let mut is_full_width = false;
-            for mon in &*monitors {
-                for ws in &mon.workspaces {
-                    for col in &ws.columns {
-                        if col.contains(&window) {
-                            width = Some(col.width);
-                            is_full_width = col.is_full_width;
-                            break;
-                        }
+        // Look up the column before removing the window from it. Floating windows don't have
+        // one.
+        let mut column = None;
+        let mut is_floating = false;
+        for mon in monitors {
+            for ws in &mon.workspaces {
+                is_floating |= ws.is_floating(&window);
+                for col in &ws.columns {
+                    if col.contains(&window) {
+                        column = Some((col.width, col.is_full_width));
+                        break;
                     }
                 }
             }
-            let Some(width) = width else { return };
+        }
+        if column.is_none() && !is_floating {
+            return;
+        }
 
+        self.remove_window(&window);
+
+        if let MonitorSet::Normal { monitors, .. } = &mut self.monitor_set {
             let new_idx = monitors
                 .iter()
                 .position(|mon| &mon.output == output)
                 .unwrap();
 
-            let workspace_idx = monitors[new_idx].active_workspace_idx;
+            // Removing the window could have removed workspaces, so look up the index only now.
+            let mon = &monitors[new_idx];
+            let workspace_idx = workspace_id
+                .and_then(|id| mon.workspaces.iter().position(|ws| ws.id() == id))
+                .unwrap_or(mon.active_workspace_idx);
+
             // FIXME: activate only if it was already active and focused.
-            self.add_window_by_idx(new_idx, workspace_idx, window, true, width, is_full_width);
+            if let Some((width, is_full_width)) = column {
+                self.add_window_by_idx(new_idx, workspace_idx, window, true, width, is_full_width);
+            } else {
+                self.add_floating_window_by_idx(new_idx, workspace_idx, window, true);
+            }
         }
     }
 
+    pub fn is_floating(&self, window: &W) -> bool {
+        match &self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => monitors
+                .iter()
+                .flat_map(|mon| &mon.workspaces)
+                .any(|ws| ws.is_floating(window)),
+            MonitorSet::NoOutputs { workspaces } => {
+                workspaces.iter().any(|ws| ws.is_floating(window))
+            }
+        }
+    }
+
+    pub fn is_maximized(&self, window: &W) -> bool {
+        match &self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => monitors
+                .iter()
+                .flat_map(|mon| &mon.workspaces)
+                .any(|ws| ws.is_maximized(window)),
+            MonitorSet::NoOutputs { workspaces } => {
+                workspaces.iter().any(|ws| ws.is_maximized(window))
+            }
+        }
+    }
+
+    /// Returns the output and the workspace that contain the window.
+    pub fn window_location(&self, window: &W) -> Option<(&Output, WorkspaceId)> {
+        let MonitorSet::Normal { monitors, .. } = &self.monitor_set else {
+            return None;
+        };
+
+        for mon in monitors {
+            for ws in &mon.workspaces {
+                if ws.has_window(window) {
+                    return Some((&mon.output, ws.id()));
+                }
+            }
+        }
+
+        None
+    }
+
     pub fn move_workspace_to_output(&mut self, output: &Output) {
         let MonitorSet::Normal {
             monitors,
@@ -1534,6 +2286,102 @@ impl<W: LayoutElement> Layout<W> {
         }
     }
 
+    pub fn set_maximized_to_working_area(&mut self, window: &W, is_maximized: bool) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.set_maximized_to_working_area(window, is_maximized);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        ws.set_maximized_to_working_area(window, is_maximized);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn set_maximized(&mut self, window: &W, is_maximized: bool) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.set_maximized(window, is_maximized);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        ws.set_maximized(window, is_maximized);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn set_new_window_height(
+        &mut self,
+        window: &W,
+        default_height: Option<Option<WindowHeight>>,
+    ) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.set_new_window_height(window, default_height);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        ws.set_new_window_height(window, default_height);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn set_new_window_column_position(&mut self, window: &W, position: ColumnPosition) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.set_new_window_column_position(window, position);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        ws.set_new_window_column_position(window, position);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
     pub fn toggle_fullscreen(&mut self, window: &W) {
         match &mut self.monitor_set {
             MonitorSet::Normal { monitors, .. } => {
@@ -1557,6 +2405,100 @@ impl<W: LayoutElement> Layout<W> {
         }
     }
 
+    pub fn interactive_resize_begin(&mut self, window: &W, edges: ResizeEdge) -> bool {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            return ws.interactive_resize_begin(window, edges);
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        return ws.interactive_resize_begin(window, edges);
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn interactive_resize_update(
+        &mut self,
+        window: &W,
+        delta: Point<f64, Logical>,
+        snap: bool,
+    ) -> bool {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            return ws.interactive_resize_update(window, delta, snap);
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        return ws.interactive_resize_update(window, delta, snap);
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn interactive_resize_end(&mut self, window: &W) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        if ws.has_window(window) {
+                            ws.interactive_resize_end(window);
+                            return;
+                        }
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    if ws.has_window(window) {
+                        ws.interactive_resize_end(window);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies the pointer movement accumulated by ongoing interactive resizes.
+    ///
+    /// This is meant to be called once per frame to throttle the resulting configures.
+    pub fn apply_pending_interactive_resizes(&mut self) {
+        match &mut self.monitor_set {
+            MonitorSet::Normal { monitors, .. } => {
+                for mon in monitors {
+                    for ws in &mut mon.workspaces {
+                        ws.apply_pending_interactive_resizes();
+                    }
+                }
+            }
+            MonitorSet::NoOutputs { workspaces, .. } => {
+                for ws in workspaces {
+                    ws.apply_pending_interactive_resizes();
+                }
+            }
+        }
+    }
+
     pub fn workspace_switch_gesture_begin(&mut self, output: &Output) {
         let monitors = match &mut self.monitor_set {
             MonitorSet::Normal { monitors, .. } => monitors,
@@ -1674,6 +2616,13 @@ impl<W: LayoutElement> Layout<W> {
                                 }
                             }
                         }
+
+                        for floating in &mut ws.floating {
+                            if floating.tile.window() == window {
+                                floating.tile.start_open_animation();
+                                return;
+                            }
+                        }
                     }
                 }
             }
@@ -1688,6 +2637,13 @@ impl<W: LayoutElement> Layout<W> {
                                 }
                             }
                         }
+
+                        for floating in &mut ws.floating {
+                            if floating.tile.window() == window {
+                                floating.tile.start_open_animation();
+                                return;
+                            }
+                        }
                     }
                 }
             }
@@ -1707,14 +2663,14 @@ impl Layout<Window> {
             } => {
                 for (idx, mon) in monitors.iter().enumerate() {
                     let is_active = idx == *active_monitor_idx;
-                    for ws in &mon.workspaces {
-                        ws.refresh(is_active);
+                    for (ws_idx, ws) in mon.workspaces.iter().enumerate() {
+                        ws.refresh(is_active, mon.is_workspace_visible(ws_idx));
                     }
                 }
             }
             MonitorSet::NoOutputs { workspaces, .. } => {
                 for ws in workspaces {
-                    ws.refresh(false);
+                    ws.refresh(false, false);
                 }
             }
         }
@@ -1735,6 +2691,7 @@ mod tests {
     use proptest_derive::Arbitrary;
     use smithay::output::{Mode, PhysicalProperties, Subpixel};
 
+    use super::workspace::ColumnDisplay;
     use super::*;
 
     impl<W: LayoutElement> Default for Layout<W> {
@@ -1749,9 +2706,13 @@ mod tests {
         bbox: Cell<Rectangle<i32, Logical>>,
         initial_bbox: Rectangle<i32, Logical>,
         requested_size: Cell<Option<Size<i32, Logical>>>,
-        min_size: Size<i32, Logical>,
-        max_size: Size<i32, Logical>,
+        min_size: Cell<Size<i32, Logical>>,
+        max_size: Cell<Size<i32, Logical>>,
         pending_fullscreen: Cell<bool>,
+        parent_id: Cell<Option<usize>>,
+        always_on_top: Cell<bool>,
+        max_auto_width: Cell<Option<i32>>,
+        aspect_ratio: Cell<Option<f64>>,
     }
 
     #[derive(Debug, Clone)]
@@ -1769,9 +2730,13 @@ mod tests {
                 bbox: Cell::new(bbox),
                 initial_bbox: bbox,
                 requested_size: Cell::new(None),
-                min_size,
-                max_size,
+                min_size: Cell::new(min_size),
+                max_size: Cell::new(max_size),
                 pending_fullscreen: Cell::new(false),
+                parent_id: Cell::new(None),
+                always_on_top: Cell::new(false),
+                max_auto_width: Cell::new(None),
+                aspect_ratio: Cell::new(None),
             }))
         }
 
@@ -1822,6 +2787,7 @@ mod tests {
             _renderer: &mut R,
             _location: Point<i32, Logical>,
             _scale: Scale<f64>,
+            _target: RenderTarget,
         ) -> Vec<LayoutElementRenderElement<R>> {
             vec![]
         }
@@ -1836,18 +2802,18 @@ mod tests {
         }
 
         fn min_size(&self) -> Size<i32, Logical> {
-            self.0.min_size
+            self.0.min_size.get()
         }
 
         fn max_size(&self) -> Size<i32, Logical> {
-            self.0.max_size
+            self.0.max_size.get()
         }
 
         fn is_wl_surface(&self, _wl_surface: &WlSurface) -> bool {
             false
         }
 
-        fn set_preferred_scale_transform(&self, _scale: i32, _transform: Transform) {}
+        fn set_preferred_scale_transform(&self, _scale: output::Scale, _transform: Transform) {}
 
         fn has_ssd(&self) -> bool {
             false
@@ -1866,6 +2832,34 @@ mod tests {
         fn is_pending_fullscreen(&self) -> bool {
             self.0.pending_fullscreen.get()
         }
+
+        fn border_color_overrides(&self) -> (Option<Color>, Option<Color>) {
+            (None, None)
+        }
+
+        fn is_urgent(&self) -> bool {
+            false
+        }
+
+        fn is_child_of(&self, parent: &Self) -> bool {
+            self.0.parent_id.get() == Some(parent.0.id)
+        }
+
+        fn max_windows_per_column(&self) -> Option<usize> {
+            None
+        }
+
+        fn is_always_on_top(&self) -> bool {
+            self.0.always_on_top.get()
+        }
+
+        fn max_auto_width(&self) -> Option<i32> {
+            self.0.max_auto_width.get()
+        }
+
+        fn aspect_ratio(&self) -> Option<f64> {
+            self.0.aspect_ratio.get()
+        }
     }
 
     fn arbitrary_bbox() -> impl Strategy<Value = Rectangle<i32, Logical>> {
@@ -1885,6 +2879,19 @@ mod tests {
         ]
     }
 
+    fn arbitrary_resize_edge() -> impl Strategy<Value = ResizeEdge> {
+        prop_oneof![
+            Just(ResizeEdge::Top),
+            Just(ResizeEdge::Bottom),
+            Just(ResizeEdge::Left),
+            Just(ResizeEdge::TopLeft),
+            Just(ResizeEdge::BottomLeft),
+            Just(ResizeEdge::Right),
+            Just(ResizeEdge::TopRight),
+            Just(ResizeEdge::BottomRight),
+        ]
+    }
+
     fn arbitrary_min_max() -> impl Strategy<Value = (i32, i32)> {
         prop_oneof![
             Just((0, 0)),
@@ -1926,8 +2933,56 @@ mod tests {
             #[proptest(strategy = "arbitrary_min_max_size()")]
             min_max_size: (Size<i32, Logical>, Size<i32, Logical>),
         },
+        AddFloatingWindow {
+            #[proptest(strategy = "1..=5usize")]
+            id: usize,
+            #[proptest(strategy = "arbitrary_bbox()")]
+            bbox: Rectangle<i32, Logical>,
+            #[proptest(strategy = "arbitrary_min_max_size()")]
+            min_max_size: (Size<i32, Logical>, Size<i32, Logical>),
+        },
+        AddWindowAsTab {
+            #[proptest(strategy = "1..=5usize")]
+            id: usize,
+            #[proptest(strategy = "arbitrary_bbox()")]
+            bbox: Rectangle<i32, Logical>,
+            #[proptest(strategy = "arbitrary_min_max_size()")]
+            min_max_size: (Size<i32, Logical>, Size<i32, Logical>),
+        },
+        AddWindowWithoutActivating {
+            #[proptest(strategy = "1..=5usize")]
+            id: usize,
+            #[proptest(strategy = "arbitrary_bbox()")]
+            bbox: Rectangle<i32, Logical>,
+            #[proptest(strategy = "arbitrary_min_max_size()")]
+            min_max_size: (Size<i32, Logical>, Size<i32, Logical>),
+        },
+        SetNamedWorkspaces(#[proptest(strategy = "0..8u8")] u8),
+        AddWindowToNamedWorkspace {
+            #[proptest(strategy = "1..=5usize")]
+            id: usize,
+            #[proptest(strategy = "0..3usize")]
+            workspace: usize,
+            #[proptest(strategy = "arbitrary_bbox()")]
+            bbox: Rectangle<i32, Logical>,
+            #[proptest(strategy = "arbitrary_min_max_size()")]
+            min_max_size: (Size<i32, Logical>, Size<i32, Logical>),
+        },
+        ActivateWindow(#[proptest(strategy = "1..=5usize")] usize),
         CloseWindow(#[proptest(strategy = "1..=5usize")] usize),
         FullscreenWindow(#[proptest(strategy = "1..=5usize")] usize),
+        MaximizeWindow(#[proptest(strategy = "1..=5usize")] usize),
+        SetFloatingWindowPosition {
+            #[proptest(strategy = "1..=5usize")]
+            id: usize,
+            #[proptest(strategy = "-2000..2000i32")]
+            x: i32,
+            #[proptest(strategy = "-2000..2000i32")]
+            y: i32,
+        },
+        UnmaximizeWindow(#[proptest(strategy = "1..=5usize")] usize),
+        MaximizeWindowToWorkingArea(#[proptest(strategy = "1..=5usize")] usize),
+        UnmaximizeWindowFromWorkingArea(#[proptest(strategy = "1..=5usize")] usize),
         FocusColumnLeft,
         FocusColumnRight,
         FocusColumnFirst,
@@ -1949,6 +3004,9 @@ mod tests {
         ConsumeWindowIntoColumn,
         ExpelWindowFromColumn,
         CenterColumn,
+        ToggleColumnTabbedDisplay,
+        FocusTabNext,
+        FocusTabPrevious,
         FocusWorkspaceDown,
         FocusWorkspaceUp,
         FocusWorkspace(#[proptest(strategy = "0..=4usize")] usize),
@@ -1968,6 +3026,23 @@ mod tests {
         SetWindowHeight(#[proptest(strategy = "arbitrary_size_change()")] SizeChange),
         Communicate(#[proptest(strategy = "1..=5usize")] usize),
         MoveWorkspaceToOutput(#[proptest(strategy = "1..=5u8")] u8),
+        InteractiveResizeBegin {
+            #[proptest(strategy = "1..=5usize")]
+            window: usize,
+            #[proptest(strategy = "arbitrary_resize_edge()")]
+            edges: ResizeEdge,
+        },
+        InteractiveResizeUpdate {
+            #[proptest(strategy = "1..=5usize")]
+            window: usize,
+            #[proptest(strategy = "-20000f64..20000f64")]
+            dx: f64,
+            #[proptest(strategy = "-20000f64..20000f64")]
+            dy: f64,
+            snap: bool,
+        },
+        InteractiveResizeEnd(#[proptest(strategy = "1..=5usize")] usize),
+        ApplyPendingInteractiveResizes,
     }
 
     impl Op {
@@ -2044,7 +3119,7 @@ mod tests {
                     }
 
                     let win = TestWindow::new(id, bbox, min_max_size.0, min_max_size.1);
-                    layout.add_window(win, None, false);
+                    layout.add_window(win, None, false, true);
                 }
                 Op::AddWindowRightOf {
                     id,
@@ -2096,18 +3171,125 @@ mod tests {
                         Size::default(),
                     );
                     let win = TestWindow::new(id, bbox, min_max_size.0, min_max_size.1);
-                    layout.add_window_right_of(&right_of_win, win, None, false);
+                    layout.add_window_right_of(&right_of_win, win, None, false, true);
                 }
-                Op::CloseWindow(id) => {
-                    let dummy =
-                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
-                    layout.remove_window(&dummy);
+                Op::AddFloatingWindow {
+                    id,
+                    bbox,
+                    min_max_size,
+                } => {
+                    let mut exists = false;
+                    layout.with_windows(|win, _, _| exists |= win.0.id == id);
+                    if exists {
+                        return;
+                    }
+
+                    let win = TestWindow::new(id, bbox, min_max_size.0, min_max_size.1);
+                    layout.add_floating_window(win, None, true);
+                }
+                Op::AddWindowAsTab {
+                    id,
+                    bbox,
+                    min_max_size,
+                } => {
+                    let mut exists = false;
+                    layout.with_windows(|win, _, _| exists |= win.0.id == id);
+                    if exists {
+                        return;
+                    }
+
+                    let win = TestWindow::new(id, bbox, min_max_size.0, min_max_size.1);
+                    layout.add_window_as_tab(win, None, false, true);
+                }
+                Op::AddWindowWithoutActivating {
+                    id,
+                    bbox,
+                    min_max_size,
+                } => {
+                    let mut exists = false;
+                    layout.with_windows(|win, _, _| exists |= win.0.id == id);
+                    if exists {
+                        return;
+                    }
+
+                    let win = TestWindow::new(id, bbox, min_max_size.0, min_max_size.1);
+                    layout.add_window(win, None, false, false);
+                }
+                Op::SetNamedWorkspaces(mask) => {
+                    // Each bit of the mask declares one named workspace.
+                    let configs: Vec<_> = (0..3)
+                        .filter(|i| mask & (1 << i) != 0)
+                        .map(|i| niri_config::Workspace {
+                            name: format!("ws{i}"),
+                        })
+                        .collect();
+                    layout.update_named_workspaces(&configs);
+                }
+                Op::AddWindowToNamedWorkspace {
+                    id,
+                    workspace,
+                    bbox,
+                    min_max_size,
+                } => {
+                    let mut exists = false;
+                    layout.with_windows(|win, _, _| exists |= win.0.id == id);
+                    if exists {
+                        return;
+                    }
+
+                    let name = format!("ws{workspace}");
+                    if layout.find_workspace_by_name(&name).is_none() {
+                        return;
+                    }
+
+                    let win = TestWindow::new(id, bbox, min_max_size.0, min_max_size.1);
+                    layout.add_window_to_named_workspace(&name, win, None, false, true);
+                }
+                Op::ActivateWindow(id) => {
+                    // Window activation requires an output.
+                    if layout.outputs().next().is_none() {
+                        return;
+                    }
+
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.activate_window(&dummy);
+                }
+                Op::CloseWindow(id) => {
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.remove_window(&dummy);
                 }
                 Op::FullscreenWindow(id) => {
                     let dummy =
                         TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
                     layout.toggle_fullscreen(&dummy);
                 }
+                Op::MaximizeWindow(id) => {
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.set_maximized(&dummy, true);
+                }
+                Op::MaximizeWindowToWorkingArea(id) => {
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.set_maximized_to_working_area(&dummy, true);
+                }
+                Op::UnmaximizeWindowFromWorkingArea(id) => {
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.set_maximized_to_working_area(&dummy, false);
+                }
+                Op::SetFloatingWindowPosition { id, x, y } => {
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.set_floating_window_position(&dummy, Point::from((x, y)));
+                }
+                Op::UnmaximizeWindow(id) => {
+                    let dummy =
+                        TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+                    layout.set_maximized(&dummy, false);
+                }
                 Op::FocusColumnLeft => layout.focus_left(),
                 Op::FocusColumnRight => layout.focus_right(),
                 Op::FocusColumnFirst => layout.focus_column_first(),
@@ -2129,6 +3311,9 @@ mod tests {
                 Op::ConsumeWindowIntoColumn => layout.consume_into_column(),
                 Op::ExpelWindowFromColumn => layout.expel_from_column(),
                 Op::CenterColumn => layout.center_column(),
+                Op::ToggleColumnTabbedDisplay => layout.toggle_column_tabbed_display(),
+                Op::FocusTabNext => layout.focus_tab_next(),
+                Op::FocusTabPrevious => layout.focus_tab_previous(),
                 Op::FocusWorkspaceDown => layout.switch_workspace_down(),
                 Op::FocusWorkspaceUp => layout.switch_workspace_up(),
                 Op::FocusWorkspace(idx) => layout.switch_workspace(idx),
@@ -2203,6 +3388,39 @@ mod tests {
 
                     layout.move_workspace_to_output(&output);
                 }
+                Op::InteractiveResizeBegin { window, edges } => {
+                    let dummy = TestWindow::new(
+                        window,
+                        Rectangle::default(),
+                        Size::default(),
+                        Size::default(),
+                    );
+                    layout.interactive_resize_begin(&dummy, edges);
+                }
+                Op::InteractiveResizeUpdate {
+                    window,
+                    dx,
+                    dy,
+                    snap,
+                } => {
+                    let dummy = TestWindow::new(
+                        window,
+                        Rectangle::default(),
+                        Size::default(),
+                        Size::default(),
+                    );
+                    layout.interactive_resize_update(&dummy, Point::from((dx, dy)), snap);
+                }
+                Op::InteractiveResizeEnd(window) => {
+                    let dummy = TestWindow::new(
+                        window,
+                        Rectangle::default(),
+                        Size::default(),
+                        Size::default(),
+                    );
+                    layout.interactive_resize_end(&dummy);
+                }
+                Op::ApplyPendingInteractiveResizes => layout.apply_pending_interactive_resizes(),
             }
         }
     }
@@ -2265,12 +3483,47 @@ mod tests {
                 bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
                 min_max_size: Default::default(),
             },
+            Op::AddFloatingWindow {
+                id: 5,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowAsTab {
+                id: 6,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowWithoutActivating {
+                id: 8,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::SetNamedWorkspaces(0b011),
+            Op::SetNamedWorkspaces(0b110),
+            Op::SetNamedWorkspaces(0),
+            Op::AddWindowToNamedWorkspace {
+                id: 9,
+                workspace: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::ActivateWindow(5),
             Op::CloseWindow(0),
             Op::CloseWindow(1),
             Op::CloseWindow(2),
             Op::FullscreenWindow(1),
             Op::FullscreenWindow(2),
             Op::FullscreenWindow(3),
+            Op::MaximizeWindow(1),
+            Op::UnmaximizeWindow(1),
+            Op::MaximizeWindowToWorkingArea(2),
+            Op::MaximizeWindowToWorkingArea(5),
+            Op::UnmaximizeWindowFromWorkingArea(2),
+            Op::SetFloatingWindowPosition {
+                id: 5,
+                x: 100,
+                y: -100,
+            },
             Op::FocusColumnLeft,
             Op::FocusColumnRight,
             Op::FocusWindowUp,
@@ -2282,6 +3535,9 @@ mod tests {
             Op::ConsumeWindowIntoColumn,
             Op::ExpelWindowFromColumn,
             Op::CenterColumn,
+            Op::ToggleColumnTabbedDisplay,
+            Op::FocusTabNext,
+            Op::FocusTabPrevious,
             Op::FocusWorkspaceDown,
             Op::FocusWorkspaceUp,
             Op::FocusWorkspace(1),
@@ -2410,12 +3666,47 @@ mod tests {
                 bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
                 min_max_size: Default::default(),
             },
+            Op::AddFloatingWindow {
+                id: 5,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowAsTab {
+                id: 6,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowWithoutActivating {
+                id: 8,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::SetNamedWorkspaces(0b011),
+            Op::SetNamedWorkspaces(0b110),
+            Op::SetNamedWorkspaces(0),
+            Op::AddWindowToNamedWorkspace {
+                id: 9,
+                workspace: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::ActivateWindow(5),
             Op::CloseWindow(0),
             Op::CloseWindow(1),
             Op::CloseWindow(2),
             Op::FullscreenWindow(1),
             Op::FullscreenWindow(2),
             Op::FullscreenWindow(3),
+            Op::MaximizeWindow(1),
+            Op::UnmaximizeWindow(1),
+            Op::MaximizeWindowToWorkingArea(2),
+            Op::MaximizeWindowToWorkingArea(5),
+            Op::UnmaximizeWindowFromWorkingArea(2),
+            Op::SetFloatingWindowPosition {
+                id: 5,
+                x: 100,
+                y: -100,
+            },
             Op::FocusColumnLeft,
             Op::FocusColumnRight,
             Op::FocusWindowUp,
@@ -2427,6 +3718,9 @@ mod tests {
             Op::ConsumeWindowIntoColumn,
             Op::ExpelWindowFromColumn,
             Op::CenterColumn,
+            Op::ToggleColumnTabbedDisplay,
+            Op::FocusTabNext,
+            Op::FocusTabPrevious,
             Op::FocusWorkspaceDown,
             Op::FocusWorkspaceUp,
             Op::FocusWorkspace(1),
@@ -2786,6 +4080,1069 @@ mod tests {
         check_ops(&ops);
     }
 
+    #[test]
+    fn floating_window_keeps_workspace_alive() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddFloatingWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindow {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::ActivateWindow(1),
+            Op::FullscreenWindow(1),
+            Op::MaximizeWindow(1),
+            Op::CloseWindow(2),
+            Op::FocusWorkspaceDown,
+            Op::FocusWorkspaceUp,
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let (win, _) = layout.active_window().unwrap();
+        assert_eq!(win.0.id, 1);
+    }
+
+    #[test]
+    fn move_focused_floating_window() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddOutput(2),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddFloatingWindow {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        // Returns the monitor and workspace indices of the window, and whether it's floating.
+        let find = |layout: &Layout<TestWindow>, id: usize| {
+            let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+                unreachable!()
+            };
+            for (mon_idx, mon) in monitors.iter().enumerate() {
+                for (ws_idx, ws) in mon.workspaces.iter().enumerate() {
+                    if let Some(win) = ws.windows().find(|win| win.0.id == id) {
+                        return (mon_idx, ws_idx, ws.is_floating(win));
+                    }
+                }
+            }
+            unreachable!()
+        };
+
+        // The focused floating window moves rather than the tiled one.
+        Op::MoveWindowToWorkspaceDown.apply(&mut layout);
+        layout.verify_invariants();
+        assert_eq!(find(&layout, 1), (0, 0, false));
+        assert_eq!(find(&layout, 2), (0, 1, true));
+        let (win, _) = layout.active_window().unwrap();
+        assert_eq!(win.0.id, 2);
+
+        // A workspace with only a floating window can move it too.
+        Op::MoveWindowToOutput(2).apply(&mut layout);
+        layout.verify_invariants();
+        assert_eq!(find(&layout, 2), (1, 0, true));
+
+        // Moving a particular floating window works as well.
+        let output = layout
+            .outputs()
+            .find(|o| o.name() == "output1")
+            .cloned()
+            .unwrap();
+        let dummy = TestWindow::new(2, Rectangle::default(), Size::default(), Size::default());
+        layout.move_window_to_workspace(dummy, &output, None);
+        layout.verify_invariants();
+        let (mon_idx, _, is_floating) = find(&layout, 2);
+        assert_eq!(mon_idx, 0);
+        assert!(is_floating);
+    }
+
+    #[test]
+    fn floating_window_fullscreen_and_back() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddFloatingWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let floating = |layout: &Layout<TestWindow>| {
+            let ws = layout.active_workspace().unwrap();
+            (ws.floating[0].pos, ws.floating[0].tile.window().clone())
+        };
+        let (pos, win) = floating(&layout);
+
+        Op::FullscreenWindow(1).apply(&mut layout);
+        layout.verify_invariants();
+        assert_eq!(floating(&layout).0, Point::from((0, 0)));
+        assert!(win.is_pending_fullscreen());
+
+        // Leaving fullscreen puts the window back where it was.
+        Op::FullscreenWindow(1).apply(&mut layout);
+        layout.verify_invariants();
+        assert_eq!(floating(&layout).0, pos);
+        assert!(!win.is_pending_fullscreen());
+        assert_eq!(win.0.requested_size.get(), Some(Size::from((100, 200))));
+    }
+
+    #[test]
+    fn floating_window_interactive_resize_from_left_edge() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddFloatingWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let ws = layout.active_workspace().unwrap();
+        let pos = ws.floating[0].pos;
+        let win = ws.floating[0].tile.window().clone();
+
+        assert!(layout.interactive_resize_begin(&win, ResizeEdge::Left));
+        assert!(layout.interactive_resize_update(&win, Point::from((-50., 0.)), false));
+        layout.apply_pending_interactive_resizes();
+        layout.interactive_resize_end(&win);
+        layout.verify_invariants();
+
+        // The right edge stays in place.
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.floating[0].pos, pos - Point::from((50, 0)));
+        assert_eq!(win.0.requested_size.get(), Some(Size::from((150, 200))));
+    }
+
+    #[test]
+    fn named_workspace_is_kept_while_declared() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::SetNamedWorkspaces(0b001),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowToNamedWorkspace {
+                id: 2,
+                workspace: 0,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::CloseWindow(2),
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        // The window went to the named workspace without switching to it.
+        let (win, _) = layout.active_window().unwrap();
+        assert_eq!(win.0.id, 1);
+        assert!(layout.find_workspace_by_name("ws0").is_some());
+
+        Op::SetNamedWorkspaces(0).apply(&mut layout);
+        layout.verify_invariants();
+
+        let MonitorSet::Normal { monitors, .. } = layout.monitor_set else {
+            unreachable!()
+        };
+
+        assert_eq!(monitors[0].workspaces.len(), 2);
+        assert!(monitors[0].workspaces.iter().all(|ws| ws.name.is_none()));
+    }
+
+    #[test]
+    fn unmaximize_restores_column_width() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::SetColumnWidth(SizeChange::SetProportion(30.)),
+            Op::MaximizeWindow(1),
+            Op::MaximizeWindow(1),
+            Op::RemoveOutput(1),
+            Op::AddOutput(2),
+            Op::UnmaximizeWindow(1),
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let MonitorSet::Normal { monitors, .. } = layout.monitor_set else {
+            unreachable!()
+        };
+
+        let col = &monitors[0].workspaces[0].columns[0];
+        assert!(!col.is_full_width);
+        assert_eq!(col.width, ColumnWidth::Proportion(0.3));
+    }
+
+    #[test]
+    fn new_window_height_is_clamped_to_workspace() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let win = TestWindow::new(1, Rectangle::default(), Size::default(), Size::default());
+        layout.set_new_window_height(&win, Some(Some(WindowHeight::Fixed(100000))));
+        layout.verify_invariants();
+
+        let ws = layout.active_workspace().unwrap();
+        let full_height = ws.new_window_size(&win, None, None).h;
+        let (win, _) = layout.active_window().unwrap();
+        assert_eq!(win.0.requested_size.get().unwrap().h, full_height);
+    }
+
+    #[test]
+    fn new_window_size_respects_max_size() {
+        let mut layout = Layout::default();
+        Op::AddOutput(1).apply(&mut layout);
+        let ws = layout.active_workspace().unwrap();
+
+        let win = TestWindow::new(
+            1,
+            Rectangle::default(),
+            Size::default(),
+            Size::from((300, 200)),
+        );
+        let default_width = Some(Some(ColumnWidth::Proportion(1.)));
+        let size = ws.new_window_size(&win, default_width, None);
+        assert_eq!(size, Size::from((300, 200)));
+
+        // Leaving the width to the window stays that way.
+        let size = ws.new_window_size(&win, Some(None), Some(None));
+        assert_eq!(size, Size::from((0, 0)));
+    }
+
+    #[test]
+    fn output_default_width_applies_on_its_output() {
+        let options = Options {
+            default_width: Some(ColumnWidth::Proportion(0.5)),
+            output_default_widths: HashMap::from([(
+                String::from("output2"),
+                Some(ColumnWidth::Fixed(500)),
+            )]),
+            ..Default::default()
+        };
+
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddOutput(2),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::FocusOutput(2),
+            Op::AddWindow {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::with_options(options);
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+            unreachable!()
+        };
+        let width = |mon: &Monitor<_>| mon.active_workspace_ref().columns[0].width;
+        assert_eq!(width(&monitors[0]), ColumnWidth::Proportion(0.5));
+        assert_eq!(width(&monitors[1]), ColumnWidth::Fixed(500));
+    }
+
+    #[test]
+    fn add_window_without_activating_keeps_focus() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowWithoutActivating {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let (win, _) = layout.active_window().unwrap();
+        assert_eq!(win.0.id, 1);
+
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.columns.len(), 2);
+    }
+
+    #[test]
+    fn new_window_column_position() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindow {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::FocusColumnLeft,
+            Op::AddWindow {
+                id: 3,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let dummy =
+            |id| TestWindow::new(id, Rectangle::default(), Size::default(), Size::default());
+        let ids = |layout: &Layout<TestWindow>| {
+            let ws = layout.active_workspace().unwrap();
+            let ids: Vec<_> = ws
+                .columns
+                .iter()
+                .map(|col| col.tiles[0].window().0.id)
+                .collect();
+            (ids, ws.active_column_idx)
+        };
+
+        // The focused new window moves along with the focus.
+        layout.set_new_window_column_position(&dummy(3), ColumnPosition::End);
+        layout.verify_invariants();
+        assert_eq!(ids(&layout), (vec![1, 2, 3], 2));
+
+        // An unfocused new window leaves the focus where it was.
+        Op::AddWindowWithoutActivating {
+            id: 4,
+            bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+            min_max_size: Default::default(),
+        }
+        .apply(&mut layout);
+        layout.set_new_window_column_position(&dummy(4), ColumnPosition::Start);
+        layout.verify_invariants();
+        assert_eq!(ids(&layout), (vec![4, 1, 2, 3], 3));
+
+        // A focused new window goes in front of the previously focused column.
+        Op::AddWindow {
+            id: 5,
+            bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+            min_max_size: Default::default(),
+        }
+        .apply(&mut layout);
+        layout.set_new_window_column_position(&dummy(5), ColumnPosition::BeforeFocused);
+        layout.verify_invariants();
+        assert_eq!(ids(&layout), (vec![4, 1, 2, 5, 3], 3));
+
+        // An unfocused one goes in front of the focused column.
+        Op::AddWindowWithoutActivating {
+            id: 6,
+            bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+            min_max_size: Default::default(),
+        }
+        .apply(&mut layout);
+        layout.set_new_window_column_position(&dummy(6), ColumnPosition::BeforeFocused);
+        layout.verify_invariants();
+        assert_eq!(ids(&layout), (vec![4, 1, 2, 6, 5, 3], 4));
+    }
+
+    #[test]
+    fn add_window_on_empty_workspace() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let window = |id| {
+            TestWindow::new(
+                id,
+                Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                Size::default(),
+                Size::default(),
+            )
+        };
+
+        // An unfocused window opens on the empty workspace below.
+        let output = layout.add_window_on_empty_workspace(None, window(2), None, false, false);
+        assert_eq!(output.unwrap().name(), "output1");
+        layout.verify_invariants();
+
+        let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+            unreachable!()
+        };
+        assert_eq!(monitors[0].active_workspace_idx, 0);
+        assert_eq!(monitors[0].workspaces[1].columns.len(), 1);
+        assert!(monitors[0].workspaces[2].columns.is_empty());
+
+        // A focused window switches to its new workspace.
+        layout.add_window_on_empty_workspace(None, window(3), None, false, true);
+        layout.verify_invariants();
+
+        let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+            unreachable!()
+        };
+        assert_eq!(monitors[0].active_workspace_idx, 2);
+        assert_eq!(monitors[0].workspaces.len(), 4);
+    }
+
+    #[test]
+    fn maximize_to_working_area_and_back() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindow {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::MaximizeWindowToWorkingArea(1),
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.columns.len(), 1);
+        assert_eq!(ws.floating.len(), 1);
+        let floating = &ws.floating[0];
+        assert_eq!(floating.tile.window().0.id, 1);
+        assert_eq!(floating.pos, Point::from((0, 0)));
+        assert_eq!(
+            floating.tile.window().0.requested_size.get(),
+            Some(ws.maximized_window_size())
+        );
+
+        Op::UnmaximizeWindowFromWorkingArea(1).apply(&mut layout);
+        layout.verify_invariants();
+
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.columns.len(), 2);
+        assert!(ws.floating.is_empty());
+    }
+
+    #[test]
+    fn floating_window_position_stays_on_screen() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddFloatingWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::SetFloatingWindowPosition {
+                id: 1,
+                x: 10_000,
+                y: -50,
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let ws = layout.active_workspace().unwrap();
+        let floating = &ws.floating[0];
+        let size = floating.tile.tile_size();
+        assert_eq!(floating.pos, Point::from((1280 - size.w, 0)));
+    }
+
+    #[test]
+    fn floating_dialog_follows_parent() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddFloatingWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (400, 300)),
+                min_max_size: Default::default(),
+            },
+            Op::SetFloatingWindowPosition {
+                id: 1,
+                x: 100,
+                y: 100,
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let parent = TestWindow::new(1, Rectangle::default(), Size::default(), Size::default());
+        let dialog = TestWindow::new(
+            2,
+            Rectangle::from_loc_and_size((0, 0), (100, 50)),
+            Size::default(),
+            Size::default(),
+        );
+        dialog.0.parent_id.set(Some(1));
+        layout.add_floating_window_over(&parent, dialog, true);
+        layout.verify_invariants();
+
+        // The dialog opens centered over its parent.
+        let ws = layout.active_workspace().unwrap();
+        let parent_size = ws.floating[0].tile.tile_size();
+        let dialog_size = ws.floating[1].tile.tile_size();
+        let offset = Point::from((
+            (parent_size.w - dialog_size.w) / 2,
+            (parent_size.h - dialog_size.h) / 2,
+        ));
+        assert_eq!(ws.floating[1].pos, ws.floating[0].pos + offset);
+
+        // Moving the parent moves the dialog along.
+        Op::SetFloatingWindowPosition {
+            id: 1,
+            x: 200,
+            y: 150,
+        }
+        .apply(&mut layout);
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.floating[0].pos, Point::from((200, 150)));
+        assert_eq!(ws.floating[1].pos, ws.floating[0].pos + offset);
+
+        // Raising the parent keeps the dialog above it.
+        Op::AddFloatingWindow {
+            id: 3,
+            bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+            min_max_size: Default::default(),
+        }
+        .apply(&mut layout);
+        layout.activate_window(&parent);
+        layout.verify_invariants();
+
+        let ws = layout.active_workspace().unwrap();
+        let order: Vec<_> = ws.floating.iter().map(|f| f.tile.window().0.id).collect();
+        assert_eq!(order, [3, 1, 2]);
+    }
+
+    #[test]
+    fn max_auto_width_limits_window_chosen_width() {
+        let mut layout = Layout::default();
+        Op::AddOutput(1).apply(&mut layout);
+
+        let bbox = Rectangle::from_loc_and_size((0, 0), (1000, 200));
+        let win = TestWindow::new(1, bbox, Size::default(), Size::default());
+        win.0.max_auto_width.set(Some(600));
+        layout.add_window(win, Some(None), false, true);
+        let win = TestWindow::new(2, bbox, Size::default(), Size::default());
+        win.0.max_auto_width.set(Some(1200));
+        layout.add_window(win, Some(None), false, true);
+        layout.verify_invariants();
+
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.columns[0].width, ColumnWidth::Fixed(600));
+        assert_eq!(ws.columns[1].width, ColumnWidth::Fixed(1000));
+    }
+
+    #[test]
+    fn aspect_ratio_sets_height_from_column_width() {
+        let mut layout = Layout::default();
+        Op::AddOutput(1).apply(&mut layout);
+
+        let bbox = Rectangle::from_loc_and_size((0, 0), (100, 100));
+        let win = TestWindow::new(1, bbox, Size::default(), Size::default());
+        win.0.aspect_ratio.set(Some(2.));
+        layout.add_window(
+            win.clone(),
+            Some(Some(ColumnWidth::Fixed(400))),
+            false,
+            true,
+        );
+        layout.verify_invariants();
+
+        assert_eq!(win.0.requested_size.get(), Some(Size::from((400, 200))));
+    }
+
+    #[test]
+    fn floating_window_follows_new_max_size() {
+        let mut layout = Layout::default();
+        Op::AddOutput(1).apply(&mut layout);
+
+        let bbox = Rectangle::from_loc_and_size((0, 0), (300, 200));
+        let win = TestWindow::new(1, bbox, Size::default(), Size::default());
+        layout.add_floating_window(win.clone(), None, true);
+        assert_eq!(win.0.requested_size.get(), None);
+
+        win.0.max_size.set(Size::from((250, 0)));
+        layout.update_window(&win);
+        layout.verify_invariants();
+
+        assert_eq!(win.0.requested_size.get(), Some(Size::from((250, 200))));
+    }
+
+    #[test]
+    fn add_window_below_stacks_in_parent_column() {
+        let mut layout = Layout::default();
+        Op::AddOutput(1).apply(&mut layout);
+
+        let bbox = Rectangle::from_loc_and_size((0, 0), (100, 200));
+        let parent = TestWindow::new(1, bbox, Size::default(), Size::default());
+        layout.add_window(parent.clone(), None, false, true);
+        let win = TestWindow::new(2, bbox, Size::default(), Size::default());
+        layout.add_window(win, None, false, true);
+
+        let win = TestWindow::new(3, bbox, Size::default(), Size::default());
+        layout.add_window_below(&parent, win, None, false, true);
+        layout.verify_invariants();
+
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.columns.len(), 2);
+        let ids: Vec<_> = ws.columns[0]
+            .tiles
+            .iter()
+            .map(|t| t.window().0.id)
+            .collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(ws.active_column_idx, 0);
+        assert_eq!(ws.columns[0].active_tile_idx, 1);
+    }
+
+    #[test]
+    fn always_on_top_floating_window_stays_above() {
+        let mut ops = vec![Op::AddOutput(1)];
+        for id in 1..=2 {
+            ops.push(Op::AddFloatingWindow {
+                id,
+                bbox: Rectangle::from_loc_and_size((0, 0), (200, 200)),
+                min_max_size: Default::default(),
+            });
+            ops.push(Op::SetFloatingWindowPosition { id, x: 0, y: 0 });
+        }
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let pos = Point::from((10., 10.));
+        let ws = layout.active_workspace().unwrap();
+        assert_eq!(ws.window_under(pos).unwrap().0 .0.id, 2);
+
+        // The first window goes on top even though the second one is focused.
+        ws.floating[0].tile.window().0.always_on_top.set(true);
+        assert_eq!(ws.floating.last().unwrap().tile.window().0.id, 2);
+        assert_eq!(ws.window_under(pos).unwrap().0 .0.id, 1);
+    }
+
+    #[test]
+    fn tabbed_column_windows_get_full_height() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindowAsTab {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::Communicate(1),
+            Op::Communicate(2),
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let (win, _) = layout.active_window().unwrap();
+        assert_eq!(win.0.id, 2);
+
+        let ws = layout.active_workspace().unwrap();
+        let col = &ws.columns[ws.active_column_idx];
+        assert_eq!(col.tiles.len(), 2);
+        assert_eq!(col.display, ColumnDisplay::Tabbed);
+
+        let h1 = col.tiles[0].window().0.requested_size.get().unwrap().h;
+        let h2 = col.tiles[1].window().0.requested_size.get().unwrap().h;
+        assert_eq!(h1, h2);
+
+        layout.toggle_column_tabbed_display();
+        layout.verify_invariants();
+
+        let ws = layout.active_workspace().unwrap();
+        let col = &ws.columns[ws.active_column_idx];
+        let h = col.tiles[0].window().0.requested_size.get().unwrap().h;
+        assert!(h < h1);
+    }
+
+    #[test]
+    fn move_window_to_output_and_back() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddOutput(2),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let (win, output1) = layout.active_window().unwrap();
+        let (win, output1) = (win.clone(), output1.clone());
+        let (_, ws_id) = layout.window_location(&win).unwrap();
+        let output2 = layout
+            .outputs()
+            .find(|o| o.name() == "output2")
+            .unwrap()
+            .clone();
+
+        layout.move_window_to_output(win.clone(), &output2);
+        layout.verify_invariants();
+        let (output, _) = layout.window_location(&win).unwrap();
+        assert_eq!(output, &output2);
+
+        layout.move_window_to_workspace(win.clone(), &output1, Some(ws_id));
+        layout.verify_invariants();
+        let (output, id) = layout.window_location(&win).unwrap();
+        assert_eq!(output, &output1);
+        assert_eq!(id, ws_id);
+    }
+
+    #[test]
+    fn interactive_resize() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::AddWindow {
+                id: 2,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::ConsumeWindowIntoColumn,
+            Op::InteractiveResizeBegin {
+                window: 1,
+                edges: ResizeEdge::BottomLeft,
+            },
+            Op::InteractiveResizeUpdate {
+                window: 1,
+                dx: -50.,
+                dy: 1e10,
+                snap: false,
+            },
+            Op::ApplyPendingInteractiveResizes,
+            Op::Communicate(1),
+            Op::Communicate(2),
+            Op::FullscreenWindow(1),
+            Op::InteractiveResizeUpdate {
+                window: 1,
+                dx: -1e10,
+                dy: -1e10,
+                snap: false,
+            },
+            Op::ApplyPendingInteractiveResizes,
+            Op::InteractiveResizeEnd(1),
+            Op::CloseWindow(1),
+            Op::InteractiveResizeEnd(1),
+        ];
+
+        check_ops(&ops);
+    }
+
+    #[test]
+    fn with_windows_follows_workspace_moves() {
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+        ];
+
+        let mut layout = Layout::default();
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let window_ws = |layout: &Layout<TestWindow>| {
+            let mut ws_id = None;
+            layout.with_windows(|win, _, id| {
+                if win.0.id == 1 {
+                    ws_id = Some(id);
+                }
+            });
+            ws_id.unwrap()
+        };
+        let before = window_ws(&layout);
+
+        // with_windows() yields the id of the workspace the window moved to.
+        Op::MoveWindowToWorkspaceDown.apply(&mut layout);
+        layout.verify_invariants();
+
+        let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+            unreachable!()
+        };
+        let after = window_ws(&layout);
+        assert_ne!(before, after);
+        assert_eq!(after, monitors[0].workspaces[1].id());
+    }
+
+    #[test]
+    fn interactive_resize_snaps_to_preset_width() {
+        let options = Options {
+            border: niri_config::Border {
+                off: true,
+                ..Default::default()
+            },
+            snap_to_preset_widths: true,
+            ..Default::default()
+        };
+        let mut layout = Layout::with_options(options);
+
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::InteractiveResizeBegin {
+                window: 1,
+                edges: ResizeEdge::Right,
+            },
+            // One half of the output is (1280 - 16) * 0.5 - 16 = 616 px.
+            Op::InteractiveResizeUpdate {
+                window: 1,
+                dx: 510.,
+                dy: 0.,
+                snap: true,
+            },
+            Op::ApplyPendingInteractiveResizes,
+        ];
+        for op in ops {
+            op.apply(&mut layout);
+        }
+        layout.verify_invariants();
+
+        let column_width = |layout: &Layout<TestWindow>| {
+            let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+                unreachable!()
+            };
+            monitors[0].workspaces[0].columns[0].width
+        };
+        assert_eq!(column_width(&layout), ColumnWidth::Preset(1));
+
+        // Without snapping, the width follows the pointer.
+        let ops = [
+            Op::InteractiveResizeUpdate {
+                window: 1,
+                dx: 510.,
+                dy: 0.,
+                snap: false,
+            },
+            Op::ApplyPendingInteractiveResizes,
+        ];
+        for op in ops {
+            op.apply(&mut layout);
+        }
+        layout.verify_invariants();
+        assert_eq!(column_width(&layout), ColumnWidth::Fixed(610));
+    }
+
+    #[test]
+    fn column_width_change_unmaximizes() {
+        let mut layout = Layout::default();
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::MaximizeWindow(1),
+        ];
+        for op in ops {
+            op.apply(&mut layout);
+        }
+
+        let window = TestWindow::new(1, Rectangle::default(), Size::default(), Size::default());
+        assert!(layout.is_maximized(&window));
+
+        Op::SetColumnWidth(SizeChange::SetProportion(50.)).apply(&mut layout);
+        layout.verify_invariants();
+        assert!(!layout.is_maximized(&window));
+
+        // Toggling the full width off also unmaximizes.
+        Op::MaximizeWindow(1).apply(&mut layout);
+        assert!(layout.is_maximized(&window));
+        layout.toggle_full_width();
+        layout.verify_invariants();
+        assert!(!layout.is_maximized(&window));
+    }
+
+    #[test]
+    fn unfullscreen_keeps_width_and_maximized_state() {
+        let mut layout = Layout::default();
+        let ops = [
+            Op::AddOutput(1),
+            Op::AddWindow {
+                id: 1,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            },
+            Op::SetColumnWidth(SizeChange::SetFixed(500)),
+        ];
+        for op in ops {
+            op.apply(&mut layout);
+        }
+        let width = layout.active_workspace().unwrap().columns[0].width;
+
+        let ops = [
+            Op::MaximizeWindow(1),
+            Op::FullscreenWindow(1),
+            Op::FullscreenWindow(1),
+        ];
+        for op in ops {
+            op.apply(&mut layout);
+            layout.verify_invariants();
+        }
+
+        let window = TestWindow::new(1, Rectangle::default(), Size::default(), Size::default());
+        assert!(layout.is_maximized(&window));
+        let col = &layout.active_workspace().unwrap().columns[0];
+        assert!(col.is_full_width);
+
+        // Unmaximizing goes back to the width from before the fullscreen.
+        layout.set_maximized(&window, false);
+        layout.verify_invariants();
+        let col = &layout.active_workspace().unwrap().columns[0];
+        assert!(!col.is_full_width);
+        assert_eq!(col.width, width);
+    }
+
+    #[test]
+    fn max_windows_per_column_limits_consume() {
+        let options = Options {
+            max_windows_per_column: Some(2),
+            ..Default::default()
+        };
+        let mut layout = Layout::with_options(options);
+
+        let mut ops = vec![Op::AddOutput(1)];
+        for id in 1..=3 {
+            ops.push(Op::AddWindow {
+                id,
+                bbox: Rectangle::from_loc_and_size((0, 0), (100, 200)),
+                min_max_size: Default::default(),
+            });
+        }
+        ops.extend([
+            Op::ConsumeOrExpelWindowLeft,
+            Op::FocusColumnLeft,
+            Op::ConsumeWindowIntoColumn,
+            Op::FocusColumnRight,
+            Op::ConsumeOrExpelWindowLeft,
+        ]);
+        for op in ops {
+            op.apply(&mut layout);
+        }
+        layout.verify_invariants();
+
+        let MonitorSet::Normal { monitors, .. } = &layout.monitor_set else {
+            unreachable!()
+        };
+        let columns = &monitors[0].workspaces[0].columns;
+        assert_eq!(columns.len(), 2);
+        assert!(columns.iter().all(|col| col.tiles.len() <= 2));
+    }
+
     #[test]
     fn open_right_of_on_different_workspace() {
         let ops = [
@@ -2893,6 +5250,7 @@ mod tests {
             focus_ring in arbitrary_focus_ring(),
             border in arbitrary_border(),
             center_focused_column in arbitrary_center_focused_column(),
+            snap_to_preset_widths in any::<bool>(),
         ) -> Options {
             Options {
                 gaps: gaps.into(),
@@ -2900,6 +5258,7 @@ mod tests {
                 center_focused_column,
                 focus_ring,
                 border,
+                snap_to_preset_widths,
                 ..Default::default()
             }
         }
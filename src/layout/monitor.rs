@@ -15,6 +15,7 @@ use super::workspace::{
 use super::{LayoutElement, Options};
 use crate::animation::Animation;
 use crate::render_helpers::renderer::NiriRenderer;
+use crate::render_helpers::RenderTarget;
 use crate::utils::output_size;
 
 #[derive(Debug)]
@@ -131,12 +132,42 @@ impl<W: LayoutElement> Monitor<W> {
         }
     }
 
+    /// Adds a window as a new tab in the active column of the active workspace.
+    pub fn add_window_as_tab(&mut self, window: W, activate: bool) {
+        let workspace = &mut self.workspaces[self.active_workspace_idx];
+
+        workspace.add_window_as_tab(window, activate);
+
+        // After adding a new window, workspace becomes this output's own.
+        workspace.original_output = OutputId::new(&self.output);
+    }
+
+    pub fn add_floating_window(&mut self, workspace_idx: usize, window: W, activate: bool) {
+        let workspace = &mut self.workspaces[workspace_idx];
+
+        workspace.add_floating_window(window, activate);
+
+        // After adding a new window, workspace becomes this output's own.
+        workspace.original_output = OutputId::new(&self.output);
+
+        if workspace_idx == self.workspaces.len() - 1 {
+            // Insert a new empty workspace.
+            let ws = Workspace::new(self.output.clone(), self.options.clone());
+            self.workspaces.push(ws);
+        }
+
+        if activate {
+            self.activate_workspace(workspace_idx);
+        }
+    }
+
     pub fn add_window_right_of(
         &mut self,
         right_of: &W,
         window: W,
         width: ColumnWidth,
         is_full_width: bool,
+        activate: bool,
     ) {
         let workspace_idx = self
             .workspaces
@@ -145,7 +176,21 @@ impl<W: LayoutElement> Monitor<W> {
             .unwrap();
         let workspace = &mut self.workspaces[workspace_idx];
 
-        workspace.add_window_right_of(right_of, window, width, is_full_width);
+        workspace.add_window_right_of(right_of, window, width, is_full_width, activate);
+
+        // After adding a new window, workspace becomes this output's own.
+        workspace.original_output = OutputId::new(&self.output);
+    }
+
+    pub fn add_window_below(&mut self, below: &W, window: W, activate: bool) {
+        let workspace_idx = self
+            .workspaces
+            .iter_mut()
+            .position(|ws| ws.has_window(below))
+            .unwrap();
+        let workspace = &mut self.workspaces[workspace_idx];
+
+        workspace.add_window_below(below, window, activate);
 
         // After adding a new window, workspace becomes this output's own.
         workspace.original_output = OutputId::new(&self.output);
@@ -178,7 +223,7 @@ impl<W: LayoutElement> Monitor<W> {
                 continue;
             }
 
-            if !self.workspaces[idx].has_windows() {
+            if !self.workspaces[idx].has_windows_or_name() {
                 self.workspaces.remove(idx);
                 if self.active_workspace_idx > idx {
                     self.active_workspace_idx -= 1;
@@ -312,6 +357,11 @@ impl<W: LayoutElement> Monitor<W> {
         }
 
         let workspace = &mut self.workspaces[source_workspace_idx];
+        if let Some(window) = workspace.remove_active_floating_window() {
+            self.add_floating_window(new_idx, window, true);
+            return;
+        }
+
         if workspace.columns.is_empty() {
             return;
         }
@@ -334,6 +384,11 @@ impl<W: LayoutElement> Monitor<W> {
         }
 
         let workspace = &mut self.workspaces[source_workspace_idx];
+        if let Some(window) = workspace.remove_active_floating_window() {
+            self.add_floating_window(new_idx, window, true);
+            return;
+        }
+
         if workspace.columns.is_empty() {
             return;
         }
@@ -356,17 +411,21 @@ impl<W: LayoutElement> Monitor<W> {
         }
 
         let workspace = &mut self.workspaces[source_workspace_idx];
-        if workspace.columns.is_empty() {
-            return;
-        }
+        if let Some(window) = workspace.remove_active_floating_window() {
+            self.add_floating_window(new_idx, window, true);
+        } else {
+            if workspace.columns.is_empty() {
+                return;
+            }
 
-        let column = &workspace.columns[workspace.active_column_idx];
-        let width = column.width;
-        let is_full_width = column.is_full_width;
-        let window =
-            workspace.remove_window_by_idx(workspace.active_column_idx, column.active_tile_idx);
+            let column = &workspace.columns[workspace.active_column_idx];
+            let width = column.width;
+            let is_full_width = column.is_full_width;
+            let window =
+                workspace.remove_window_by_idx(workspace.active_column_idx, column.active_tile_idx);
 
-        self.add_window(new_idx, window, true, width, is_full_width);
+            self.add_window(new_idx, window, true, width, is_full_width);
+        }
 
         // Don't animate this action.
         self.workspace_switch = None;
@@ -461,14 +520,20 @@ impl<W: LayoutElement> Monitor<W> {
         self.active_workspace().center_column();
     }
 
-    pub fn focus(&self) -> Option<&W> {
-        let workspace = &self.workspaces[self.active_workspace_idx];
-        if !workspace.has_windows() {
-            return None;
-        }
+    pub fn toggle_column_tabbed_display(&mut self) {
+        self.active_workspace().toggle_column_tabbed_display();
+    }
 
-        let column = &workspace.columns[workspace.active_column_idx];
-        Some(column.tiles[column.active_tile_idx].window())
+    pub fn focus_tab_next(&mut self) {
+        self.active_workspace().focus_tab_next();
+    }
+
+    pub fn focus_tab_previous(&mut self) {
+        self.active_workspace().focus_tab_previous();
+    }
+
+    pub fn focus(&self) -> Option<&W> {
+        self.workspaces[self.active_workspace_idx].active_window()
     }
 
     pub fn advance_animations(&mut self, current_time: Duration, is_active: bool) {
@@ -601,6 +666,19 @@ impl<W: LayoutElement> Monitor<W> {
         }
     }
 
+    /// Returns whether the workspace at `idx` is on screen, or is about to be.
+    pub fn is_workspace_visible(&self, idx: usize) -> bool {
+        if idx == self.active_workspace_idx {
+            return true;
+        }
+
+        // During a switch, the workspaces on both sides of the current position are on screen.
+        self.workspace_switch.as_ref().map_or(false, |switch| {
+            let render_idx = switch.current_idx();
+            idx == render_idx.floor() as usize || idx == render_idx.ceil() as usize
+        })
+    }
+
     pub fn render_above_top_layer(&self) -> bool {
         // Render above the top layer only if the view is stationary.
         if self.workspace_switch.is_some() {
@@ -614,6 +692,7 @@ impl<W: LayoutElement> Monitor<W> {
     pub fn render_elements<R: NiriRenderer>(
         &self,
         renderer: &mut R,
+        target: RenderTarget,
     ) -> Vec<MonitorRenderElement<R>> {
         let _span = tracy_client::span!("Monitor::render_elements");
 
@@ -630,8 +709,8 @@ impl<W: LayoutElement> Monitor<W> {
 
                 let offset = ((render_idx - before_idx as f64) * size.h as f64).round() as i32;
 
-                let before = self.workspaces[before_idx].render_elements(renderer);
-                let after = self.workspaces[after_idx].render_elements(renderer);
+                let before = self.workspaces[before_idx].render_elements(renderer, target);
+                let after = self.workspaces[after_idx].render_elements(renderer, target);
 
                 let before = before.into_iter().filter_map(|elem| {
                     Some(RelocateRenderElement::from_element(
@@ -658,7 +737,8 @@ impl<W: LayoutElement> Monitor<W> {
                 before.chain(after).collect()
             }
             None => {
-                let elements = self.workspaces[self.active_workspace_idx].render_elements(renderer);
+                let elements =
+                    self.workspaces[self.active_workspace_idx].render_elements(renderer, target);
                 elements
                     .into_iter()
                     .filter_map(|elem| {
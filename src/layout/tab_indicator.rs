@@ -0,0 +1,82 @@
+use std::cmp::max;
+use std::iter::zip;
+
+use niri_config::Color;
+use smithay::backend::renderer::element::solid::{SolidColorBuffer, SolidColorRenderElement};
+use smithay::backend::renderer::element::Kind;
+use smithay::utils::{Logical, Point, Scale};
+
+/// Height of the tab indicator strip.
+pub const TAB_INDICATOR_HEIGHT: i32 = 4;
+
+/// Vertical space between the tab indicator and the tabs.
+pub const TAB_INDICATOR_GAP: i32 = 4;
+
+/// Horizontal space between the tab indicator segments.
+const SEGMENT_GAP: i32 = 2;
+
+/// Strip above a tabbed column with one segment for every tab.
+#[derive(Debug, Default)]
+pub struct TabIndicator {
+    buffers: Vec<SolidColorBuffer>,
+    locations: Vec<Point<i32, Logical>>,
+}
+
+pub type TabIndicatorRenderElement = SolidColorRenderElement;
+
+impl TabIndicator {
+    pub fn update(
+        &mut self,
+        width: i32,
+        tab_count: usize,
+        active_idx: usize,
+        active_color: Color,
+        inactive_color: Color,
+    ) {
+        self.buffers.resize_with(tab_count, Default::default);
+        self.locations.resize(tab_count, Point::default());
+
+        if tab_count == 0 {
+            return;
+        }
+
+        let gaps = SEGMENT_GAP * (tab_count as i32 - 1);
+        let segment_width = max((width - gaps) / tab_count as i32, 1);
+
+        let mut x = 0;
+        for (idx, (buf, loc)) in zip(&mut self.buffers, &mut self.locations).enumerate() {
+            // The last segment takes the remainder left after integer division.
+            let w = if idx + 1 == tab_count {
+                max(width - x, 1)
+            } else {
+                segment_width
+            };
+            let color = if idx == active_idx {
+                active_color
+            } else {
+                inactive_color
+            };
+
+            buf.update((w, TAB_INDICATOR_HEIGHT), color.into());
+            *loc = Point::from((x, 0));
+
+            x += segment_width + SEGMENT_GAP;
+        }
+    }
+
+    pub fn render(
+        &self,
+        location: Point<i32, Logical>,
+        scale: Scale<f64>,
+    ) -> impl Iterator<Item = TabIndicatorRenderElement> + '_ {
+        zip(&self.buffers, &self.locations).map(move |(buf, loc)| {
+            SolidColorRenderElement::from_buffer(
+                buf,
+                (location + *loc).to_physical_precise_round(scale),
+                scale,
+                1.,
+                Kind::Unspecified,
+            )
+        })
+    }
+}
@@ -7,14 +7,17 @@ use smithay::backend::renderer::element::utils::{
     Relocate, RelocateRenderElement, RescaleRenderElement,
 };
 use smithay::backend::renderer::element::{Element, Kind};
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::ResizeEdge;
 use smithay::utils::{Logical, Point, Rectangle, Scale, Size};
 
 use super::focus_ring::FocusRing;
+use super::workspace::ColumnWidth;
 use super::{LayoutElement, LayoutElementRenderElement, Options};
 use crate::animation::Animation;
 use crate::niri_render_elements;
 use crate::render_helpers::offscreen::OffscreenRenderElement;
 use crate::render_helpers::renderer::NiriRenderer;
+use crate::render_helpers::RenderTarget;
 
 /// Toplevel window with decorations.
 #[derive(Debug)]
@@ -46,10 +49,34 @@ pub struct Tile<W: LayoutElement> {
     /// The animation upon opening a window.
     open_animation: Option<Animation>,
 
+    /// Width of the column before the window was maximized.
+    ///
+    /// This is set while the window is maximized, and is restored upon unmaximizing.
+    pre_maximize_width: Option<ColumnWidth>,
+
+    /// Ongoing interactive resize of this tile, if any.
+    interactive_resize: Option<InteractiveResize>,
+
     /// Configurable properties of the layout.
     options: Rc<Options>,
 }
 
+/// State of an interactive (pointer-driven) resize of a tile.
+#[derive(Debug, Clone, Copy)]
+pub struct InteractiveResize {
+    /// Edges that are being dragged.
+    pub edges: ResizeEdge,
+    /// Size of the window when the resize started.
+    pub original_window_size: Size<i32, Logical>,
+    /// Pointer movement since the start of the resize that was not yet applied to the layout.
+    ///
+    /// Resizes are applied at most once per frame, so that we don't flood the client with
+    /// configures on every pointer motion event.
+    pub pending_delta: Option<Point<f64, Logical>>,
+    /// Whether the column width should snap to nearby preset widths.
+    pub snap: bool,
+}
+
 niri_render_elements! {
     TileRenderElement => {
         LayoutElement = LayoutElementRenderElement<R>,
@@ -58,6 +85,26 @@ niri_render_elements! {
     }
 }
 
+impl InteractiveResize {
+    /// Returns how the window width and height change with the pointer position.
+    ///
+    /// Each component is `1` if the size grows along with the pointer coordinate, `-1` if it
+    /// shrinks, and `0` if the size along this axis is not being resized.
+    pub fn edge_signs(&self) -> (i32, i32) {
+        let x = match self.edges {
+            ResizeEdge::Left | ResizeEdge::TopLeft | ResizeEdge::BottomLeft => -1,
+            ResizeEdge::Right | ResizeEdge::TopRight | ResizeEdge::BottomRight => 1,
+            _ => 0,
+        };
+        let y = match self.edges {
+            ResizeEdge::Top | ResizeEdge::TopLeft | ResizeEdge::TopRight => -1,
+            ResizeEdge::Bottom | ResizeEdge::BottomLeft | ResizeEdge::BottomRight => 1,
+            _ => 0,
+        };
+        (x, y)
+    }
+}
+
 impl<W: LayoutElement> Tile<W> {
     pub fn new(window: W, options: Rc<Options>) -> Self {
         Self {
@@ -68,6 +115,8 @@ impl<W: LayoutElement> Tile<W> {
             fullscreen_backdrop: SolidColorBuffer::new((0, 0), [0., 0., 0., 1.]),
             fullscreen_size: Default::default(),
             open_animation: None,
+            pre_maximize_width: None,
+            interactive_resize: None,
             options,
         }
     }
@@ -92,10 +141,16 @@ impl<W: LayoutElement> Tile<W> {
             self.window.size(),
             self.window.has_ssd(),
         );
+        let (active_color, inactive_color) = self.window.border_color_overrides();
+        self.border
+            .set_color_overrides(active_color, inactive_color);
+        let is_urgent = self.window.is_urgent();
+        self.border.set_urgent(is_urgent);
         self.border.set_active(is_active);
 
         self.focus_ring
             .update((0, 0).into(), self.tile_size(), self.has_ssd());
+        self.focus_ring.set_urgent(is_urgent);
         self.focus_ring.set_active(is_active);
 
         match &mut self.open_animation {
@@ -122,6 +177,22 @@ impl<W: LayoutElement> Tile<W> {
         ));
     }
 
+    pub fn pre_maximize_width(&self) -> Option<ColumnWidth> {
+        self.pre_maximize_width
+    }
+
+    pub fn set_pre_maximize_width(&mut self, width: Option<ColumnWidth>) {
+        self.pre_maximize_width = width;
+    }
+
+    pub fn interactive_resize_mut(&mut self) -> Option<&mut InteractiveResize> {
+        self.interactive_resize.as_mut()
+    }
+
+    pub fn set_interactive_resize(&mut self, resize: Option<InteractiveResize>) {
+        self.interactive_resize = resize;
+    }
+
     pub fn window(&self) -> &W {
         &self.window
     }
@@ -244,6 +315,14 @@ impl<W: LayoutElement> Tile<W> {
         }
     }
 
+    pub fn window_width_for_tile_width(&self, size: i32) -> i32 {
+        if self.border.is_off() {
+            size
+        } else {
+            size.saturating_sub(self.border.width() * 2)
+        }
+    }
+
     pub fn tile_height_for_window_height(&self, size: i32) -> i32 {
         if self.border.is_off() {
             size
@@ -305,10 +384,11 @@ impl<W: LayoutElement> Tile<W> {
         location: Point<i32, Logical>,
         scale: Scale<f64>,
         focus_ring: bool,
+        target: RenderTarget,
     ) -> impl Iterator<Item = TileRenderElement<R>> {
         let rv = self
             .window
-            .render(renderer, location + self.window_loc(), scale)
+            .render(renderer, location + self.window_loc(), scale, target)
             .into_iter()
             .map(Into::into);
 
@@ -324,6 +404,8 @@ impl<W: LayoutElement> Tile<W> {
         });
         let rv = rv.chain(elem.into_iter().flatten());
 
+        // Urgent windows show the focus ring to draw attention even when they aren't focused.
+        let focus_ring = focus_ring || self.window.is_urgent();
         let elem = focus_ring.then(|| {
             self.focus_ring.render(scale).map(move |elem| {
                 RelocateRenderElement::from_element(
@@ -355,10 +437,11 @@ impl<W: LayoutElement> Tile<W> {
         location: Point<i32, Logical>,
         scale: Scale<f64>,
         focus_ring: bool,
+        target: RenderTarget,
     ) -> impl Iterator<Item = TileRenderElement<R>> {
         if let Some(anim) = &self.open_animation {
             let renderer = renderer.as_gles_renderer();
-            let elements = self.render_inner(renderer, location, scale, focus_ring);
+            let elements = self.render_inner(renderer, location, scale, focus_ring, target);
             let elements = elements.collect::<Vec<TileRenderElement<_>>>();
 
             let elem = OffscreenRenderElement::new(
@@ -386,7 +469,7 @@ impl<W: LayoutElement> Tile<W> {
         } else {
             self.window().set_offscreen_element_id(None);
 
-            let elements = self.render_inner(renderer, location, scale, focus_ring);
+            let elements = self.render_inner(renderer, location, scale, focus_ring, target);
             None.into_iter().chain(Some(elements).into_iter().flatten())
         }
     }
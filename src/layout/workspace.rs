@@ -1,31 +1,47 @@
 use std::cmp::{max, min};
 use std::iter::{self, zip};
+use std::mem;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 
-use niri_config::{CenterFocusedColumn, PresetWidth, Struts};
+use niri_config::{CenterFocusedColumn, ColumnPosition, PresetHeight, PresetWidth, Struts};
 use niri_ipc::SizeChange;
 use smithay::desktop::space::SpaceElement;
 use smithay::desktop::{layer_map_for_output, Window};
 use smithay::output::Output;
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::{self, ResizeEdge};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::utils::{Logical, Point, Rectangle, Scale, Size};
+use smithay::wayland::shell::xdg::ToplevelState;
 
-use super::tile::{Tile, TileRenderElement};
+use super::tab_indicator::{
+    TabIndicator, TabIndicatorRenderElement, TAB_INDICATOR_GAP, TAB_INDICATOR_HEIGHT,
+};
+use super::tile::{InteractiveResize, Tile, TileRenderElement};
 use super::{LayoutElement, Options};
 use crate::animation::Animation;
 use crate::niri_render_elements;
 use crate::render_helpers::renderer::NiriRenderer;
+use crate::render_helpers::RenderTarget;
 use crate::utils::output_size;
 
 #[derive(Debug)]
 pub struct Workspace<W: LayoutElement> {
+    /// Unique ID of this workspace.
+    id: WorkspaceId,
+
     /// The original output of this workspace.
     ///
     /// Most of the time this will be the workspace's current output, however, after an output
     /// disconnection, it may remain pointing to the disconnected output.
     pub original_output: OutputId,
 
+    /// Name of this workspace, if it was declared in the config.
+    ///
+    /// Named workspaces are not removed when they become empty.
+    pub name: Option<String>,
+
     /// Current output of this workspace.
     output: Option<Output>,
 
@@ -47,6 +63,14 @@ pub struct Workspace<W: LayoutElement> {
     /// Index of the currently active column, if any.
     pub active_column_idx: usize,
 
+    /// Windows floating above the columns, in stacking order from bottom to top.
+    pub floating: Vec<FloatingTile<W>>,
+
+    /// Whether the topmost floating window, rather than the active column, is focused.
+    ///
+    /// Can only be `true` when there are floating windows.
+    floating_is_active: bool,
+
     /// Offset of the view computed from the active column.
     ///
     /// Any gaps, including left padding from work area left exclusive zone, is handled
@@ -74,9 +98,25 @@ pub struct Workspace<W: LayoutElement> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OutputId(String);
 
+/// Unique ID of a workspace that stays the same as workspaces move around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspaceId(u32);
+
+impl WorkspaceId {
+    fn next() -> Self {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
 niri_render_elements! {
     WorkspaceRenderElement => {
         Tile = TileRenderElement<R>,
+        TabIndicator = TabIndicatorRenderElement,
     }
 }
 
@@ -114,6 +154,47 @@ pub enum WindowHeight {
     Fixed(i32),
 }
 
+/// How the windows of a column are displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnDisplay {
+    /// Windows are stacked vertically, sharing the column height.
+    #[default]
+    Normal,
+    /// Only the active window is shown, taking up the full column height, with a tab indicator
+    /// above it.
+    Tabbed,
+}
+
+/// Window floating above the columns.
+#[derive(Debug)]
+pub struct FloatingTile<W: LayoutElement> {
+    /// The tile with the window.
+    pub tile: Tile<W>,
+
+    /// Location of the tile relative to the view.
+    pub pos: Point<i32, Logical>,
+
+    /// Where the window was before it got maximized to the working area, if it is.
+    pub pre_maximize: Option<PreMaximize>,
+
+    /// Geometry of the tile from before the window went fullscreen, if it is.
+    pub pre_fullscreen: Option<Rectangle<i32, Logical>>,
+
+    /// Location of the tile when the ongoing interactive resize started.
+    ///
+    /// Dragging the left or top edge moves the tile, keeping the opposite edge in place.
+    pub resize_origin: Option<Point<i32, Logical>>,
+}
+
+/// Placement of a window from before it got maximized to the working area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreMaximize {
+    /// In a column of this width.
+    Tiled(ColumnWidth),
+    /// Floating with this tile geometry.
+    Floating(Rectangle<i32, Logical>),
+}
+
 #[derive(Debug)]
 pub struct Column<W: LayoutElement> {
     /// Tiles in this column.
@@ -143,6 +224,12 @@ pub struct Column<W: LayoutElement> {
     /// Whether this column contains a single full-screened window.
     pub is_fullscreen: bool,
 
+    /// How the windows of this column are displayed.
+    pub display: ColumnDisplay,
+
+    /// Indicator of the tabs, shown in tabbed display.
+    tab_indicator: TabIndicator,
+
     /// Latest known view size for this column's workspace.
     view_size: Size<i32, Logical>,
 
@@ -180,16 +267,28 @@ impl From<PresetWidth> for ColumnWidth {
     }
 }
 
+impl From<PresetHeight> for WindowHeight {
+    fn from(value: PresetHeight) -> Self {
+        match value {
+            PresetHeight::Fixed(f) => Self::Fixed(f.clamp(1, 100000)),
+        }
+    }
+}
+
 impl<W: LayoutElement> Workspace<W> {
     pub fn new(output: Output, options: Rc<Options>) -> Self {
         let working_area = compute_working_area(&output, options.struts);
         Self {
+            id: WorkspaceId::next(),
             original_output: OutputId::new(&output),
+            name: None,
             view_size: output_size(&output),
             working_area,
             output: Some(output),
             columns: vec![],
             active_column_idx: 0,
+            floating: vec![],
+            floating_is_active: false,
             view_offset: 0,
             view_offset_anim: None,
             activate_prev_column_on_removal: false,
@@ -199,12 +298,16 @@ impl<W: LayoutElement> Workspace<W> {
 
     pub fn new_no_outputs(options: Rc<Options>) -> Self {
         Self {
+            id: WorkspaceId::next(),
             output: None,
             original_output: OutputId(String::new()),
+            name: None,
             view_size: Size::from((1280, 720)),
             working_area: Rectangle::from_loc_and_size((0, 0), (1280, 720)),
             columns: vec![],
             active_column_idx: 0,
+            floating: vec![],
+            floating_is_active: false,
             view_offset: 0,
             view_offset_anim: None,
             activate_prev_column_on_removal: false,
@@ -225,13 +328,25 @@ impl<W: LayoutElement> Workspace<W> {
         }
 
         for (col_idx, col) in self.columns.iter_mut().enumerate() {
-            let is_active = is_active && col_idx == self.active_column_idx;
+            let is_active =
+                is_active && !self.floating_is_active && col_idx == self.active_column_idx;
             col.advance_animations(current_time, is_active);
         }
+
+        let floating_count = self.floating.len();
+        for (idx, floating) in self.floating.iter_mut().enumerate() {
+            let is_active = is_active && self.floating_is_active && idx + 1 == floating_count;
+            floating.tile.advance_animations(current_time, is_active);
+        }
     }
 
     pub fn are_animations_ongoing(&self) -> bool {
-        self.view_offset_anim.is_some() || self.columns.iter().any(Column::are_animations_ongoing)
+        self.view_offset_anim.is_some()
+            || self.columns.iter().any(Column::are_animations_ongoing)
+            || self
+                .floating
+                .iter()
+                .any(|f| f.tile.are_animations_ongoing())
     }
 
     pub fn update_config(&mut self, options: Rc<Options>) {
@@ -239,16 +354,53 @@ impl<W: LayoutElement> Workspace<W> {
             column.update_config(options.clone());
         }
 
+        for floating in &mut self.floating {
+            floating.tile.update_config(options.clone());
+        }
+
         self.options = options;
     }
 
     pub fn windows(&self) -> impl Iterator<Item = &W> + '_ {
+        let floating = self.floating.iter().map(|f| &f.tile);
         self.columns
             .iter()
             .flat_map(|col| col.tiles.iter())
+            .chain(floating)
             .map(Tile::window)
     }
 
+    pub fn is_floating(&self, window: &W) -> bool {
+        self.floating.iter().any(|f| f.tile.window() == window)
+    }
+
+    /// Returns whether the window is currently maximized, either in its column or to the working
+    /// area.
+    pub fn is_maximized(&self, window: &W) -> bool {
+        if let Some(floating) = self.floating.iter().find(|f| f.tile.window() == window) {
+            return floating.pre_maximize.is_some();
+        }
+
+        self.columns
+            .iter()
+            .flat_map(|col| &col.tiles)
+            .any(|tile| tile.window() == window && tile.pre_maximize_width().is_some())
+    }
+
+    /// Returns the focused window on this workspace, if any.
+    pub fn active_window(&self) -> Option<&W> {
+        if self.floating_is_active {
+            return self.floating.last().map(|f| f.tile.window());
+        }
+
+        if self.columns.is_empty() {
+            return None;
+        }
+
+        let col = &self.columns[self.active_column_idx];
+        Some(col.tiles[col.active_tile_idx].window())
+    }
+
     pub fn set_output(&mut self, output: Option<Output>) {
         if self.output == output {
             return;
@@ -294,12 +446,26 @@ impl<W: LayoutElement> Workspace<W> {
         for col in &mut self.columns {
             col.set_view_size(self.view_size, self.working_area);
         }
+
+        for floating in &mut self.floating {
+            if floating.pre_fullscreen.is_some() {
+                floating.tile.request_fullscreen(self.view_size);
+            }
+        }
+    }
+
+    pub fn output(&self) -> Option<&Output> {
+        self.output.as_ref()
     }
 
     pub fn view_size(&self) -> Size<i32, Logical> {
         self.view_size
     }
 
+    pub fn id(&self) -> WorkspaceId {
+        self.id
+    }
+
     pub fn update_output_scale_transform(&mut self) {
         let Some(output) = self.output.as_ref() else {
             return;
@@ -309,6 +475,19 @@ impl<W: LayoutElement> Workspace<W> {
         }
     }
 
+    /// Returns the size of a window maximized to the working area.
+    pub fn maximized_window_size(&self) -> Size<i32, Logical> {
+        let mut border = 0;
+        if !self.options.border.off {
+            border = self.options.border.width as i32 * 2;
+        }
+
+        Size::from((
+            max(self.working_area.size.w - border, 1),
+            max(self.working_area.size.h - border, 1),
+        ))
+    }
+
     fn toplevel_bounds(&self) -> Size<i32, Logical> {
         let mut border = 0;
         if !self.options.border.off {
@@ -321,14 +500,20 @@ impl<W: LayoutElement> Workspace<W> {
         ))
     }
 
+    /// Computes the size to configure a new window with.
+    ///
+    /// The size respects the min and max size of the window, so that it doesn't visibly snap to
+    /// its limits after the first commit.
     pub fn new_window_size(
         &self,
+        window: &impl LayoutElement,
         default_width: Option<Option<ColumnWidth>>,
+        default_height: Option<Option<WindowHeight>>,
     ) -> Size<i32, Logical> {
         let default_width = match default_width {
             Some(Some(width)) => Some(width),
             Some(None) => None,
-            None => self.options.default_width,
+            None => self.options.default_width_for_output(self.output.as_ref()),
         };
 
         let width = if let Some(width) = default_width {
@@ -341,20 +526,40 @@ impl<W: LayoutElement> Workspace<W> {
             0
         };
 
-        let mut height = self.working_area.size.h - self.options.gaps * 2;
-        if !self.options.border.off {
-            height -= self.options.border.width as i32 * 2;
-        }
+        let full_height = self.toplevel_bounds().h;
+        let height = match default_height {
+            // Don't make the window taller than the workspace can fit.
+            Some(Some(WindowHeight::Fixed(height))) => height.clamp(1, full_height),
+            Some(None) => 0,
+            None | Some(Some(WindowHeight::Auto)) => full_height,
+        };
+
+        // Zero means that the window picks on its own, and zero limits are unset.
+        let min_size = window.min_size();
+        let max_size = window.max_size();
+        let clamp = |size: i32, min_size: i32, max_size: i32| {
+            let mut size = size;
+            if size > 0 && max_size > 0 {
+                size = min(size, max_size);
+            }
+            if size > 0 && min_size > 0 {
+                size = max(size, min_size);
+            }
+            size
+        };
+        let width = clamp(width, min_size.w, max_size.w);
+        let height = clamp(height, min_size.h, max_size.h);
 
-        Size::from((width, max(height, 1)))
+        Size::from((width, height))
     }
 
     pub fn configure_new_window(
         &self,
         window: &Window,
         default_width: Option<Option<ColumnWidth>>,
+        default_height: Option<Option<WindowHeight>>,
     ) {
-        let size = self.new_window_size(default_width);
+        let size = self.new_window_size(window, default_width, default_height);
         let bounds = self.toplevel_bounds();
 
         if let Some(output) = self.output.as_ref() {
@@ -452,6 +657,9 @@ impl<W: LayoutElement> Workspace<W> {
     }
 
     fn activate_column(&mut self, idx: usize) {
+        // Activating a column takes the focus away from floating windows.
+        self.floating_is_active = false;
+
         if self.active_column_idx == idx {
             return;
         }
@@ -503,6 +711,11 @@ impl<W: LayoutElement> Workspace<W> {
         self.windows().next().is_some()
     }
 
+    /// Returns whether this workspace should be kept around even if it's not active.
+    pub fn has_windows_or_name(&self) -> bool {
+        self.has_windows() || self.name.is_some()
+    }
+
     pub fn has_window(&self, window: &W) -> bool {
         self.windows().any(|win| win == window)
     }
@@ -580,12 +793,152 @@ impl<W: LayoutElement> Workspace<W> {
         }
     }
 
+    pub fn add_floating_window(&mut self, window: W, activate: bool) {
+        self.enter_output_for_window(&window);
+
+        let tile = Tile::new(window, self.options.clone());
+
+        // Center dialogs over their parent, and other windows in the working area.
+        let area = self
+            .windows()
+            .find(|parent| tile.window().is_child_of(parent))
+            .and_then(|parent| self.tile_rect(parent))
+            .unwrap_or(self.working_area);
+        let size = tile.tile_size();
+        let pos = Point::from((
+            area.loc.x + (area.size.w - size.w) / 2,
+            area.loc.y + (area.size.h - size.h) / 2,
+        ));
+
+        self.floating.push(FloatingTile {
+            tile,
+            pos: self.clamp_floating_pos(pos, size),
+            pre_maximize: None,
+            pre_fullscreen: None,
+            resize_origin: None,
+        });
+
+        if activate {
+            self.floating_is_active = true;
+        }
+    }
+
+    /// Returns the geometry of the tile with this window, relative to the view.
+    fn tile_rect(&self, window: &W) -> Option<Rectangle<i32, Logical>> {
+        if let Some(floating) = self.floating.iter().find(|f| f.tile.window() == window) {
+            return Some(Rectangle::from_loc_and_size(
+                floating.pos,
+                floating.tile.tile_size(),
+            ));
+        }
+
+        let col_idx = self.columns.iter().position(|col| col.contains(window))?;
+        let col = &self.columns[col_idx];
+        let tile_idx = col.position(window).unwrap();
+        let loc = Point::from((
+            self.column_x(col_idx) - self.view_pos(),
+            col.tile_y(tile_idx),
+        ));
+        Some(Rectangle::from_loc_and_size(
+            loc,
+            col.tiles[tile_idx].tile_size(),
+        ))
+    }
+
+    /// Keeps a floating tile of this size in the working area.
+    ///
+    /// Prefers keeping the top-left corner visible when the tile is larger than the area.
+    fn clamp_floating_pos(
+        &self,
+        pos: Point<i32, Logical>,
+        size: Size<i32, Logical>,
+    ) -> Point<i32, Logical> {
+        let area = self.working_area;
+        let max_x = max(area.loc.x + area.size.w - size.w, area.loc.x);
+        let max_y = max(area.loc.y + area.size.h - size.h, area.loc.y);
+        Point::from((
+            pos.x.clamp(area.loc.x, max_x),
+            pos.y.clamp(area.loc.y, max_y),
+        ))
+    }
+
+    /// Moves a floating window to `pos` relative to the view, keeping it in the working area.
+    ///
+    /// Floating dialogs of the window move along with it.
+    pub fn set_floating_window_position(&mut self, window: &W, pos: Point<i32, Logical>) {
+        if let Some(idx) = self.floating.iter().position(|f| f.tile.window() == window) {
+            self.set_floating_tile_position(idx, pos);
+        }
+    }
+
+    fn set_floating_tile_position(&mut self, idx: usize, pos: Point<i32, Logical>) {
+        let floating = &self.floating[idx];
+        let old_pos = floating.pos;
+        let new_pos = self.clamp_floating_pos(pos, floating.tile.tile_size());
+        self.floating[idx].pos = new_pos;
+
+        let delta = new_pos - old_pos;
+        if delta == Point::from((0, 0)) {
+            return;
+        }
+
+        let parent = self.floating[idx].tile.window();
+        let children: Vec<usize> = (0..self.floating.len())
+            .filter(|&i| self.floating[i].tile.window().is_child_of(parent))
+            .collect();
+        for child_idx in children {
+            let pos = self.floating[child_idx].pos + delta;
+            self.set_floating_tile_position(child_idx, pos);
+        }
+    }
+
+    /// Raises a floating window to the top, together with its floating dialogs.
+    fn raise_floating(&mut self, idx: usize) {
+        // Find the dialogs of the window, and their dialogs in turn.
+        let mut raised = vec![false; self.floating.len()];
+        raised[idx] = true;
+        loop {
+            let mut changed = false;
+            for i in 0..self.floating.len() {
+                if raised[i] {
+                    continue;
+                }
+
+                let window = self.floating[i].tile.window();
+                let is_dialog = (0..self.floating.len())
+                    .any(|j| raised[j] && window.is_child_of(self.floating[j].tile.window()));
+                if is_dialog {
+                    raised[i] = true;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        // Keep the relative order otherwise, but put the window itself below its dialogs.
+        let mut floating = mem::take(&mut self.floating);
+        let window = floating.remove(idx);
+        raised.remove(idx);
+
+        let (top, rest): (Vec<_>, Vec<_>) = floating
+            .into_iter()
+            .zip(raised)
+            .partition(|(_, raised)| *raised);
+        self.floating = rest.into_iter().map(|(f, _)| f).collect();
+        self.floating.push(window);
+        self.floating.extend(top.into_iter().map(|(f, _)| f));
+    }
+
     pub fn add_window_right_of(
         &mut self,
         right_of: &W,
         window: W,
         width: ColumnWidth,
         is_full_width: bool,
+        activate: bool,
     ) {
         self.enter_output_for_window(&window);
 
@@ -607,7 +960,7 @@ impl<W: LayoutElement> Workspace<W> {
         self.columns.insert(idx, column);
 
         // Activate the new window if right_of was active.
-        if self.active_column_idx == right_of_idx {
+        if activate && self.active_column_idx == right_of_idx {
             self.activate_column(idx);
             self.activate_prev_column_on_removal = true;
         } else if idx <= self.active_column_idx {
@@ -615,6 +968,69 @@ impl<W: LayoutElement> Workspace<W> {
         }
     }
 
+    /// Returns whether [`Self::add_window_below()`] can stack `window` in the column of `below`.
+    pub fn can_add_window_below(&self, below: &W, window: &W) -> bool {
+        self.columns
+            .iter()
+            .find(|col| col.contains(below))
+            .map_or(false, |col| {
+                !col.is_fullscreen && col.can_add_window(window)
+            })
+    }
+
+    /// Adds a window to the column of another window, right below it.
+    pub fn add_window_below(&mut self, below: &W, window: W, activate: bool) {
+        assert!(self.can_add_window_below(below, &window));
+
+        self.enter_output_for_window(&window);
+
+        let col_idx = self
+            .columns
+            .iter()
+            .position(|col| col.contains(below))
+            .unwrap();
+        let column = &mut self.columns[col_idx];
+        let idx = column.position(below).unwrap() + 1;
+        column.insert_window(idx, window);
+
+        if activate {
+            column.active_tile_idx = idx;
+            self.activate_column(col_idx);
+        }
+    }
+
+    /// Returns whether [`Self::add_window_as_tab()`] has a column to add the window to.
+    pub fn can_add_window_as_tab(&self) -> bool {
+        self.columns
+            .get(self.active_column_idx)
+            .map_or(false, |col| !col.is_fullscreen)
+    }
+
+    /// Returns the window size that a new tab in the active column would get.
+    pub fn new_tab_size(&self) -> Option<Size<i32, Logical>> {
+        if !self.can_add_window_as_tab() {
+            return None;
+        }
+
+        Some(self.columns[self.active_column_idx].tab_window_size())
+    }
+
+    /// Adds a window as a new tab in the active column, switching it to tabbed display.
+    pub fn add_window_as_tab(&mut self, window: W, activate: bool) {
+        assert!(self.can_add_window_as_tab());
+
+        self.enter_output_for_window(&window);
+
+        let column = &mut self.columns[self.active_column_idx];
+        column.add_window(window);
+        column.set_display(ColumnDisplay::Tabbed);
+
+        if activate {
+            column.focus_last();
+            self.floating_is_active = false;
+        }
+    }
+
     pub fn add_column(&mut self, mut column: Column<W>, activate: bool) {
         for tile in &column.tiles {
             self.enter_output_for_window(tile.window());
@@ -733,6 +1149,20 @@ impl<W: LayoutElement> Workspace<W> {
     }
 
     pub fn remove_window(&mut self, window: &W) {
+        if let Some(idx) = self.floating.iter().position(|f| f.tile.window() == window) {
+            let window = self.floating.remove(idx).tile.into_window();
+
+            if let Some(output) = &self.output {
+                window.output_leave(output);
+            }
+
+            if self.floating.is_empty() {
+                self.floating_is_active = false;
+            }
+
+            return;
+        }
+
         let column_idx = self
             .columns
             .iter()
@@ -744,7 +1174,56 @@ impl<W: LayoutElement> Workspace<W> {
         self.remove_window_by_idx(column_idx, window_idx);
     }
 
+    /// Removes the focused window if it is floating.
+    pub fn remove_active_floating_window(&mut self) -> Option<W> {
+        if !self.floating_is_active {
+            return None;
+        }
+
+        let window = self.floating.pop().unwrap().tile.into_window();
+
+        if let Some(output) = &self.output {
+            window.output_leave(output);
+        }
+
+        if self.floating.is_empty() {
+            self.floating_is_active = false;
+        }
+
+        Some(window)
+    }
+
     pub fn update_window(&mut self, window: &W) {
+        if let Some(floating) = self.floating.iter_mut().find(|f| f.tile.window() == window) {
+            floating.tile.update_window();
+
+            // The window may have changed its min or max size. Columns re-clamp their tiles in
+            // update_tile_sizes() below, but nothing else resizes a floating window, so bring it
+            // within the new bounds here.
+            let size = floating.tile.tile_size();
+            let min_size = floating.tile.min_size();
+            let max_size = floating.tile.max_size();
+            let clamp = |size: i32, min_size: i32, max_size: i32| {
+                let mut size = size;
+                if max_size > 0 {
+                    size = min(size, max_size);
+                }
+                if min_size > 0 {
+                    size = max(size, min_size);
+                }
+                size
+            };
+            let clamped = Size::from((
+                clamp(size.w, min_size.w, max_size.w),
+                clamp(size.h, min_size.h, max_size.h),
+            ));
+            if clamped != size && !floating.tile.window().is_fullscreen() {
+                floating.tile.request_tile_size(clamped);
+            }
+
+            return;
+        }
+
         let (idx, column) = self
             .columns
             .iter_mut()
@@ -769,6 +1248,12 @@ impl<W: LayoutElement> Workspace<W> {
     }
 
     pub fn activate_window(&mut self, window: &W) {
+        if let Some(idx) = self.floating.iter().position(|f| f.tile.window() == window) {
+            self.raise_floating(idx);
+            self.floating_is_active = true;
+            return;
+        }
+
         let column_idx = self
             .columns
             .iter()
@@ -785,6 +1270,10 @@ impl<W: LayoutElement> Workspace<W> {
         assert!(self.view_size.w > 0);
         assert!(self.view_size.h > 0);
 
+        if self.floating_is_active {
+            assert!(!self.floating.is_empty());
+        }
+
         if !self.columns.is_empty() {
             assert!(self.active_column_idx < self.columns.len());
 
@@ -834,6 +1323,30 @@ impl<W: LayoutElement> Workspace<W> {
         self.columns[self.active_column_idx].focus_up();
     }
 
+    pub fn focus_tab_next(&mut self) {
+        if self.columns.is_empty() {
+            return;
+        }
+
+        self.columns[self.active_column_idx].focus_tab_next();
+    }
+
+    pub fn focus_tab_previous(&mut self) {
+        if self.columns.is_empty() {
+            return;
+        }
+
+        self.columns[self.active_column_idx].focus_tab_previous();
+    }
+
+    pub fn toggle_column_tabbed_display(&mut self) {
+        if self.columns.is_empty() {
+            return;
+        }
+
+        self.columns[self.active_column_idx].toggle_tabbed_display();
+    }
+
     fn move_column_to(&mut self, new_idx: usize) {
         if self.active_column_idx == new_idx {
             return;
@@ -907,6 +1420,11 @@ impl<W: LayoutElement> Workspace<W> {
 
             // Move into adjacent column.
             let target_column_idx = self.active_column_idx - 1;
+            let target_column = &self.columns[target_column_idx];
+            if !target_column.can_add_window(source_column.tiles[0].window()) {
+                return;
+            }
+
             let window = self.remove_window_by_idx(self.active_column_idx, 0);
             self.enter_output_for_window(&window);
 
@@ -940,6 +1458,11 @@ impl<W: LayoutElement> Workspace<W> {
 
             // Move into adjacent column.
             let target_column_idx = self.active_column_idx;
+            let target_column = &self.columns[target_column_idx + 1];
+            if !target_column.can_add_window(source_column.tiles[0].window()) {
+                return;
+            }
+
             let window = self.remove_window_by_idx(self.active_column_idx, 0);
             self.enter_output_for_window(&window);
 
@@ -968,6 +1491,11 @@ impl<W: LayoutElement> Workspace<W> {
         }
 
         let source_column_idx = self.active_column_idx + 1;
+        let source_window = self.columns[source_column_idx].tiles[0].window();
+        if !self.columns[self.active_column_idx].can_add_window(source_window) {
+            return;
+        }
+
         let window = self.remove_window_by_idx(source_column_idx, 0);
         self.enter_output_for_window(&window);
 
@@ -1002,6 +1530,18 @@ impl<W: LayoutElement> Workspace<W> {
         self.column_x(self.active_column_idx) + self.view_offset
     }
 
+    /// Returns the X of the window relative to the view.
+    pub fn window_x(&self, window: &W) -> Option<i32> {
+        if let Some(floating) = self.floating.iter().find(|f| f.tile.window() == window) {
+            return Some(floating.pos.x + floating.tile.window_loc().x);
+        }
+
+        let col_idx = self.columns.iter().position(|col| col.contains(window))?;
+        let col = &self.columns[col_idx];
+        let tile = &col.tiles[col.position(window).unwrap()];
+        Some(self.column_x(col_idx) - self.view_pos() + tile.window_loc().x)
+    }
+
     fn tiles_in_render_order(&self) -> impl Iterator<Item = (&'_ Tile<W>, Point<i32, Logical>)> {
         let view_pos = self.visual_column_x(self.active_column_idx) + self.view_offset;
 
@@ -1033,6 +1573,10 @@ impl<W: LayoutElement> Workspace<W> {
                             return None;
                         }
 
+                        if !col.is_tile_visible(tile_idx) {
+                            return None;
+                        }
+
                         let tile_pos = Point::from((x, y));
                         Some((tile, tile_pos))
                     },
@@ -1041,15 +1585,31 @@ impl<W: LayoutElement> Workspace<W> {
         first.chain(rest)
     }
 
+    /// Returns the floating windows with their indices, topmost first.
+    ///
+    /// Always-on-top windows stay above the rest regardless of which floating window was raised
+    /// last.
+    fn floating_in_render_order(&self) -> impl Iterator<Item = (usize, &FloatingTile<W>)> {
+        let on_top = |f: &FloatingTile<W>| f.tile.window().is_always_on_top();
+        let above = self.floating.iter().enumerate().rev();
+        let below = above.clone();
+        above
+            .filter(move |&(_, f)| on_top(f))
+            .chain(below.filter(move |&(_, f)| !on_top(f)))
+    }
+
     pub fn window_under(
         &self,
         pos: Point<f64, Logical>,
     ) -> Option<(&W, Option<Point<i32, Logical>>)> {
-        if self.columns.is_empty() {
-            return None;
-        }
+        let floating = self
+            .floating_in_render_order()
+            .map(|(_, f)| (&f.tile, f.pos));
 
-        self.tiles_in_render_order().find_map(|(tile, tile_pos)| {
+        let tiles = (!self.columns.is_empty()).then(|| self.tiles_in_render_order());
+        let tiles = tiles.into_iter().flatten();
+
+        floating.chain(tiles).find_map(|(tile, tile_pos)| {
             let pos_within_tile = pos - tile_pos.to_f64();
 
             if tile.is_in_input_region(pos_within_tile) {
@@ -1092,23 +1652,114 @@ impl<W: LayoutElement> Workspace<W> {
             return;
         }
 
-        self.columns[self.active_column_idx].set_window_height(change);
+        self.columns[self.active_column_idx].set_window_height(change, None);
     }
 
-    pub fn set_fullscreen(&mut self, window: &W, is_fullscreen: bool) {
-        let (mut col_idx, tile_idx) = self
+    pub fn interactive_resize_begin(&mut self, window: &W, edges: ResizeEdge) -> bool {
+        if let Some(floating) = self.floating.iter_mut().find(|f| f.tile.window() == window) {
+            // Fullscreen windows fill the whole output, there's nothing to resize.
+            if floating.pre_fullscreen.is_some() {
+                return false;
+            }
+
+            let resize = InteractiveResize {
+                edges,
+                original_window_size: floating.tile.window_size(),
+                pending_delta: None,
+                snap: false,
+            };
+            floating.tile.set_interactive_resize(Some(resize));
+            floating.resize_origin = Some(floating.pos);
+            return true;
+        }
+
+        let col = self
             .columns
-            .iter()
-            .enumerate()
-            .find_map(|(col_idx, col)| col.position(window).map(|tile_idx| (col_idx, tile_idx)))
+            .iter_mut()
+            .find(|col| col.contains(window))
             .unwrap();
+        col.interactive_resize_begin(window, edges)
+    }
 
-        let mut col = &mut self.columns[col_idx];
+    pub fn interactive_resize_update(
+        &mut self,
+        window: &W,
+        delta: Point<f64, Logical>,
+        snap: bool,
+    ) -> bool {
+        if let Some(floating) = self.floating.iter_mut().find(|f| f.tile.window() == window) {
+            let Some(resize) = floating.tile.interactive_resize_mut() else {
+                return false;
+            };
 
-        if is_fullscreen && col.tiles.len() > 1 {
-            // This wasn't the only window in its column; extract it into a separate column.
-            let target_window_was_focused =
-                self.active_column_idx == col_idx && col.active_tile_idx == tile_idx;
+            resize.pending_delta = Some(delta);
+            resize.snap = snap;
+            return true;
+        }
+
+        let col = self
+            .columns
+            .iter_mut()
+            .find(|col| col.contains(window))
+            .unwrap();
+        col.interactive_resize_update(window, delta, snap)
+    }
+
+    pub fn interactive_resize_end(&mut self, window: &W) {
+        if let Some(floating) = self.floating.iter_mut().find(|f| f.tile.window() == window) {
+            floating.tile.set_interactive_resize(None);
+            floating.resize_origin = None;
+            return;
+        }
+
+        let col = self
+            .columns
+            .iter_mut()
+            .find(|col| col.contains(window))
+            .unwrap();
+        col.interactive_resize_end(window);
+    }
+
+    pub fn apply_pending_interactive_resizes(&mut self) {
+        for col in &mut self.columns {
+            col.apply_pending_interactive_resizes();
+        }
+
+        for floating in &mut self.floating {
+            floating.apply_pending_interactive_resize();
+        }
+    }
+
+    pub fn set_fullscreen(&mut self, window: &W, is_fullscreen: bool) {
+        // Floating windows go fullscreen in place and come back to where they were.
+        if let Some(floating) = self.floating.iter_mut().find(|f| f.tile.window() == window) {
+            if is_fullscreen {
+                if floating.pre_fullscreen.is_none() {
+                    let geo = Rectangle::from_loc_and_size(floating.pos, floating.tile.tile_size());
+                    floating.pre_fullscreen = Some(geo);
+                }
+                floating.pos = Point::from((0, 0));
+                floating.tile.request_fullscreen(self.view_size);
+            } else if let Some(geo) = floating.pre_fullscreen.take() {
+                floating.pos = geo.loc;
+                floating.tile.request_tile_size(geo.size);
+            }
+            return;
+        }
+
+        let (mut col_idx, tile_idx) = self
+            .columns
+            .iter()
+            .enumerate()
+            .find_map(|(col_idx, col)| col.position(window).map(|tile_idx| (col_idx, tile_idx)))
+            .unwrap();
+
+        let mut col = &mut self.columns[col_idx];
+
+        if is_fullscreen && col.tiles.len() > 1 {
+            // This wasn't the only window in its column; extract it into a separate column.
+            let target_window_was_focused =
+                self.active_column_idx == col_idx && col.active_tile_idx == tile_idx;
             let window = col.tiles.remove(tile_idx).into_window();
             col.heights.remove(tile_idx);
             col.active_tile_idx = min(col.active_tile_idx, col.tiles.len() - 1);
@@ -1137,7 +1788,180 @@ impl<W: LayoutElement> Workspace<W> {
         col.set_fullscreen(is_fullscreen);
     }
 
+    pub fn set_maximized(&mut self, window: &W, is_maximized: bool) {
+        if self.is_floating(window) {
+            return;
+        }
+
+        let col = self
+            .columns
+            .iter_mut()
+            .find(|col| col.contains(window))
+            .unwrap();
+        let tile_idx = col.position(window).unwrap();
+        col.set_maximized(tile_idx, is_maximized);
+    }
+
+    /// Maximizes a window to float above the columns and cover the working area, or undoes that.
+    pub fn set_maximized_to_working_area(&mut self, window: &W, is_maximized: bool) {
+        let area = self.working_area;
+
+        if !is_maximized {
+            let Some(idx) = self
+                .floating
+                .iter()
+                .position(|f| f.tile.window() == window && f.pre_maximize.is_some())
+            else {
+                return;
+            };
+
+            match self.floating[idx].pre_maximize.take().unwrap() {
+                PreMaximize::Floating(geo) => {
+                    let floating = &mut self.floating[idx];
+                    floating.pos = geo.loc;
+                    floating.tile.request_tile_size(geo.size);
+                }
+                PreMaximize::Tiled(width) => {
+                    let window = self.floating.remove(idx).tile.into_window();
+                    if self.floating.is_empty() {
+                        self.floating_is_active = false;
+                    }
+                    self.add_window(window, true, width, false);
+                }
+            }
+
+            return;
+        }
+
+        if let Some(floating) = self.floating.iter_mut().find(|f| f.tile.window() == window) {
+            // A fullscreen window stays fullscreen.
+            if floating.pre_maximize.is_none() && floating.pre_fullscreen.is_none() {
+                let geo = Rectangle::from_loc_and_size(floating.pos, floating.tile.tile_size());
+                floating.pre_maximize = Some(PreMaximize::Floating(geo));
+                floating.pos = area.loc;
+                floating.tile.request_tile_size(area.size);
+            }
+            return;
+        }
+
+        let col_idx = self
+            .columns
+            .iter()
+            .position(|col| col.contains(window))
+            .unwrap();
+        let col = &self.columns[col_idx];
+        let tile_idx = col.position(window).unwrap();
+
+        // A fullscreen window stays fullscreen, and will be maximized when it leaves fullscreen.
+        if col.is_fullscreen {
+            return;
+        }
+
+        // Come back to the width from before any column maximize.
+        let width = if col.is_full_width {
+            ColumnWidth::Proportion(1.)
+        } else {
+            col.width
+        };
+        let width = col.tiles[tile_idx].pre_maximize_width().unwrap_or(width);
+
+        let window = self.remove_window_by_idx(col_idx, tile_idx);
+        self.add_floating_window(window, true);
+
+        let floating = self.floating.last_mut().unwrap();
+        floating.pre_maximize = Some(PreMaximize::Tiled(width));
+        floating.pos = area.loc;
+        floating.tile.request_tile_size(area.size);
+    }
+
+    /// Sets the height of a newly added window according to its default height.
+    ///
+    /// The three states match those of `new_window_size()`: `None` keeps the automatic height,
+    /// `Some(None)` keeps the height that the window picked for itself.
+    pub fn set_new_window_height(
+        &mut self,
+        window: &W,
+        default_height: Option<Option<WindowHeight>>,
+    ) {
+        if self.is_floating(window) {
+            return;
+        }
+
+        let full_height = self.toplevel_bounds().h;
+
+        let col = self
+            .columns
+            .iter_mut()
+            .find(|col| col.contains(window))
+            .unwrap();
+        if col.is_fullscreen {
+            return;
+        }
+
+        let tile_idx = col.position(window).unwrap();
+        let height = match default_height {
+            Some(Some(WindowHeight::Fixed(height))) => height,
+            Some(None) => col.tiles[tile_idx].window().size().h,
+            None | Some(Some(WindowHeight::Auto)) => return,
+        };
+
+        // Don't make the window taller than the workspace can fit.
+        let height = height.clamp(1, full_height);
+        col.set_window_height(SizeChange::SetFixed(height), Some(tile_idx));
+    }
+
+    /// Moves the column of a newly added window to `position` in the workspace.
+    pub fn set_new_window_column_position(&mut self, window: &W, position: ColumnPosition) {
+        if self.is_floating(window) {
+            return;
+        }
+
+        let idx = self
+            .columns
+            .iter()
+            .position(|col| col.contains(window))
+            .unwrap();
+
+        // Windows that joined an existing column, like tabs, have no column of their own to move.
+        if self.columns[idx].tiles.len() > 1 {
+            return;
+        }
+
+        let new_idx = match position {
+            ColumnPosition::AfterFocused => return,
+            // New columns go right after the focused one, which is the active column unless the
+            // new window took focus.
+            ColumnPosition::BeforeFocused if idx == self.active_column_idx => idx.saturating_sub(1),
+            ColumnPosition::BeforeFocused => self.active_column_idx,
+            ColumnPosition::Start => 0,
+            ColumnPosition::End => self.columns.len() - 1,
+        };
+        if idx == new_idx {
+            return;
+        }
+
+        if idx == self.active_column_idx {
+            self.move_column_to(new_idx);
+            return;
+        }
+
+        // Keep the focus on the same column as before.
+        let column = self.columns.remove(idx);
+        self.columns.insert(new_idx, column);
+        if idx < self.active_column_idx && self.active_column_idx <= new_idx {
+            self.active_column_idx -= 1;
+        } else if new_idx <= self.active_column_idx && self.active_column_idx < idx {
+            self.active_column_idx += 1;
+        }
+    }
+
     pub fn toggle_fullscreen(&mut self, window: &W) {
+        if let Some(floating) = self.floating.iter().find(|f| f.tile.window() == window) {
+            let value = floating.pre_fullscreen.is_none();
+            self.set_fullscreen(window, value);
+            return;
+        }
+
         let col = self
             .columns
             .iter_mut()
@@ -1163,8 +1987,9 @@ impl<W: LayoutElement> Workspace<W> {
     pub fn render_elements<R: NiriRenderer>(
         &self,
         renderer: &mut R,
+        target: RenderTarget,
     ) -> Vec<WorkspaceRenderElement<R>> {
-        if self.columns.is_empty() {
+        if !self.has_windows() {
             return vec![];
         }
 
@@ -1177,7 +2002,24 @@ impl<W: LayoutElement> Workspace<W> {
             .unwrap_or(Scale::from(1.));
 
         let mut rv = vec![];
-        let mut first = true;
+
+        // Floating windows go on top, with the topmost one first.
+        for (idx, floating) in self.floating_in_render_order() {
+            let focus_ring = self.floating_is_active && idx + 1 == self.floating.len();
+
+            rv.extend(
+                floating
+                    .tile
+                    .render(renderer, floating.pos, output_scale, focus_ring, target)
+                    .map(Into::into),
+            );
+        }
+
+        if self.columns.is_empty() {
+            return rv;
+        }
+
+        let mut first = !self.floating_is_active;
 
         for (tile, tile_pos) in self.tiles_in_render_order() {
             // For the active tile (which comes first), draw the focus ring.
@@ -1185,35 +2027,119 @@ impl<W: LayoutElement> Workspace<W> {
             first = false;
 
             rv.extend(
-                tile.render(renderer, tile_pos, output_scale, focus_ring)
+                tile.render(renderer, tile_pos, output_scale, focus_ring, target)
                     .map(Into::into),
             );
         }
 
+        let view_pos = self.visual_column_x(self.active_column_idx) + self.view_offset;
+        let mut x = -view_pos;
+        for col in &self.columns {
+            if let Some(y) = col.tab_indicator_y() {
+                rv.extend(
+                    col.tab_indicator
+                        .render(Point::from((x, y)), output_scale)
+                        .map(Into::into),
+                );
+            }
+
+            x += col.visual_width() + self.options.gaps;
+        }
+
         rv
     }
 }
 
 impl Workspace<Window> {
-    pub fn refresh(&self, is_active: bool) {
+    /// Sends pending state to the windows.
+    ///
+    /// Windows on a workspace that isn't visible are suspended, so that they can stop rendering.
+    pub fn refresh(&self, is_active: bool, is_visible: bool) {
         let bounds = self.toplevel_bounds();
 
         for (col_idx, col) in self.columns.iter().enumerate() {
             for (tile_idx, tile) in col.tiles.iter().enumerate() {
                 let win = tile.window();
                 let active = is_active
+                    && !self.floating_is_active
                     && self.active_column_idx == col_idx
                     && col.active_tile_idx == tile_idx;
                 win.set_activated(active);
 
+                let is_maximized = tile.pre_maximize_width().is_some();
                 win.toplevel().with_pending_state(|state| {
                     state.bounds = Some(bounds);
+                    set_suspended(state, !is_visible);
+                    set_maximized(state, is_maximized);
                 });
 
                 win.toplevel().send_pending_configure();
                 win.refresh();
             }
         }
+
+        for (idx, floating) in self.floating.iter().enumerate() {
+            let win = floating.tile.window();
+            let active = is_active && self.floating_is_active && idx + 1 == self.floating.len();
+            win.set_activated(active);
+
+            let is_maximized = floating.pre_maximize.is_some();
+            win.toplevel().with_pending_state(|state| {
+                state.bounds = Some(bounds);
+                set_suspended(state, !is_visible);
+                set_maximized(state, is_maximized);
+            });
+
+            win.toplevel().send_pending_configure();
+            win.refresh();
+        }
+    }
+}
+
+impl<W: LayoutElement> FloatingTile<W> {
+    fn apply_pending_interactive_resize(&mut self) {
+        let Some(resize) = self.tile.interactive_resize_mut() else {
+            return;
+        };
+        let Some(delta) = resize.pending_delta.take() else {
+            return;
+        };
+
+        let original = resize.original_window_size;
+        let (sign_x, sign_y) = resize.edge_signs();
+
+        // Zero means no limit.
+        let resize_axis = |original: i32, sign: i32, delta: f64, min_size: i32, max_size: i32| {
+            let mut size = (f64::from(original) + f64::from(sign) * delta).round() as i32;
+            if max_size > 0 {
+                size = min(size, max_size);
+            }
+            max(size, max(min_size, 1))
+        };
+        let min_size = self.tile.window().min_size();
+        let max_size = self.tile.window().max_size();
+        let size = Size::from((
+            resize_axis(original.w, sign_x, delta.x, min_size.w, max_size.w),
+            resize_axis(original.h, sign_y, delta.y, min_size.h, max_size.h),
+        ));
+
+        // Dragging the left or top edge keeps the opposite edge in place.
+        if let Some(origin) = self.resize_origin {
+            let mut pos = origin;
+            if sign_x < 0 {
+                pos.x += original.w - size.w;
+            }
+            if sign_y < 0 {
+                pos.y += original.h - size.h;
+            }
+            self.pos = pos;
+        }
+
+        let tile_size = Size::from((
+            self.tile.tile_width_for_window_width(size.w),
+            self.tile.tile_height_for_window_height(size.h),
+        ));
+        self.tile.request_tile_size(tile_size);
     }
 }
 
@@ -1233,6 +2159,8 @@ impl<W: LayoutElement> Column<W> {
             width,
             is_full_width,
             is_fullscreen: false,
+            display: ColumnDisplay::Normal,
+            tab_indicator: TabIndicator::default(),
             view_size,
             working_area,
             options,
@@ -1302,6 +2230,18 @@ impl<W: LayoutElement> Column<W> {
             let is_active = is_active && tile_idx == self.active_tile_idx;
             tile.advance_animations(current_time, is_active);
         }
+
+        if self.display == ColumnDisplay::Tabbed {
+            let width = self.visual_width();
+            let focus_ring = &self.options.focus_ring;
+            self.tab_indicator.update(
+                width,
+                self.tiles.len(),
+                self.active_tile_idx,
+                focus_ring.active_color,
+                focus_ring.inactive_color,
+            );
+        }
     }
 
     pub fn are_animations_ongoing(&self) -> bool {
@@ -1324,11 +2264,40 @@ impl<W: LayoutElement> Column<W> {
         self.active_tile_idx = idx;
     }
 
+    /// Returns whether `window` can stack in this column without going over the window limits.
+    ///
+    /// Tabbed columns show one window at a time, so they are never full.
+    fn can_add_window(&self, window: &W) -> bool {
+        if self.display == ColumnDisplay::Tabbed {
+            return true;
+        }
+
+        let windows = self
+            .tiles
+            .iter()
+            .map(Tile::window)
+            .chain(iter::once(window));
+        let max = windows
+            .filter_map(|win| win.max_windows_per_column())
+            .chain(self.options.max_windows_per_column)
+            .min();
+        max.map_or(true, |max| self.tiles.len() < max)
+    }
+
     fn add_window(&mut self, window: W) {
+        self.insert_window(self.tiles.len(), window);
+    }
+
+    fn insert_window(&mut self, idx: usize, window: W) {
         let tile = Tile::new(window, self.options.clone());
         self.is_fullscreen = false;
-        self.tiles.push(tile);
-        self.heights.push(WindowHeight::Auto);
+        self.tiles.insert(idx, tile);
+        self.heights.insert(idx, WindowHeight::Auto);
+
+        if idx <= self.active_tile_idx && self.tiles.len() > 1 {
+            self.active_tile_idx += 1;
+        }
+
         self.update_tile_sizes();
     }
 
@@ -1342,6 +2311,14 @@ impl<W: LayoutElement> Column<W> {
     }
 
     fn update_tile_sizes(&mut self) {
+        // Maximized windows take up the full width, so a column that is no longer full-width
+        // doesn't have any maximized windows left.
+        if !self.is_full_width {
+            for tile in &mut self.tiles {
+                tile.set_pre_maximize_width(None);
+            }
+        }
+
         if self.is_fullscreen {
             self.tiles[0].request_fullscreen(self.view_size);
             return;
@@ -1386,10 +2363,29 @@ impl<W: LayoutElement> Column<W> {
         let width = width.resolve(&self.options, self.working_area.size.w);
         let width = max(min(width, max_width), min_width);
 
+        if self.display == ColumnDisplay::Tabbed {
+            // Every tab takes up the full column height below the tab indicator.
+            let height = self.tab_tile_height();
+
+            for (tile, min_size) in zip(&mut self.tiles, &min_size) {
+                let height = max(max(height, min_size.h), 1);
+                tile.request_tile_size(Size::from((width, height)));
+            }
+            return;
+        }
+
         // Compute the tile heights. Start by converting window heights to tile heights.
         let mut heights = zip(&self.tiles, &self.heights)
             .map(|(tile, height)| match *height {
-                WindowHeight::Auto => WindowHeight::Auto,
+                WindowHeight::Auto => match tile.window().aspect_ratio() {
+                    // Windows keeping an aspect ratio get the height matching the column width.
+                    Some(ratio) => {
+                        let window_width = tile.window_width_for_tile_width(width);
+                        let window_height = (f64::from(window_width) / ratio).round() as i32;
+                        WindowHeight::Fixed(tile.tile_height_for_window_height(window_height))
+                    }
+                    None => WindowHeight::Auto,
+                },
                 WindowHeight::Fixed(height) => {
                     WindowHeight::Fixed(tile.tile_height_for_window_height(height))
                 }
@@ -1526,6 +2522,61 @@ impl<W: LayoutElement> Column<W> {
         self.active_tile_idx = self.tiles.len() - 1;
     }
 
+    fn focus_tab_next(&mut self) {
+        self.active_tile_idx = (self.active_tile_idx + 1) % self.tiles.len();
+    }
+
+    fn focus_tab_previous(&mut self) {
+        self.active_tile_idx = self
+            .active_tile_idx
+            .checked_sub(1)
+            .unwrap_or(self.tiles.len() - 1);
+    }
+
+    fn set_display(&mut self, display: ColumnDisplay) {
+        if self.display == display {
+            return;
+        }
+
+        self.display = display;
+        self.update_tile_sizes();
+    }
+
+    fn toggle_tabbed_display(&mut self) {
+        let display = match self.display {
+            ColumnDisplay::Normal => ColumnDisplay::Tabbed,
+            ColumnDisplay::Tabbed => ColumnDisplay::Normal,
+        };
+        self.set_display(display);
+    }
+
+    /// Returns the tile height of a tab, which is the full column height below the tab indicator.
+    fn tab_tile_height(&self) -> i32 {
+        self.working_area.size.h - self.options.gaps * 2 - TAB_INDICATOR_HEIGHT - TAB_INDICATOR_GAP
+    }
+
+    /// Returns the window size of a tab in this column, when in tabbed display.
+    fn tab_window_size(&self) -> Size<i32, Logical> {
+        let tile = &self.tiles[self.active_tile_idx];
+        let height = tile.window_height_for_tile_height(self.tab_tile_height());
+        let height = max(height, 1);
+        Size::from((tile.window_size().w, height))
+    }
+
+    /// Returns whether the tile is shown, which is not the case for inactive tabs.
+    fn is_tile_visible(&self, tile_idx: usize) -> bool {
+        self.display == ColumnDisplay::Normal || tile_idx == self.active_tile_idx
+    }
+
+    /// Returns the Y of the tab indicator, if it is shown.
+    fn tab_indicator_y(&self) -> Option<i32> {
+        if self.display != ColumnDisplay::Tabbed || self.is_fullscreen {
+            return None;
+        }
+
+        Some(self.working_area.loc.y + self.options.gaps)
+    }
+
     fn move_up(&mut self) {
         let new_idx = self.active_tile_idx.saturating_sub(1);
         if self.active_tile_idx == new_idx {
@@ -1641,9 +2692,11 @@ impl<W: LayoutElement> Column<W> {
         self.set_width(width);
     }
 
-    fn set_window_height(&mut self, change: SizeChange) {
-        let current = self.heights[self.active_tile_idx];
-        let tile = &self.tiles[self.active_tile_idx];
+    fn set_window_height(&mut self, change: SizeChange, tile_idx: Option<usize>) {
+        let tile_idx = tile_idx.unwrap_or(self.active_tile_idx);
+
+        let current = self.heights[tile_idx];
+        let tile = &self.tiles[tile_idx];
         let current_window_px = match current {
             WindowHeight::Auto => tile.window_size().h,
             WindowHeight::Fixed(height) => height,
@@ -1676,7 +2729,7 @@ impl<W: LayoutElement> Column<W> {
         };
 
         // Clamp it against the window height constraints.
-        let win = &self.tiles[self.active_tile_idx].window();
+        let win = &self.tiles[tile_idx].window();
         let min_h = win.min_size().h;
         let max_h = win.max_size().h;
 
@@ -1687,16 +2740,152 @@ impl<W: LayoutElement> Column<W> {
             window_height = window_height.max(min_h);
         }
 
-        self.heights[self.active_tile_idx] = WindowHeight::Fixed(window_height.clamp(1, MAX_PX));
+        self.heights[tile_idx] = WindowHeight::Fixed(window_height.clamp(1, MAX_PX));
         self.update_tile_sizes();
     }
 
+    fn interactive_resize_begin(&mut self, window: &W, edges: ResizeEdge) -> bool {
+        // Fullscreen windows fill the whole output, there's nothing to resize.
+        if self.is_fullscreen {
+            return false;
+        }
+
+        let tile = self
+            .tiles
+            .iter_mut()
+            .find(|tile| tile.window() == window)
+            .unwrap();
+        let resize = InteractiveResize {
+            edges,
+            original_window_size: tile.window_size(),
+            pending_delta: None,
+            snap: false,
+        };
+        tile.set_interactive_resize(Some(resize));
+        true
+    }
+
+    fn interactive_resize_update(
+        &mut self,
+        window: &W,
+        delta: Point<f64, Logical>,
+        snap: bool,
+    ) -> bool {
+        let tile = self
+            .tiles
+            .iter_mut()
+            .find(|tile| tile.window() == window)
+            .unwrap();
+        let Some(resize) = tile.interactive_resize_mut() else {
+            return false;
+        };
+
+        resize.pending_delta = Some(delta);
+        resize.snap = snap;
+        true
+    }
+
+    fn interactive_resize_end(&mut self, window: &W) {
+        let tile = self
+            .tiles
+            .iter_mut()
+            .find(|tile| tile.window() == window)
+            .unwrap();
+        tile.set_interactive_resize(None);
+    }
+
+    fn apply_pending_interactive_resizes(&mut self) {
+        if self.is_fullscreen {
+            return;
+        }
+
+        for tile_idx in 0..self.tiles.len() {
+            let Some(resize) = self.tiles[tile_idx].interactive_resize_mut() else {
+                continue;
+            };
+            let Some(delta) = resize.pending_delta.take() else {
+                continue;
+            };
+
+            let original = resize.original_window_size;
+            let (sign_x, sign_y) = resize.edge_signs();
+            let snap = resize.snap && self.options.snap_to_preset_widths;
+
+            // Horizontal edges resize the whole column.
+            if sign_x != 0 {
+                let width = f64::from(original.w) + f64::from(sign_x) * delta.x;
+                let width = width.round() as i32;
+
+                let preset = if snap {
+                    self.preset_width_near(tile_idx, width)
+                } else {
+                    None
+                };
+
+                match preset {
+                    Some(idx) => self.set_width(ColumnWidth::Preset(idx)),
+                    None => self.set_column_width(SizeChange::SetFixed(width)),
+                }
+            }
+
+            // Vertical edges resize the window within the column.
+            if sign_y != 0 {
+                let height = f64::from(original.h) + f64::from(sign_y) * delta.y;
+                self.set_window_height(SizeChange::SetFixed(height.round() as i32), Some(tile_idx));
+            }
+        }
+    }
+
+    /// Returns the index of the preset width close enough to snap a window of this width to.
+    fn preset_width_near(&self, tile_idx: usize, window_width: i32) -> Option<usize> {
+        // Distance in logical pixels within which the width snaps to a preset.
+        const SNAP_DISTANCE: i32 = 16;
+
+        let tile_width = self.tiles[tile_idx].tile_width_for_window_width(window_width);
+        let view_width = self.working_area.size.w;
+
+        self.options
+            .preset_widths
+            .iter()
+            .enumerate()
+            .map(|(idx, preset)| {
+                let distance = (preset.resolve(&self.options, view_width) - tile_width).abs();
+                (idx, distance)
+            })
+            .filter(|(_, distance)| *distance <= SNAP_DISTANCE)
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(idx, _)| idx)
+    }
+
     fn set_fullscreen(&mut self, is_fullscreen: bool) {
         assert_eq!(self.tiles.len(), 1);
         self.is_fullscreen = is_fullscreen;
         self.update_tile_sizes();
     }
 
+    fn set_maximized(&mut self, tile_idx: usize, is_maximized: bool) {
+        let tile = &mut self.tiles[tile_idx];
+
+        if is_maximized {
+            // Remember the width only on the first maximize, so that repeated requests don't
+            // overwrite it with the full width.
+            if tile.pre_maximize_width().is_none() {
+                let width = if self.is_full_width {
+                    ColumnWidth::Proportion(1.)
+                } else {
+                    self.width
+                };
+                tile.set_pre_maximize_width(Some(width));
+            }
+
+            self.is_full_width = true;
+            self.update_tile_sizes();
+        } else if let Some(width) = tile.pre_maximize_width() {
+            tile.set_pre_maximize_width(None);
+            self.set_width(width);
+        }
+    }
+
     pub fn window_y(&self, tile_idx: usize) -> i32 {
         let (tile, tile_y) = zip(&self.tiles, self.tile_ys()).nth(tile_idx).unwrap();
         tile_y + tile.window_loc().y
@@ -1713,9 +2902,17 @@ impl<W: LayoutElement> Column<W> {
             y = self.working_area.loc.y + self.options.gaps;
         }
 
+        // Tabs are all at the same position below the tab indicator.
+        let is_tabbed = self.display == ColumnDisplay::Tabbed;
+        if is_tabbed && !self.is_fullscreen {
+            y += TAB_INDICATOR_HEIGHT + TAB_INDICATOR_GAP;
+        }
+
         self.tiles.iter().map(move |tile| {
             let pos = y;
-            y += tile.tile_size().h + self.options.gaps;
+            if !is_tabbed {
+                y += tile.tile_size().h + self.options.gaps;
+            }
             pos
         })
     }
@@ -1755,9 +2952,25 @@ fn compute_new_view_offset(
     }
 }
 
+fn set_suspended(state: &mut ToplevelState, suspended: bool) {
+    if suspended {
+        state.states.set(xdg_toplevel::State::Suspended);
+    } else {
+        state.states.unset(xdg_toplevel::State::Suspended);
+    }
+}
+
+fn set_maximized(state: &mut ToplevelState, maximized: bool) {
+    if maximized {
+        state.states.set(xdg_toplevel::State::Maximized);
+    } else {
+        state.states.unset(xdg_toplevel::State::Maximized);
+    }
+}
+
 fn set_preferred_scale_transform(window: &impl LayoutElement, output: &Output) {
     // FIXME: cache this on the workspace.
-    let scale = output.current_scale().integer_scale();
+    let scale = output.current_scale();
     let transform = output.current_transform();
     window.set_preferred_scale_transform(scale, transform);
 }
@@ -11,7 +11,7 @@ use std::{env, mem, thread};
 use _server_decoration::server::org_kde_kwin_server_decoration_manager::Mode as KdeDecorationsMode;
 use anyhow::Context;
 use calloop::futures::Scheduler;
-use niri_config::{Config, TrackLayout};
+use niri_config::{BlockOutMode, Color, Config, TrackLayout};
 use smithay::backend::allocator::Fourcc;
 use smithay::backend::renderer::element::memory::MemoryRenderBufferRenderElement;
 use smithay::backend::renderer::element::solid::{SolidColorBuffer, SolidColorRenderElement};
@@ -20,8 +20,7 @@ use smithay::backend::renderer::element::surface::{
 };
 use smithay::backend::renderer::element::utils::{select_dmabuf_feedback, RelocateRenderElement};
 use smithay::backend::renderer::element::{
-    default_primary_scanout_output_compare, AsRenderElements, Id, Kind, PrimaryScanoutOutput,
-    RenderElementStates,
+    default_primary_scanout_output_compare, Id, Kind, PrimaryScanoutOutput, RenderElementStates,
 };
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::desktop::utils::{
@@ -31,11 +30,13 @@ use smithay::desktop::utils::{
     under_from_surface_tree, update_surface_primary_scanout_output, OutputPresentationFeedback,
 };
 use smithay::desktop::{
-    layer_map_for_output, LayerSurface, PopupGrab, PopupManager, PopupUngrabStrategy, Space,
-    Window, WindowSurfaceType,
+    layer_map_for_output, LayerSurface, PopupGrab, PopupKeyboardGrab, PopupManager,
+    PopupPointerGrab, PopupUngrabStrategy, Space, Window, WindowSurfaceType,
 };
 use smithay::input::keyboard::{Layout as KeyboardLayout, XkbContextHandler};
-use smithay::input::pointer::{CursorIcon, CursorImageAttributes, CursorImageStatus, MotionEvent};
+use smithay::input::pointer::{
+    CursorIcon, CursorImageAttributes, CursorImageStatus, Focus, MotionEvent,
+};
 use smithay::input::{Seat, SeatState};
 use smithay::output::{self, Output};
 use smithay::reexports::calloop::generic::Generic;
@@ -44,7 +45,9 @@ use smithay::reexports::calloop::{
     self, Idle, Interest, LoopHandle, LoopSignal, Mode, PostAction, RegistrationToken,
 };
 use smithay::reexports::input;
-use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::WmCapabilities;
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::{
+    self, WmCapabilities,
+};
 use smithay::reexports::wayland_protocols_misc::server_decoration as _server_decoration;
 use smithay::reexports::wayland_server::backend::{
     ClientData, ClientId, DisconnectReason, GlobalId,
@@ -52,7 +55,7 @@ use smithay::reexports::wayland_server::backend::{
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{Display, DisplayHandle, Resource};
 use smithay::utils::{
-    ClockSource, Logical, Monotonic, Physical, Point, Rectangle, Scale, Size, Transform,
+    ClockSource, Logical, Monotonic, Physical, Point, Rectangle, Scale, Serial, Size, Transform,
     SERIAL_COUNTER,
 };
 use smithay::wayland::compositor::{
@@ -61,6 +64,7 @@ use smithay::wayland::compositor::{
 };
 use smithay::wayland::cursor_shape::CursorShapeManagerState;
 use smithay::wayland::dmabuf::DmabufState;
+use smithay::wayland::fractional_scale::FractionalScaleManagerState;
 use smithay::wayland::idle_inhibit::IdleInhibitManagerState;
 use smithay::wayland::idle_notify::IdleNotifierState;
 use smithay::wayland::input_method::{InputMethodManagerState, InputMethodSeat};
@@ -83,6 +87,7 @@ use smithay::wayland::socket::ListeningSocketSource;
 use smithay::wayland::tablet_manager::{TabletManagerState, TabletSeatTrait};
 use smithay::wayland::text_input::TextInputManagerState;
 use smithay::wayland::virtual_keyboard::VirtualKeyboardManagerState;
+use smithay::wayland::xdg_activation::XdgActivationState;
 
 use crate::backend::tty::SurfaceDmabufFeedback;
 use crate::backend::{Backend, RenderResult, Tty, Winit};
@@ -96,17 +101,21 @@ use crate::exit_confirm_dialog::ExitConfirmDialog;
 use crate::frame_clock::FrameClock;
 use crate::handlers::configure_lock_surface;
 use crate::hotkey_overlay::HotkeyOverlay;
+use crate::input::resize_grab::end_interactive_resize;
 use crate::input::{apply_libinput_settings, TabletData};
 use crate::ipc::server::IpcServer;
 use crate::layout::{Layout, MonitorRenderElement};
 use crate::protocols::foreign_toplevel::{self, ForeignToplevelManagerState};
 use crate::pw_utils::{Cast, PipeWire};
 use crate::render_helpers::renderer::NiriRenderer;
-use crate::render_helpers::{render_to_texture, render_to_vec};
+use crate::render_helpers::{
+    render_surface_with_popups, render_to_texture, render_to_vec, RenderTarget,
+};
 use crate::screenshot_ui::{ScreenshotUi, ScreenshotUiRenderElement};
 use crate::utils::{
-    center, get_monotonic_time, make_screenshot_path, output_size, write_png_rgba8,
+    center, get_monotonic_time, glob_match, make_screenshot_path, output_size, write_png_rgba8,
 };
+use crate::window::rules::named_column_width;
 use crate::{animation, niri_render_elements};
 
 const CLEAR_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.];
@@ -167,8 +176,13 @@ pub struct Niri {
     pub data_device_state: DataDeviceState,
     pub primary_selection_state: PrimarySelectionState,
     pub data_control_state: DataControlState,
+    pub activation_state: XdgActivationState,
+    pub fractional_scale_manager_state: FractionalScaleManagerState,
     pub popups: PopupManager,
-    pub popup_grab: Option<PopupGrabState>,
+    /// Popup grabs of different roots, nested in the order they started, the active one last.
+    pub popup_grab: Vec<PopupGrabState>,
+    /// Root of the popups that asked for a grab while the input method had the keyboard grabbed.
+    pub popup_grab_blocked_by_ime: Option<WlSurface>,
     pub presentation_state: PresentationState,
     pub security_context_state: SecurityContextState,
 
@@ -179,6 +193,12 @@ pub struct Niri {
     // popup grabs are active (which means the real keyboard focus is on a popup descending from
     // this toplevel surface).
     pub keyboard_focus: Option<WlSurface>,
+    /// Windows marked with a key, so that they can be found later.
+    pub window_marks: HashMap<String, Window>,
+    /// Mapped windows hidden in the scratchpad, most recently hidden last.
+    ///
+    /// These windows are not in the layout.
+    pub scratchpad: Vec<Window>,
 
     pub idle_inhibiting_surfaces: HashSet<WlSurface>,
     pub is_fdo_idle_inhibited: Arc<AtomicBool>,
@@ -250,6 +270,8 @@ pub enum RedrawState {
 pub struct PopupGrabState {
     pub root: WlSurface,
     pub grab: PopupGrab<State>,
+    /// Whether the grab also holds the pointer.
+    pub grab_pointer: bool,
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -285,6 +307,76 @@ struct SurfaceFrameThrottlingState {
 #[derive(Default)]
 pub struct WindowOffscreenId(pub RefCell<Option<Id>>);
 
+/// Screen capture block out state of a window, set from its window rules.
+pub struct WindowBlockOut {
+    pub mode: Cell<Option<BlockOutMode>>,
+    /// Solid color drawn in place of the window when it's blocked out.
+    pub buffer: RefCell<SolidColorBuffer>,
+}
+
+/// Window rule state of a mapped window, stored in its user data.
+#[derive(Default)]
+pub struct WindowRulesState {
+    /// Title, app ID and whether there was a parent when the window rules were last resolved.
+    pub resolved_for: RefCell<Option<(Option<String>, Option<String>, bool)>>,
+    /// Opacity to render the window with.
+    pub opacity: Cell<Option<f32>>,
+    /// Border color override for when the window is active.
+    pub border_active_color: Cell<Option<Color>>,
+    /// Border color override for when the window is inactive.
+    pub border_inactive_color: Cell<Option<Color>>,
+    /// Minimum width of the window in logical pixels.
+    pub min_width: Cell<Option<i32>>,
+    /// Maximum width of the window in logical pixels.
+    pub max_width: Cell<Option<i32>>,
+    /// Scale to send to the window as preferred instead of the output scale.
+    pub scale_override: Cell<Option<f64>>,
+    /// Maximum number of windows stacked in the column of the window.
+    pub max_windows_per_column: Cell<Option<u16>>,
+    /// Whether the window stays above other floating windows.
+    pub always_on_top: Cell<Option<bool>>,
+    /// Maximum width of a new column when the window chooses its own width.
+    pub max_auto_width: Cell<Option<i32>>,
+    /// Whether the window gets focused when the pointer moves onto it.
+    pub focus_follows_mouse: Cell<Option<bool>>,
+    /// Whether popup grabs of the window also grab the pointer.
+    pub popup_pointer_grab: Cell<Option<bool>>,
+    /// Width-to-height ratio kept for the window when the layout sizes it.
+    pub aspect_ratio: Cell<Option<f64>>,
+}
+
+/// Always-on-top state toggled by the user, stored in the window user data.
+///
+/// Takes precedence over the always-on-top window rule.
+#[derive(Default)]
+pub struct AlwaysOnTopOverride(pub Cell<Option<bool>>);
+
+/// Whether a window belongs to the scratchpad, stored in its user data.
+///
+/// A scratchpad window goes back to the scratchpad when toggled while focused.
+#[derive(Default)]
+pub struct ScratchpadWindow(pub Cell<bool>);
+
+/// Time when a window was last mapped, stored in its user data.
+#[derive(Default)]
+pub struct WindowMapTime(pub Cell<Option<Duration>>);
+
+/// Urgency of a mapped window, stored in its user data.
+#[derive(Default)]
+pub struct WindowUrgency {
+    /// Whether the window requested attention and has not been focused since.
+    pub is_urgent: Cell<bool>,
+}
+
+impl Default for WindowBlockOut {
+    fn default() -> Self {
+        Self {
+            mode: Cell::new(None),
+            buffer: RefCell::new(SolidColorBuffer::new((0, 0), [0., 0., 0., 1.])),
+        }
+    }
+}
+
 impl Default for SurfaceFrameThrottlingState {
     fn default() -> Self {
         Self {
@@ -324,6 +416,10 @@ impl State {
         let mut niri = Niri::new(config.clone(), event_loop, stop_signal, display, &backend);
         backend.init(&mut niri);
 
+        // Check after the backend connected the outputs so they count as known.
+        niri.warn_about_window_rule_targets();
+        niri.warn_about_window_rule_presets();
+
         Ok(Self { backend, niri })
     }
 
@@ -332,6 +428,8 @@ impl State {
 
         // These should be called periodically, before flushing the clients.
         self.niri.layout.refresh();
+        self.refresh_popups_output();
+        self.refresh_layer_popups();
         self.niri.cursor_manager.check_cursor_image_surface_alive();
         self.niri.refresh_pointer_outputs();
         self.niri.popups.cleanup();
@@ -339,6 +437,7 @@ impl State {
         self.refresh_popup_grab();
         self.update_keyboard_focus();
         self.refresh_pointer_focus();
+        self.refresh_window_rules();
         foreign_toplevel::refresh(self);
 
         {
@@ -434,21 +533,138 @@ impl State {
     pub fn refresh_popup_grab(&mut self) {
         let keyboard_grabbed = self.niri.seat.input_method().keyboard_grabbed();
 
-        if let Some(grab) = &mut self.niri.popup_grab {
-            if grab.grab.has_ended() {
-                self.niri.popup_grab = None;
-            } else if keyboard_grabbed {
-                // HACK: remove popup grab if IME grabbed the keyboard, because we can't yet do
-                // popup grabs together with an IME grab.
-                // FIXME: do this properly.
+        if keyboard_grabbed && !self.niri.popup_grab.is_empty() {
+            // HACK: remove popup grabs if IME grabbed the keyboard, because we can't yet do
+            // popup grabs together with an IME grab.
+            // FIXME: do this properly.
+            let serial = self.niri.popup_grab.last().unwrap().grab.serial();
+            for mut grab in mem::take(&mut self.niri.popup_grab) {
                 grab.grab.ungrab(PopupUngrabStrategy::All);
-                self.niri.seat.get_pointer().unwrap().unset_grab(
-                    self,
-                    SERIAL_COUNTER.next_serial(),
-                    get_monotonic_time().as_millis() as u32,
-                );
-                self.niri.popup_grab = None;
             }
+            self.unset_popup_seat_grabs(serial);
+            return;
+        }
+
+        let Some(serial) = self.niri.popup_grab.last().map(|grab| grab.grab.serial()) else {
+            return;
+        };
+        if prune_popup_grabs(&mut self.niri.popup_grab, |grab| grab.grab.has_ended()) {
+            self.restore_popup_grab(serial);
+        }
+    }
+
+    /// Hands the keyboard, and the pointer if the grab holds it, back to the active popup grab.
+    ///
+    /// Called when the grabs above it went away; `ended_serial` is the serial of the grab that
+    /// was active. Seat grabs that something else took over in the meantime, like an interactive
+    /// resize, are left alone.
+    pub fn restore_popup_grab(&mut self, ended_serial: Serial) {
+        let Some(top) = self.niri.popup_grab.last() else {
+            self.unset_popup_seat_grabs(ended_serial);
+            return;
+        };
+
+        trace!("restoring grab for root {:?}", top.root);
+        let grab = top.grab.clone();
+        let grab_pointer = top.grab_pointer;
+        let serial = grab.serial();
+
+        let keyboard = self.niri.seat.get_keyboard().unwrap();
+        match seat_grab_after_popup_grab(
+            keyboard.is_grabbed(),
+            keyboard.has_grab(ended_serial),
+            true,
+        ) {
+            SeatGrabAfterPopupGrab::Keep => (),
+            SeatGrabAfterPopupGrab::Restore => {
+                keyboard.set_focus(self, grab.current_grab(), serial);
+                keyboard.set_grab(PopupKeyboardGrab::new(&grab), serial);
+            }
+            SeatGrabAfterPopupGrab::Unset => keyboard.unset_grab(),
+        }
+
+        let pointer = self.niri.seat.get_pointer().unwrap();
+        match seat_grab_after_popup_grab(
+            pointer.is_grabbed(),
+            pointer.has_grab(ended_serial),
+            grab_pointer,
+        ) {
+            SeatGrabAfterPopupGrab::Keep => (),
+            SeatGrabAfterPopupGrab::Restore => {
+                pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+            }
+            SeatGrabAfterPopupGrab::Unset => pointer.unset_grab(
+                self,
+                SERIAL_COUNTER.next_serial(),
+                get_monotonic_time().as_millis() as u32,
+            ),
+        }
+    }
+
+    /// Unsets the keyboard and pointer grabs if they still belong to the popup grab with this
+    /// serial.
+    ///
+    /// Another grab, like an interactive resize, might have replaced them, and it must keep going.
+    fn unset_popup_seat_grabs(&mut self, serial: Serial) {
+        let keyboard = self.niri.seat.get_keyboard().unwrap();
+        let action =
+            seat_grab_after_popup_grab(keyboard.is_grabbed(), keyboard.has_grab(serial), false);
+        if action == SeatGrabAfterPopupGrab::Unset {
+            keyboard.unset_grab();
+        }
+
+        let pointer = self.niri.seat.get_pointer().unwrap();
+        let action =
+            seat_grab_after_popup_grab(pointer.is_grabbed(), pointer.has_grab(serial), false);
+        if action == SeatGrabAfterPopupGrab::Unset {
+            pointer.unset_grab(
+                self,
+                SERIAL_COUNTER.next_serial(),
+                get_monotonic_time().as_millis() as u32,
+            );
+        }
+    }
+
+    /// Ends all popup grabs, dismissing their popups.
+    pub fn ungrab_popups(&mut self) {
+        let Some(serial) = self.niri.popup_grab.last().map(|grab| grab.grab.serial()) else {
+            return;
+        };
+
+        for mut grab in mem::take(&mut self.niri.popup_grab) {
+            grab.grab.ungrab(PopupUngrabStrategy::All);
+        }
+        self.unset_popup_seat_grabs(serial);
+    }
+
+    /// Ends the popup grabs along with any keyboard and pointer grabs.
+    ///
+    /// This is a way out of grabs that got stuck, so it unsets the seat grabs whoever holds them.
+    pub fn release_grabs(&mut self) {
+        for mut grab in mem::take(&mut self.niri.popup_grab) {
+            debug!("releasing popup grab on {:?}", grab.root);
+            grab.grab.ungrab(PopupUngrabStrategy::All);
+        }
+
+        self.niri.seat.get_keyboard().unwrap().unset_grab();
+        self.niri.seat.get_pointer().unwrap().unset_grab(
+            self,
+            SERIAL_COUNTER.next_serial(),
+            get_monotonic_time().as_millis() as u32,
+        );
+
+        // An interactive resize only ends from its pointer grab, which is gone now.
+        let mut resizing = Vec::new();
+        self.niri.layout.with_windows(|window, _, _| {
+            let is_resizing = window
+                .toplevel()
+                .with_pending_state(|state| state.states.contains(xdg_toplevel::State::Resizing));
+            if is_resizing {
+                resizing.push(window.clone());
+            }
+        });
+        for window in resizing {
+            end_interactive_resize(self, &window);
         }
     }
 
@@ -463,7 +679,7 @@ impl State {
 
             // Explicitly check for layer-shell popup grabs here, our keyboard focus will stay on
             // the root layer surface while it has grabs.
-            let layer_grab = self.niri.popup_grab.as_ref().and_then(|g| {
+            let layer_grab = self.niri.popup_grab.last().and_then(|g| {
                 layers
                     .layer_for_surface(&g.root, WindowSurfaceType::TOPLEVEL)
                     .map(|l| (&g.root, l.layer()))
@@ -513,7 +729,7 @@ impl State {
                 focus
             );
 
-            if let Some(grab) = self.niri.popup_grab.as_mut() {
+            if let Some(grab) = self.niri.popup_grab.last() {
                 if Some(&grab.root) != focus.as_ref() {
                     trace!(
                         "grab root {:?} is not the new focus {:?}, ungrabbing",
@@ -521,14 +737,20 @@ impl State {
                         focus
                     );
 
-                    grab.grab.ungrab(PopupUngrabStrategy::All);
-                    keyboard.unset_grab();
-                    self.niri.seat.get_pointer().unwrap().unset_grab(
-                        self,
-                        SERIAL_COUNTER.next_serial(),
-                        get_monotonic_time().as_millis() as u32,
-                    );
-                    self.niri.popup_grab = None;
+                    // Focus going back to the root of an earlier grab only ends the grabs above
+                    // it.
+                    let above = split_popup_grabs_above(&mut self.niri.popup_grab, |grab| {
+                        Some(&grab.root) == focus.as_ref()
+                    });
+                    if let Some(above) = above {
+                        let serial = above.last().unwrap().grab.serial();
+                        for mut grab in above {
+                            grab.grab.ungrab(PopupUngrabStrategy::All);
+                        }
+                        self.restore_popup_grab(serial);
+                    } else {
+                        self.ungrab_popups();
+                    }
                 }
             }
 
@@ -565,6 +787,16 @@ impl State {
                 }
             }
 
+            // Focusing a window answers its request for attention.
+            if let Some((window, _)) = focus
+                .as_ref()
+                .and_then(|focus| self.niri.layout.find_window_and_output(focus))
+            {
+                if let Some(urgency) = window.user_data().get::<WindowUrgency>() {
+                    urgency.is_urgent.set(false);
+                }
+            }
+
             self.niri.keyboard_focus = focus.clone();
             keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
 
@@ -641,11 +873,35 @@ impl State {
             self.niri.hotkey_overlay.on_hotkey_config_updated();
         }
 
+        let window_rules_changed = config.window_rules != old_config.window_rules;
+        let named_widths_changed =
+            config.layout.named_column_widths != old_config.layout.named_column_widths;
+
         *old_config = config;
 
         // Release the borrow.
         drop(old_config);
 
+        if window_rules_changed || output_config_changed {
+            self.niri.warn_about_window_rule_targets();
+        }
+
+        if window_rules_changed || named_widths_changed {
+            self.niri.warn_about_window_rule_presets();
+
+            // Make the rules get re-resolved for every mapped window, including the ones hidden
+            // in the scratchpad, which pick them up once they're back in the layout.
+            let invalidate = |window: &Window| {
+                if let Some(state) = window.user_data().get::<WindowRulesState>() {
+                    state.resolved_for.replace(None);
+                }
+            };
+            self.niri
+                .layout
+                .with_windows(|window, _, _| invalidate(window));
+            self.niri.scratchpad.iter().for_each(invalidate);
+        }
+
         // Now with a &mut self we can reload the xkb config.
         if let Some(xkb) = reload_xkb {
             let keyboard = self.niri.seat.get_keyboard().unwrap();
@@ -820,7 +1076,7 @@ impl Niri {
         let compositor_state = CompositorState::new_v6::<State>(&display_handle);
         let xdg_shell_state = XdgShellState::new_with_capabilities::<State>(
             &display_handle,
-            [WmCapabilities::Fullscreen],
+            [WmCapabilities::Fullscreen, WmCapabilities::Maximize],
         );
         let xdg_decoration_state =
             XdgDecorationState::new_with_filter::<State, _>(&display_handle, |client| {
@@ -866,6 +1122,9 @@ impl Niri {
             Some(&primary_selection_state),
             |client| !client.get_data::<ClientState>().unwrap().restricted,
         );
+        let activation_state = XdgActivationState::new::<State>(&display_handle);
+        let fractional_scale_manager_state =
+            FractionalScaleManagerState::new::<State>(&display_handle);
         let presentation_state =
             PresentationState::new::<State>(&display_handle, Monotonic::ID as u32);
         let security_context_state =
@@ -1022,14 +1281,19 @@ impl Niri {
             data_device_state,
             primary_selection_state,
             data_control_state,
+            activation_state,
+            fractional_scale_manager_state,
             popups: PopupManager::default(),
-            popup_grab: None,
+            popup_grab: Vec::new(),
+            popup_grab_blocked_by_ime: None,
             suppressed_keys: HashSet::new(),
             presentation_state,
             security_context_state,
 
             seat,
             keyboard_focus: None,
+            window_marks: HashMap::new(),
+            scratchpad: Vec::new(),
             idle_inhibiting_surfaces: HashSet::new(),
             is_fdo_idle_inhibited: Arc::new(AtomicBool::new(false)),
             cursor_manager,
@@ -1247,6 +1511,11 @@ impl Niri {
         let rv = self.output_by_name.insert(name, output.clone());
         assert!(rv.is_none(), "output was already tracked");
 
+        // Windows that got configured while there were no outputs can get a proper size now.
+        self.event_loop.insert_idle(|state| {
+            state.configure_windows_waiting_for_output();
+        });
+
         // Must be last since it will call queue_redraw(output) which needs things to be filled-in.
         self.reposition_outputs(Some(&output));
     }
@@ -1612,10 +1881,46 @@ impl Niri {
         };
         let layer_shell_output = || self.layout.outputs().find(has_layer_surface);
 
-        layout_output.or_else(layer_shell_output)
+        // Check the lock screen.
+        let lock_output = || self.output_for_lock_surface(root);
+
+        layout_output
+            .or_else(layer_shell_output)
+            .or_else(lock_output)
+    }
+
+    /// Returns the output that the lock surface is on.
+    pub fn output_for_lock_surface(&self, surface: &WlSurface) -> Option<&Output> {
+        self.output_state.iter().find_map(|(output, state)| {
+            let lock_surface = state.lock_surface.as_ref()?;
+            (lock_surface.wl_surface() == surface).then_some(output)
+        })
+    }
+
+    /// Returns the output containing `pos`, falling back to the output of the root surface.
+    ///
+    /// Useful for popups of windows extending past the edge of their output, where `pos` is the
+    /// center of the popup's anchor rectangle, e.g. where a right click happened.
+    pub fn output_for_root_at(
+        &self,
+        root: &WlSurface,
+        pos: Point<f64, Logical>,
+    ) -> Option<&Output> {
+        self.global_space
+            .output_under(pos)
+            .next()
+            .or_else(|| self.output_for_root(root))
     }
 
     pub fn lock_surface_focus(&self) -> Option<WlSurface> {
+        // A lock surface with a popup grab, like an open dropdown, keeps the focus even when the
+        // pointer moves to another output.
+        if let Some(grab) = self.popup_grab.last() {
+            if self.output_for_lock_surface(&grab.root).is_some() {
+                return Some(grab.root.clone());
+            }
+        }
+
         let output_under_cursor = self.output_under_cursor();
         let output = output_under_cursor
             .as_ref()
@@ -1626,6 +1931,78 @@ impl Niri {
         state.lock_surface.as_ref().map(|s| s.wl_surface()).cloned()
     }
 
+    /// Warns about window rules that open windows on outputs or workspaces that don't exist.
+    ///
+    /// Windows matching such rules silently open on the active output or workspace, so a typo in a
+    /// name is easy to miss otherwise. Outputs count as existing when they are connected or have
+    /// an output section in the config, since rules may target outputs that get plugged in later.
+    pub fn warn_about_window_rule_targets(&self) {
+        let config = self.config.borrow();
+
+        let output_exists = |name: &str| {
+            let mut known = self
+                .output_by_name
+                .keys()
+                .map(String::as_str)
+                .chain(config.outputs.iter().map(|o| o.name.as_str()));
+            if name.contains(['*', '?']) {
+                known.any(|known| glob_match(name, known))
+            } else {
+                known.any(|known| known == name)
+            }
+        };
+
+        for (idx, rule) in config.window_rules.iter().enumerate() {
+            // Count from one to match how people refer to rules in the config.
+            let number = idx + 1;
+
+            for name in rule.open_on_output.iter().flatten() {
+                if !output_exists(name) {
+                    warn!(
+                        "window rule #{number}: open-on-output {name:?} does not match \
+                         any connected or configured output"
+                    );
+                }
+            }
+
+            if let Some(name) = &rule.open_on_workspace {
+                if !config.workspaces.iter().any(|ws| &ws.name == name) {
+                    warn!("window rule #{number}: open-on-workspace {name:?} does not exist");
+                }
+            }
+        }
+    }
+
+    /// Warns about window rules that refer to column width presets that don't exist.
+    ///
+    /// Such rules are ignored entirely. The check runs when the config loads rather than when the
+    /// rules are resolved, since that happens on every title change.
+    pub fn warn_about_window_rule_presets(&self) {
+        let config = self.config.borrow();
+
+        for (idx, rule) in config.window_rules.iter().enumerate() {
+            let Some(name) = &rule.default_column_width_preset else {
+                continue;
+            };
+
+            if named_column_width(&config, name).is_none() {
+                // Count from one to match how people refer to rules in the config.
+                let number = idx + 1;
+                warn!(
+                    "window rule #{number}: column width preset {name:?} does not exist, \
+                     ignoring the rule"
+                );
+            }
+        }
+    }
+
+    /// Sends an event to the IPC event stream clients.
+    pub fn ipc_send_event(&self, event: niri_ipc::Event) {
+        if let Some(server) = &self.ipc_server {
+            server.send_event(event);
+        }
+    }
+
     /// Schedules an immediate redraw on all outputs if one is not already scheduled.
     pub fn queue_redraw_all(&mut self) {
         let outputs: Vec<_> = self.output_state.keys().cloned().collect();
@@ -1897,6 +2274,7 @@ impl Niri {
         renderer: &mut R,
         output: &Output,
         include_pointer: bool,
+        target: RenderTarget,
     ) -> Vec<OutputRenderElements<R>> {
         let _span = tracy_client::span!("Niri::render");
 
@@ -1982,7 +2360,7 @@ impl Niri {
 
         // Get monitor elements.
         let mon = self.layout.monitor_for_output(output).unwrap();
-        let monitor_elements = mon.render_elements(renderer);
+        let monitor_elements = mon.render_elements(renderer, target);
 
         // Get layer-shell elements.
         let layer_map = layer_map_for_output(output);
@@ -1995,15 +2373,16 @@ impl Niri {
                         .map(|geo| (geo.loc, surface))
                 })
                 .flat_map(|(loc, surface)| {
-                    surface
-                        .render_elements(
-                            renderer,
-                            loc.to_physical_precise_round(output_scale),
-                            output_scale,
-                            1.,
-                        )
-                        .into_iter()
-                        .map(OutputRenderElements::Wayland)
+                    render_surface_with_popups(
+                        renderer,
+                        surface.wl_surface(),
+                        loc,
+                        Point::from((0, 0)),
+                        output_scale,
+                        1.,
+                    )
+                    .into_iter()
+                    .map(OutputRenderElements::Wayland)
                 });
             elements.extend(iter);
         };
@@ -2044,6 +2423,9 @@ impl Niri {
 
         let mut res = RenderResult::Skipped;
         if self.monitors_active {
+            // Apply interactive resizes once per frame, to throttle the resulting configures.
+            self.layout.apply_pending_interactive_resizes();
+
             // Update from the config and advance the animations.
             self.layout.advance_animations(target_presentation_time);
             state.unfinished_animations_remain = self
@@ -2551,8 +2933,9 @@ impl Niri {
                 let dmabuf = cast.dmabufs.borrow()[&fd].clone();
 
                 // FIXME: Hidden / embedded / metadata cursor
-                let elements = elements
-                    .get_or_insert_with(|| self.render::<GlesRenderer>(renderer, output, true));
+                let elements = elements.get_or_insert_with(|| {
+                    self.render::<GlesRenderer>(renderer, output, true, RenderTarget::Screencast)
+                });
                 let elements = elements.iter().rev();
 
                 if let Err(err) = render_to_dmabuf(renderer, dmabuf, size, scale, elements) {
@@ -2631,7 +3014,12 @@ impl Niri {
                 let size = transform.transform_size(size);
 
                 let scale = Scale::from(output.current_scale().fractional_scale());
-                let elements = self.render::<GlesRenderer>(renderer, &output, true);
+                let elements = self.render::<GlesRenderer>(
+                    renderer,
+                    &output,
+                    true,
+                    RenderTarget::ScreenCapture,
+                );
                 let elements = elements.iter().rev();
 
                 let res = render_to_texture(renderer, size, scale, Fourcc::Abgr8888, elements);
@@ -2662,7 +3050,8 @@ impl Niri {
         let size = transform.transform_size(size);
 
         let scale = Scale::from(output.current_scale().fractional_scale());
-        let elements = self.render::<GlesRenderer>(renderer, output, true);
+        let elements =
+            self.render::<GlesRenderer>(renderer, output, true, RenderTarget::ScreenCapture);
         let elements = elements.iter().rev();
         let pixels = render_to_vec(renderer, size, scale, Fourcc::Abgr8888, elements)?;
 
@@ -2681,13 +3070,14 @@ impl Niri {
         let scale = Scale::from(output.current_scale().fractional_scale());
         let bbox = window.bbox_with_popups();
         let size = bbox.size.to_physical_precise_ceil(scale);
-        let buf_pos = Point::from((0, 0)) - bbox.loc;
+        let location = window.geometry().loc - bbox.loc;
         // FIXME: pointer.
-        let elements = window.render_elements::<WaylandSurfaceRenderElement<GlesRenderer>>(
+        let elements = crate::layout::LayoutElement::render(
+            window,
             renderer,
-            buf_pos.to_physical_precise_ceil(scale),
+            location,
             scale,
-            1.,
+            RenderTarget::ScreenCapture,
         );
         let elements = elements.iter().rev();
         let pixels = render_to_vec(renderer, size, scale, Fourcc::Abgr8888, elements)?;
@@ -2787,7 +3177,12 @@ impl Niri {
         let transform = output.current_transform();
         let size = transform.transform_size(size);
 
-        let elements = self.render::<GlesRenderer>(renderer, &output, include_pointer);
+        let elements = self.render::<GlesRenderer>(
+            renderer,
+            &output,
+            include_pointer,
+            RenderTarget::ScreenCapture,
+        );
         let elements = elements.iter().rev();
         let pixels = render_to_vec(
             renderer,
@@ -2931,6 +3326,82 @@ impl Niri {
     }
 }
 
+/// Adds a popup grab on top of the stack of popup grabs.
+///
+/// Smithay's `PopupGrab` already nests the grabs of one root, so a grab of the same root as the
+/// active one replaces it.
+pub fn push_popup_grab<T>(stack: &mut Vec<T>, grab: T, same_root: impl Fn(&T, &T) -> bool) {
+    if stack.last().map_or(false, |top| same_root(top, &grab)) {
+        stack.pop();
+    }
+    stack.push(grab);
+}
+
+/// Drops the popup grabs that have ended.
+///
+/// Returns whether the active grab was dropped, so that the one below it becomes active again.
+pub fn prune_popup_grabs<T>(stack: &mut Vec<T>, has_ended: impl Fn(&T) -> bool) -> bool {
+    let top_ended = stack.last().map_or(false, &has_ended);
+    stack.retain(|grab| !has_ended(grab));
+    top_ended
+}
+
+/// Takes the popup grabs above the first one matching `is_target` off the stack.
+///
+/// Returns `None` and leaves the stack alone if no grab matches.
+pub fn split_popup_grabs_above<T>(
+    stack: &mut Vec<T>,
+    is_target: impl Fn(&T) -> bool,
+) -> Option<Vec<T>> {
+    let idx = stack.iter().position(is_target)?;
+    Some(stack.split_off(idx + 1))
+}
+
+/// Takes the popup grabs matching `is_root` off the stack, like when their root goes away.
+///
+/// Returns the taken grabs and whether the active grab was among them.
+pub fn take_popup_grabs_of_root<T>(
+    stack: &mut Vec<T>,
+    is_root: impl Fn(&T) -> bool,
+) -> (Vec<T>, bool) {
+    let top_taken = stack.last().map_or(false, &is_root);
+    let (taken, kept) = mem::take(stack).into_iter().partition(is_root);
+    *stack = kept;
+    (taken, top_taken)
+}
+
+/// What happens to a keyboard or pointer grab once the active popup grab ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeatGrabAfterPopupGrab {
+    /// Leave the seat grab as is.
+    Keep,
+    /// Hand the seat grab to the popup grab that is active now.
+    Restore,
+    /// Unset the seat grab.
+    Unset,
+}
+
+/// Decides what happens to a keyboard or pointer grab once the active popup grab ends.
+///
+/// `held_by_ended` is whether the seat grab still belongs to the popup grab that ended, and
+/// `restore` whether a popup grab that is active now wants the seat grab. Seat grabs that
+/// something else took over, like an interactive resize, are kept.
+pub fn seat_grab_after_popup_grab(
+    is_grabbed: bool,
+    held_by_ended: bool,
+    restore: bool,
+) -> SeatGrabAfterPopupGrab {
+    if is_grabbed && !held_by_ended {
+        SeatGrabAfterPopupGrab::Keep
+    } else if restore {
+        SeatGrabAfterPopupGrab::Restore
+    } else if held_by_ended {
+        SeatGrabAfterPopupGrab::Unset
+    } else {
+        SeatGrabAfterPopupGrab::Keep
+    }
+}
+
 pub struct ClientState {
     pub compositor_state: CompositorClientState,
     pub can_view_decoration_globals: bool,
@@ -2954,3 +3425,111 @@ niri_render_elements! {
         RelocatedMemoryBuffer = RelocateRenderElement<MemoryRenderBufferRenderElement<R>>,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_popup_grab_then_release() {
+        // (root, has ended)
+        let mut stack = Vec::new();
+        let same_root = |a: &(&str, bool), b: &(&str, bool)| a.0 == b.0;
+
+        push_popup_grab(&mut stack, ("a", false), same_root);
+        // A nested grab of the same root replaces the level.
+        push_popup_grab(&mut stack, ("a", false), same_root);
+        assert_eq!(stack, [("a", false)]);
+
+        // A grab of another root goes on top.
+        push_popup_grab(&mut stack, ("b", false), same_root);
+        assert_eq!(stack, [("a", false), ("b", false)]);
+        assert!(!prune_popup_grabs(&mut stack, |grab| grab.1));
+
+        // Releasing the nested grab makes the previous one active again.
+        stack[1].1 = true;
+        assert!(prune_popup_grabs(&mut stack, |grab| grab.1));
+        assert_eq!(stack, [("a", false)]);
+
+        // An ended grab below the active one goes away without changing the active grab.
+        push_popup_grab(&mut stack, ("b", false), same_root);
+        stack[0].1 = true;
+        assert!(!prune_popup_grabs(&mut stack, |grab| grab.1));
+        assert_eq!(stack, [("b", false)]);
+    }
+
+    #[test]
+    fn focus_returning_to_earlier_grab_root() {
+        let mut stack = vec!["a", "b", "c"];
+
+        assert_eq!(split_popup_grabs_above(&mut stack, |r| *r == "d"), None);
+        assert_eq!(stack, ["a", "b", "c"]);
+
+        assert_eq!(
+            split_popup_grabs_above(&mut stack, |r| *r == "a"),
+            Some(vec!["b", "c"])
+        );
+        assert_eq!(stack, ["a"]);
+    }
+
+    #[test]
+    fn destroying_popup_grab_root() {
+        // Destroying the root of the only grab releases it.
+        let mut stack = vec!["a"];
+        assert_eq!(
+            take_popup_grabs_of_root(&mut stack, |r| *r == "a"),
+            (vec!["a"], true)
+        );
+        assert!(stack.is_empty());
+
+        // Destroying the root of the active grab makes the one below it active again.
+        let mut stack = vec!["a", "b"];
+        assert_eq!(
+            take_popup_grabs_of_root(&mut stack, |r| *r == "b"),
+            (vec!["b"], true)
+        );
+        assert_eq!(stack, ["a"]);
+
+        // Destroying the root of a grab below the active one leaves the active grab alone.
+        let mut stack = vec!["a", "b"];
+        assert_eq!(
+            take_popup_grabs_of_root(&mut stack, |r| *r == "a"),
+            (vec!["a"], false)
+        );
+        assert_eq!(stack, ["b"]);
+
+        // Roots without grabs don't touch the stack.
+        let mut stack = vec!["a", "b"];
+        assert_eq!(
+            take_popup_grabs_of_root(&mut stack, |r| *r == "c"),
+            (vec![], false)
+        );
+        assert_eq!(stack, ["a", "b"]);
+    }
+
+    #[test]
+    fn screenshot_ui_opening_over_menu() {
+        use SeatGrabAfterPopupGrab::*;
+
+        // An open menu and its submenu hold the keyboard and the pointer. Taking over input for
+        // the screenshot UI ends every grab level, so nothing is left to restore and both seat
+        // grabs go away.
+        let mut stack = vec!["menu", "submenu"];
+        let ended = mem::take(&mut stack);
+        assert_eq!(ended, ["menu", "submenu"]);
+        assert_eq!(
+            seat_grab_after_popup_grab(true, true, !stack.is_empty()),
+            Unset
+        );
+
+        // An interactive resize that took the pointer from the menu keeps going.
+        assert_eq!(seat_grab_after_popup_grab(true, false, false), Keep);
+
+        // Seat grabs that were already released stay released.
+        assert_eq!(seat_grab_after_popup_grab(false, false, false), Keep);
+
+        // Closing just the submenu, on the other hand, hands the seat grabs back to the menu.
+        assert_eq!(seat_grab_after_popup_grab(true, true, true), Restore);
+        assert_eq!(seat_grab_after_popup_grab(false, false, true), Restore);
+    }
+}
@@ -1,16 +1,88 @@
 use anyhow::Context;
+use niri_config::BlockOutMode;
 use smithay::backend::allocator::Fourcc;
-use smithay::backend::renderer::element::RenderElement;
+use smithay::backend::renderer::element::surface::{
+    render_elements_from_surface_tree, WaylandSurfaceRenderElement,
+};
+use smithay::backend::renderer::element::{Kind, RenderElement};
 use smithay::backend::renderer::gles::{GlesMapping, GlesRenderer, GlesTexture};
 use smithay::backend::renderer::sync::SyncPoint;
 use smithay::backend::renderer::{Bind, ExportMem, Frame, Offscreen, Renderer};
-use smithay::utils::{Physical, Rectangle, Scale, Size, Transform};
+use smithay::desktop::PopupManager;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{Logical, Physical, Point, Rectangle, Scale, Size, Transform};
+
+use self::renderer::NiriRenderer;
 
 pub mod offscreen;
 pub mod primary_gpu_texture;
 pub mod render_elements;
 pub mod renderer;
 
+/// What we're rendering for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderTarget {
+    /// Rendering to display on screen.
+    Output,
+    /// Rendering for a screencast.
+    Screencast,
+    /// Rendering for any other screen capture, like a screenshot.
+    ScreenCapture,
+}
+
+impl RenderTarget {
+    /// Returns whether a window with the given block out mode should be hidden.
+    pub fn should_block_out(self, block_out_from: Option<BlockOutMode>) -> bool {
+        match block_out_from {
+            None => false,
+            Some(BlockOutMode::Screencast) => self == RenderTarget::Screencast,
+            Some(BlockOutMode::ScreenCapture) => self != RenderTarget::Output,
+        }
+    }
+}
+
+/// Renders a root surface with its popups on top.
+///
+/// `location` is where the surface geometry goes, and `geometry_loc` is the offset of the
+/// geometry within the surface buffer.
+///
+/// Smithay rounds the surface location and the popup offsets to physical pixels separately,
+/// which at fractional scales can put popups a pixel off, leaving thin seams. Here every element
+/// position is computed in logical coordinates and rounded once.
+pub fn render_surface_with_popups<R: NiriRenderer, E: From<WaylandSurfaceRenderElement<R>>>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    location: Point<i32, Logical>,
+    geometry_loc: Point<i32, Logical>,
+    scale: Scale<f64>,
+    alpha: f32,
+) -> Vec<E> {
+    let mut elements: Vec<E> = PopupManager::popups_for_surface(surface)
+        .flat_map(|(popup, popup_offset)| {
+            let popup_pos = location + popup_offset - popup.geometry().loc;
+            render_elements_from_surface_tree(
+                renderer,
+                popup.wl_surface(),
+                popup_pos.to_physical_precise_round(scale),
+                scale,
+                alpha,
+                Kind::Unspecified,
+            )
+        })
+        .collect();
+
+    let buf_pos = location - geometry_loc;
+    elements.extend(render_elements_from_surface_tree(
+        renderer,
+        surface,
+        buf_pos.to_physical_precise_round(scale),
+        scale,
+        alpha,
+        Kind::Unspecified,
+    ));
+    elements
+}
+
 pub fn render_to_texture(
     renderer: &mut GlesRenderer,
     size: Size<i32, Physical>,
@@ -13,9 +13,12 @@ use anyhow::{ensure, Context};
 use directories::UserDirs;
 use git_version::git_version;
 use niri_config::Config;
-use smithay::output::Output;
+use smithay::output::{self, Output};
 use smithay::reexports::rustix::time::{clock_gettime, ClockId};
-use smithay::utils::{Logical, Point, Rectangle, Size};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{Logical, Point, Rectangle, Size, Transform};
+use smithay::wayland::compositor::{send_surface_state, SurfaceData};
+use smithay::wayland::fractional_scale::with_fractional_scale;
 
 pub fn clone2<T: Clone, U: Clone>(t: (&T, &U)) -> (T, U) {
     (t.0.clone(), t.1.clone())
@@ -34,10 +37,61 @@ pub fn get_monotonic_time() -> Duration {
     Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
 }
 
+/// Sends the preferred scale and transform to a surface.
+///
+/// The integer scale goes out as the `wl_surface` preferred buffer scale, and the exact scale
+/// through wp-fractional-scale to surfaces that use it.
+pub fn send_scale_transform(
+    surface: &WlSurface,
+    data: &SurfaceData,
+    scale: output::Scale,
+    transform: Transform,
+) {
+    send_surface_state(surface, data, scale.integer_scale(), transform);
+    with_fractional_scale(data, |fractional| {
+        fractional.set_preferred_scale(scale.fractional_scale());
+    });
+}
+
 pub fn center(rect: Rectangle<i32, Logical>) -> Point<i32, Logical> {
     rect.loc + rect.size.downscale(2).to_point()
 }
 
+/// Matches `name` against a glob `pattern`, where `*` stands for any number of characters and `?`
+/// for exactly one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name character it's matched up to.
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => {
+                // Let the last `*` take one more character and try again.
+                let Some((star_p, star_n)) = star else {
+                    return false;
+                };
+                star = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 pub fn output_size(output: &Output) -> Size<i32, Logical> {
     let output_scale = output.current_scale().integer_scale();
     let output_transform = output.current_transform();
@@ -206,3 +260,21 @@ pub fn cause_panic() {
     let b = Duration::from_secs(2);
     let _ = a - b;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("DP-1", "DP-1"));
+        assert!(!glob_match("DP-1", "DP-10"));
+        assert!(glob_match("DP-*", "DP-1"));
+        assert!(glob_match("DP-*", "DP-"));
+        assert!(!glob_match("DP-*", "eDP-1"));
+        assert!(glob_match("*DP-?", "eDP-1"));
+        assert!(!glob_match("DP-?", "DP-10"));
+        assert!(glob_match("*-*-1", "HDMI-A-1"));
+        assert!(!glob_match("*-*-1", "HDMI-A-2"));
+    }
+}
@@ -0,0 +1,988 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use niri_config::{
+    ActivationFocus, BlockOutMode, Color, ColorScheme, ColumnPosition, Config, DecorationMode,
+    FloatingPosition, Match, MaximizeTarget, NamedWidth,
+};
+use smithay::output::Output;
+use smithay::reexports::wayland_server::{DisplayHandle, Resource};
+use smithay::wayland::compositor::with_states;
+use smithay::wayland::shell::xdg::{SurfaceCachedState, ToplevelSurface, XdgToplevelSurfaceData};
+
+use crate::layout::workspace::{ColumnWidth, WindowHeight};
+use crate::utils::glob_match;
+
+/// Window rule properties resolved for a particular window.
+///
+/// The rules are resolved both when the window opens and whenever its title, app ID or output
+/// changes, but every property belongs to one of two kinds:
+///
+/// - *Initial* properties take effect once, in the initial configure and when the window maps.
+///   They are ignored on later resolutions, so changing the title won't move or resize a window.
+/// - *Dynamic* properties are applied to the window every time the rules are resolved for a
+///   mapped window, so they follow the window title and config changes.
+#[derive(Debug, Default)]
+pub struct ResolvedWindowRule<'a> {
+    // Initial properties.
+    /// Default width for this window.
+    ///
+    /// - `None`: unset.
+    /// - `Some(None)`: set to empty.
+    /// - `Some(Some(width))`: set to a particular width.
+    pub default_width: Option<Option<ColumnWidth>>,
+
+    /// Default widths for this window on particular outputs, by output name.
+    ///
+    /// Later entries take precedence, and all of them take precedence over `default_width`.
+    pub default_width_on_output: Vec<(&'a str, Option<ColumnWidth>)>,
+
+    /// Maximum width of the column when the window chooses its own width.
+    pub max_auto_width: Option<i32>,
+
+    /// Default height for this window.
+    ///
+    /// - `None`: unset.
+    /// - `Some(None)`: set to empty.
+    /// - `Some(Some(height))`: set to a particular height.
+    pub default_height: Option<Option<WindowHeight>>,
+
+    /// Outputs to open this window on, in order of preference.
+    pub open_on_output: Option<&'a [String]>,
+
+    /// Named workspace to open this window on.
+    pub open_on_workspace: Option<&'a str>,
+
+    /// Whether to move the window to its open-on-output when it changes its app ID later on.
+    pub reapply_open_on_output: Option<bool>,
+
+    /// Whether the window should open maximized.
+    pub open_maximized: Option<bool>,
+
+    /// Whether the window should open fullscreen.
+    pub open_fullscreen: Option<bool>,
+
+    /// Whether the window should open floating.
+    pub open_floating: Option<bool>,
+
+    /// Where the window should appear if it opens floating.
+    pub default_floating_position: Option<FloatingPosition>,
+
+    /// Whether the window should open as a new tab in the active column.
+    pub open_as_tab: Option<bool>,
+
+    /// Whether the window should open in the column of its parent window, right below it.
+    pub open_below_parent: Option<bool>,
+
+    /// Whether the window should take the place of the focused window, closing it.
+    pub open_replacing_focused: Option<bool>,
+
+    /// Whether the window should open hidden in the scratchpad.
+    pub open_in_scratchpad: Option<bool>,
+
+    /// Whether the window should take focus when it opens.
+    pub open_focused: Option<bool>,
+
+    /// Whether to switch to the workspace and output where the window opens, if elsewhere.
+    pub focus_on_open_across_workspace: Option<bool>,
+
+    /// Where in the workspace the column of the window should go.
+    pub default_column_position: Option<ColumnPosition>,
+
+    /// Column count past which the window opens on an empty workspace instead.
+    pub max_columns: Option<u16>,
+
+    /// Maximum number of windows stacked in the column of the window.
+    pub max_windows_per_column: Option<u16>,
+
+    /// Key to mark the window with when it opens.
+    pub mark: Option<&'a str>,
+
+    /// Whether the window should be told that it's tiled on all sides.
+    ///
+    /// Overrides the default, which follows `prefer-no-csd`.
+    pub tiled_state: Option<bool>,
+
+    /// Whether the window should draw its own decorations.
+    ///
+    /// Used when the client negotiates its decoration mode, which normally happens before the
+    /// window maps.
+    pub prefer_csd: Option<bool>,
+
+    /// Decoration mode that the window gets regardless of the mode it requests.
+    pub force_decoration: Option<DecorationMode>,
+
+    // Dynamic properties.
+    /// Screen captures that the window should be blocked out from.
+    pub block_out_from: Option<BlockOutMode>,
+
+    /// Opacity of the window.
+    pub opacity: Option<f32>,
+
+    /// Border color to use when the window is active.
+    pub border_active_color: Option<Color>,
+
+    /// Border color to use when the window is inactive.
+    pub border_inactive_color: Option<Color>,
+
+    /// Minimum width of the window in logical pixels.
+    pub min_width: Option<i32>,
+
+    /// Maximum width of the window in logical pixels.
+    pub max_width: Option<i32>,
+
+    /// Scale to send to the window as preferred instead of the output scale.
+    pub scale_override: Option<f64>,
+
+    /// Whether the window, when floating, stays above other floating windows.
+    pub always_on_top: Option<bool>,
+
+    /// Whether the window gets focused when the pointer moves onto it.
+    ///
+    /// Overrides the `focus-follows-mouse` input setting.
+    pub focus_follows_mouse: Option<bool>,
+
+    /// Whether popup grabs of the window also grab the pointer.
+    ///
+    /// Without the pointer grab, the pointer keeps working with other surfaces while a popup of
+    /// the window holds the keyboard.
+    pub popup_pointer_grab: Option<bool>,
+
+    /// Width-to-height ratio to keep for the window.
+    ///
+    /// In a column, the window gets the height matching the column width, unless its height was
+    /// set explicitly.
+    pub aspect_ratio: Option<f64>,
+
+    /// Whether to focus the window when it requests activation.
+    ///
+    /// Resolved when the window requests activation rather than applied to the window.
+    pub activation_focus: Option<ActivationFocus>,
+
+    /// What maximizing the window does.
+    ///
+    /// Resolved when the window gets maximized rather than applied to the window.
+    pub maximize_to: Option<MaximizeTarget>,
+
+    /// Color scheme that the window should prefer.
+    ///
+    /// niri doesn't pass the color scheme on to windows, so this is only reported over IPC.
+    pub color_scheme: Option<ColorScheme>,
+}
+
+impl ResolvedWindowRule<'_> {
+    /// Returns the default width for this window when it opens on `output`.
+    pub fn default_width_for_output(&self, output: Option<&Output>) -> Option<Option<ColumnWidth>> {
+        if let Some(output) = output {
+            let name = output.name();
+            let mut on_output = self.default_width_on_output.iter().rev();
+            if let Some((_, width)) = on_output.find(|(o, _)| *o == name) {
+                return Some(*width);
+            }
+        }
+
+        self.default_width
+    }
+
+    /// Returns the first connected output out of the ones to open this window on.
+    ///
+    /// Names with `*` or `?` are glob patterns. When a pattern matches several connected outputs,
+    /// the one with the alphabetically first name is picked, so that the choice is stable.
+    pub fn open_on_connected_output<'b>(
+        &self,
+        output_by_name: &'b HashMap<String, Output>,
+    ) -> Option<&'b Output> {
+        self.open_on_output?.iter().find_map(|name| {
+            if !name.contains(['*', '?']) {
+                return output_by_name.get(name);
+            }
+
+            output_by_name
+                .iter()
+                .filter(|(output_name, _)| glob_match(name, output_name))
+                .min_by_key(|(output_name, _)| *output_name)
+                .map(|(_, output)| output)
+        })
+    }
+
+    /// Converts the resolved properties for reporting over IPC.
+    pub fn to_ipc(&self) -> niri_ipc::ResolvedWindowRule {
+        let color = |c: Color| [c.r, c.g, c.b, c.a];
+
+        niri_ipc::ResolvedWindowRule {
+            default_column_width: self.default_width.map(|width| match width {
+                None => niri_ipc::RuleSize::WindowChosen,
+                Some(ColumnWidth::Proportion(p)) => niri_ipc::RuleSize::Proportion(p),
+                Some(ColumnWidth::Fixed(f)) => niri_ipc::RuleSize::Fixed(f),
+                Some(ColumnWidth::Preset(_)) => {
+                    unreachable!("window rules don't use preset indices")
+                }
+            }),
+            max_auto_width: self.max_auto_width,
+            default_window_height: self.default_height.map(|height| match height {
+                None | Some(WindowHeight::Auto) => niri_ipc::RuleSize::WindowChosen,
+                Some(WindowHeight::Fixed(f)) => niri_ipc::RuleSize::Fixed(f),
+            }),
+            open_on_output: self.open_on_output.map(|x| x.to_vec()),
+            open_on_workspace: self.open_on_workspace.map(String::from),
+            reapply_open_on_output: self.reapply_open_on_output,
+            open_maximized: self.open_maximized,
+            open_fullscreen: self.open_fullscreen,
+            open_floating: self.open_floating,
+            default_floating_position: self.default_floating_position.map(|pos| match pos {
+                FloatingPosition::Center => String::from("center"),
+                FloatingPosition::Fixed(x, y) => format!("fixed {x} {y}"),
+                FloatingPosition::Cursor => String::from("cursor"),
+            }),
+            open_as_tab: self.open_as_tab,
+            open_below_parent: self.open_below_parent,
+            open_replacing_focused: self.open_replacing_focused,
+            open_in_scratchpad: self.open_in_scratchpad,
+            open_focused: self.open_focused,
+            focus_on_open_across_workspace: self.focus_on_open_across_workspace,
+            default_column_position: self.default_column_position.map(|position| {
+                String::from(match position {
+                    ColumnPosition::AfterFocused => "after-focused",
+                    ColumnPosition::BeforeFocused => "before-focused",
+                    ColumnPosition::Start => "start",
+                    ColumnPosition::End => "end",
+                })
+            }),
+            max_columns: self.max_columns,
+            max_windows_per_column: self.max_windows_per_column,
+            mark: self.mark.map(String::from),
+            tiled_state: self.tiled_state,
+            prefer_csd: self.prefer_csd,
+            force_decoration: self.force_decoration.map(|mode| {
+                String::from(match mode {
+                    DecorationMode::ServerSide => "server-side",
+                    DecorationMode::ClientSide => "client-side",
+                })
+            }),
+            activation_focus: self.activation_focus.map(|focus| {
+                String::from(match focus {
+                    ActivationFocus::Always => "always",
+                    ActivationFocus::Never => "never",
+                    ActivationFocus::SameApp => "same-app",
+                })
+            }),
+            maximize_to: self.maximize_to.map(|target| {
+                String::from(match target {
+                    MaximizeTarget::Column => "column",
+                    MaximizeTarget::Monitor => "monitor",
+                })
+            }),
+            color_scheme: self.color_scheme.map(|scheme| {
+                String::from(match scheme {
+                    ColorScheme::Light => "light",
+                    ColorScheme::Dark => "dark",
+                })
+            }),
+            block_out_from: self.block_out_from.map(|mode| {
+                String::from(match mode {
+                    BlockOutMode::Screencast => "screencast",
+                    BlockOutMode::ScreenCapture => "screen-capture",
+                })
+            }),
+            opacity: self.opacity,
+            border_active_color: self.border_active_color.map(color),
+            border_inactive_color: self.border_inactive_color.map(color),
+            min_width: self.min_width,
+            max_width: self.max_width,
+            scale_override: self.scale_override,
+            always_on_top: self.always_on_top,
+            focus_follows_mouse: self.focus_follows_mouse,
+            popup_pointer_grab: self.popup_pointer_grab,
+            aspect_ratio: self.aspect_ratio,
+        }
+    }
+}
+
+/// Title that a toplevel had when it was mapped.
+///
+/// Stored in the toplevel surface data map so that window rules can match against it after the
+/// title changes.
+#[derive(Debug, Default)]
+struct InitialTitle(RefCell<Option<String>>);
+
+/// Remembers the current title of the toplevel as its initial title.
+///
+/// Should be called when the toplevel is mapped.
+pub fn store_initial_title(toplevel: &ToplevelSurface) {
+    with_states(toplevel.wl_surface(), |states| {
+        let title = states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .title
+            .clone();
+
+        states.data_map.insert_if_missing(InitialTitle::default);
+        let initial_title = states.data_map.get::<InitialTitle>().unwrap();
+        *initial_title.0.borrow_mut() = title;
+    });
+}
+
+/// Name of the output that a toplevel is currently on.
+///
+/// Stored in the toplevel surface data map so that window rules can match against it.
+#[derive(Debug, Default)]
+struct CurrentOutput(RefCell<Option<String>>);
+
+/// Remembers the output that the toplevel is currently on.
+///
+/// Returns `true` if the output changed.
+pub fn store_current_output(toplevel: &ToplevelSurface, output: Option<&Output>) -> bool {
+    with_states(toplevel.wl_surface(), |states| {
+        states.data_map.insert_if_missing(CurrentOutput::default);
+        let current = states.data_map.get::<CurrentOutput>().unwrap();
+        let name = output.map(Output::name);
+        if *current.0.borrow() == name {
+            return false;
+        }
+
+        *current.0.borrow_mut() = name;
+        true
+    })
+}
+
+pub fn window_matches(subject: &WindowRuleSubject, m: &Match) -> bool {
+    if !m.app_id.is_empty() {
+        let Some(app_id) = &subject.app_id else {
+            return false;
+        };
+        if !m.app_id.iter().any(|re| re.is_match(app_id)) {
+            return false;
+        }
+    }
+
+    if !m.title.is_empty() {
+        let Some(title) = &subject.title else {
+            return false;
+        };
+        if !m.title.iter().any(|re| re.is_match(title)) {
+            return false;
+        }
+    }
+
+    if let Some(app_id) = &subject.app_id {
+        if m.app_id_not.iter().any(|re| re.is_match(app_id)) {
+            return false;
+        }
+    }
+
+    if let Some(title) = &subject.title {
+        if m.title_not.iter().any(|re| re.is_match(title)) {
+            return false;
+        }
+    }
+
+    if let Some(initial_title_re) = &m.initial_title {
+        let Some(initial_title) = &subject.initial_title else {
+            return false;
+        };
+        if !initial_title_re.is_match(initial_title) {
+            return false;
+        }
+    }
+
+    if let Some(pid_to_match) = m.pid {
+        if subject.pid != Some(pid_to_match) {
+            return false;
+        }
+    }
+
+    if let Some(output_to_match) = &m.on_output {
+        if subject.output.as_ref() != Some(output_to_match) {
+            return false;
+        }
+    }
+
+    if let Some(has_parent) = m.has_parent {
+        if subject.has_parent != has_parent {
+            return false;
+        }
+    }
+
+    if let Some(fixed_size_to_match) = m.is_fixed_size {
+        if subject.is_fixed_size != fixed_size_to_match {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Returns the current title and app ID of the toplevel.
+pub fn title_and_app_id(toplevel: &ToplevelSurface) -> (Option<String>, Option<String>) {
+    with_states(toplevel.wl_surface(), |states| {
+        let role = states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap();
+        (role.title.clone(), role.app_id.clone())
+    })
+}
+
+/// Properties of a window that window rules match against.
+///
+/// This is a snapshot of the surface state, so that rules can be matched without locking it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WindowRuleSubject {
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    /// Title that the window had when it was mapped.
+    pub initial_title: Option<String>,
+    /// PID of the connected client.
+    pub pid: Option<i32>,
+    /// Name of the output that the window is on.
+    pub output: Option<String>,
+    pub has_parent: bool,
+    pub is_fixed_size: bool,
+}
+
+impl WindowRuleSubject {
+    /// Takes a snapshot of the properties of the toplevel.
+    pub fn from_toplevel(toplevel: &ToplevelSurface, display_handle: &DisplayHandle) -> Self {
+        // This is the PID of the connected client, which is not necessarily the process that
+        // "owns" the window.
+        let pid = toplevel
+            .wl_surface()
+            .client()
+            .and_then(|client| client.get_credentials(display_handle).ok())
+            .map(|creds| creds.pid);
+
+        with_states(toplevel.wl_surface(), |states| {
+            let role = states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap();
+
+            // Before the window is mapped, its current title is the initial one.
+            let initial_title = states
+                .data_map
+                .get::<InitialTitle>()
+                .and_then(|x| x.0.borrow().clone())
+                .or_else(|| role.title.clone());
+
+            // Unmapped windows aren't on any output yet.
+            let output = states
+                .data_map
+                .get::<CurrentOutput>()
+                .and_then(|x| x.0.borrow().clone());
+
+            // Zero means no limit, so only windows with both limits set can be fixed-size.
+            let is_fixed_size = {
+                let sizes = states.cached_state.current::<SurfaceCachedState>();
+                let (min, max) = (sizes.min_size, sizes.max_size);
+                min == max && min.w > 0 && min.h > 0
+            };
+
+            Self {
+                title: role.title.clone(),
+                app_id: role.app_id.clone(),
+                initial_title,
+                pid,
+                output,
+                has_parent: role.parent.is_some(),
+                is_fixed_size,
+            }
+        })
+    }
+}
+
+/// Returns the indices of the window rules matching the toplevel, in the order they apply.
+pub fn matching_window_rules(
+    config: &Config,
+    toplevel: &ToplevelSurface,
+    display_handle: &DisplayHandle,
+) -> Vec<usize> {
+    let subject = WindowRuleSubject::from_toplevel(toplevel, display_handle);
+    matching_window_rules_for(config, &subject)
+}
+
+/// Returns the indices of the window rules matching the window properties, in the order they
+/// apply.
+///
+/// Rules apply from the lowest to the highest priority, so that higher-priority rules override
+/// lower-priority ones. Rules without a priority have priority 0, and rules of equal priority
+/// apply in config order.
+pub fn matching_window_rules_for(config: &Config, subject: &WindowRuleSubject) -> Vec<usize> {
+    let mut matched = Vec::new();
+    for (idx, rule) in config.window_rules.iter().enumerate() {
+        // A rule referring to a missing column width preset is ignored as a whole. The config
+        // loading warns about it.
+        if let Some(name) = &rule.default_column_width_preset {
+            if named_column_width(config, name).is_none() {
+                continue;
+            }
+        }
+
+        if !(rule.matches.is_empty() || rule.matches.iter().any(|m| window_matches(subject, m))) {
+            continue;
+        }
+
+        if rule.excludes.iter().any(|m| window_matches(subject, m)) {
+            continue;
+        }
+
+        matched.push(idx);
+    }
+
+    // The sort is stable, so equal priorities keep the config order.
+    matched.sort_by_key(|&idx| config.window_rules[idx].priority.unwrap_or(0));
+    matched
+}
+
+/// Returns the named column width with this name from the layout config.
+pub fn named_column_width<'a>(config: &'a Config, name: &str) -> Option<&'a NamedWidth> {
+    config
+        .layout
+        .named_column_widths
+        .iter()
+        .find(|preset| preset.name == name)
+}
+
+pub fn resolve_window_rules<'a>(
+    config: &'a Config,
+    toplevel: &ToplevelSurface,
+    display_handle: &DisplayHandle,
+) -> ResolvedWindowRule<'a> {
+    let subject = WindowRuleSubject::from_toplevel(toplevel, display_handle);
+    resolve_window_rules_for(config, &subject)
+}
+
+/// Resolves the window rules against a snapshot of the window properties.
+pub fn resolve_window_rules_for<'a>(
+    config: &'a Config,
+    subject: &WindowRuleSubject,
+) -> ResolvedWindowRule<'a> {
+    let span = tracy_client::span!("resolve_window_rules");
+
+    let matched = matching_window_rules_for(config, subject);
+    let mut resolved = ResolvedWindowRule::default();
+
+    for rule in matched.iter().map(|&idx| &config.window_rules[idx]) {
+        if let Some(x) = rule
+            .default_column_width
+            .as_ref()
+            .map(|d| d.0.first().copied().map(ColumnWidth::from))
+        {
+            resolved.default_width = Some(x);
+        }
+
+        for x in &rule.default_column_width_on_output {
+            let width = x.width.first().copied().map(ColumnWidth::from);
+            resolved.default_width_on_output.push((&x.output, width));
+        }
+
+        if let Some(x) = rule.max_auto_width {
+            resolved.max_auto_width = Some(i32::from(x));
+        }
+
+        // Rules with a missing preset don't match, so the preset exists here.
+        if let Some(preset) = rule
+            .default_column_width_preset
+            .as_deref()
+            .and_then(|name| named_column_width(config, name))
+        {
+            resolved.default_width = Some(preset.width.first().copied().map(ColumnWidth::from));
+        }
+
+        if let Some(x) = rule
+            .default_window_height
+            .as_ref()
+            .map(|d| d.0.first().copied().map(WindowHeight::from))
+        {
+            resolved.default_height = Some(x);
+        }
+
+        if let Some(x) = rule.open_on_output.as_deref() {
+            resolved.open_on_output = Some(x);
+        }
+
+        if let Some(x) = rule.open_on_workspace.as_deref() {
+            resolved.open_on_workspace = Some(x);
+        }
+
+        if let Some(x) = rule.reapply_open_on_output {
+            resolved.reapply_open_on_output = Some(x);
+        }
+
+        if let Some(x) = rule.open_maximized {
+            resolved.open_maximized = Some(x);
+        }
+
+        if let Some(x) = rule.open_fullscreen {
+            resolved.open_fullscreen = Some(x);
+        }
+
+        if let Some(x) = rule.open_floating {
+            resolved.open_floating = Some(x);
+        }
+
+        if let Some(x) = rule
+            .default_floating_position
+            .as_ref()
+            .and_then(|d| d.0.first().copied())
+        {
+            resolved.default_floating_position = Some(x);
+        }
+
+        if let Some(x) = rule.open_as_tab {
+            resolved.open_as_tab = Some(x);
+        }
+
+        if let Some(x) = rule.open_below_parent {
+            resolved.open_below_parent = Some(x);
+        }
+
+        if let Some(x) = rule.open_replacing_focused {
+            resolved.open_replacing_focused = Some(x);
+        }
+
+        if let Some(x) = rule.open_in_scratchpad {
+            resolved.open_in_scratchpad = Some(x);
+        }
+
+        if let Some(x) = rule.open_focused {
+            resolved.open_focused = Some(x);
+        }
+
+        if let Some(x) = rule.focus_on_open_across_workspace {
+            resolved.focus_on_open_across_workspace = Some(x);
+        }
+
+        if let Some(x) = rule.default_column_position {
+            resolved.default_column_position = Some(x);
+        }
+
+        if let Some(x) = rule.max_columns {
+            resolved.max_columns = Some(x);
+        }
+
+        if let Some(x) = rule.max_windows_per_column {
+            resolved.max_windows_per_column = Some(x);
+        }
+
+        if let Some(x) = rule.mark.as_deref() {
+            resolved.mark = Some(x);
+        }
+
+        if let Some(x) = rule.tiled_state {
+            resolved.tiled_state = Some(x);
+        }
+
+        if let Some(x) = rule.prefer_csd {
+            resolved.prefer_csd = Some(x);
+        }
+
+        if let Some(x) = rule.force_decoration {
+            resolved.force_decoration = Some(x);
+        }
+
+        if let Some(x) = rule.block_out_from {
+            resolved.block_out_from = Some(x);
+        }
+
+        if let Some(x) = rule.opacity {
+            resolved.opacity = Some(x.clamp(0., 1.));
+        }
+
+        if let Some(x) = rule.border_active_color {
+            resolved.border_active_color = Some(x);
+        }
+
+        if let Some(x) = rule.border_inactive_color {
+            resolved.border_inactive_color = Some(x);
+        }
+
+        if let Some(x) = rule.min_width {
+            resolved.min_width = Some(i32::from(x));
+        }
+
+        if let Some(x) = rule.max_width {
+            resolved.max_width = Some(i32::from(x));
+        }
+
+        if let Some(x) = rule.scale_override {
+            resolved.scale_override = Some(x);
+        }
+
+        if let Some(x) = rule.always_on_top {
+            resolved.always_on_top = Some(x);
+        }
+
+        if let Some(x) = rule.focus_follows_mouse {
+            resolved.focus_follows_mouse = Some(x);
+        }
+
+        if let Some(x) = rule.popup_pointer_grab {
+            resolved.popup_pointer_grab = Some(x);
+        }
+
+        if let Some(x) = rule.aspect_ratio {
+            resolved.aspect_ratio = Some(x.clamp(0.01, 100.));
+        }
+
+        if let Some(x) = rule.activation_focus {
+            resolved.activation_focus = Some(x);
+        }
+
+        if let Some(x) = rule.maximize_to {
+            resolved.maximize_to = Some(x);
+        }
+
+        if let Some(x) = rule.color_scheme {
+            resolved.color_scheme = Some(x);
+        }
+    }
+
+    if config.debug.log_window_rules {
+        let WindowRuleSubject { title, app_id, .. } = subject;
+
+        let text = format!("matched window rules {matched:?}, resolved {resolved:?}");
+        span.emit_text(&text);
+        debug!(?title, ?app_id, "{text}");
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_rules_match_subject() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            window-rule {
+                match app-id="^firefox$"
+            }
+
+            window-rule {
+                match app-id="^firefox$" has-parent=true
+                exclude on-output="HDMI-A-1"
+            }
+
+            window-rule {
+                match initial-title="^Loading$"
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut subject = WindowRuleSubject {
+            title: Some("Mozilla Firefox".to_owned()),
+            app_id: Some("firefox".to_owned()),
+            initial_title: Some("Loading".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(matching_window_rules_for(&config, &subject), [0, 2]);
+
+        subject.has_parent = true;
+        assert_eq!(matching_window_rules_for(&config, &subject), [0, 1, 2]);
+
+        subject.output = Some("HDMI-A-1".to_owned());
+        assert_eq!(matching_window_rules_for(&config, &subject), [0, 2]);
+
+        subject.app_id = None;
+        subject.initial_title = None;
+        assert!(matching_window_rules_for(&config, &subject).is_empty());
+    }
+
+    fn subject(app_id: &str, title: &str) -> WindowRuleSubject {
+        WindowRuleSubject {
+            title: Some(title.to_owned()),
+            app_id: Some(app_id.to_owned()),
+            initial_title: Some(title.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolve_rule_without_matches_applies_to_all() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            window-rule {
+                opacity 0.5
+            }
+
+            window-rule {
+                match app-id="^foot$"
+                open-floating true
+            }
+            "#,
+        )
+        .unwrap();
+
+        let resolved = resolve_window_rules_for(&config, &subject("foot", "~"));
+        assert_eq!(resolved.opacity, Some(0.5));
+        assert_eq!(resolved.open_floating, Some(true));
+
+        let resolved = resolve_window_rules_for(&config, &subject("firefox", "Mozilla Firefox"));
+        assert_eq!(resolved.opacity, Some(0.5));
+        assert_eq!(resolved.open_floating, None);
+
+        let resolved = resolve_window_rules_for(&config, &WindowRuleSubject::default());
+        assert_eq!(resolved.opacity, Some(0.5));
+    }
+
+    #[test]
+    fn resolve_rule_excludes() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            window-rule {
+                match app-id="^firefox$"
+                exclude title="Private Browsing$"
+                block-out-from "screencast"
+            }
+
+            window-rule {
+                exclude app-id="^firefox$"
+                open-maximized true
+            }
+            "#,
+        )
+        .unwrap();
+
+        let resolved = resolve_window_rules_for(&config, &subject("firefox", "Mozilla Firefox"));
+        assert_eq!(resolved.block_out_from, Some(BlockOutMode::Screencast));
+        assert_eq!(resolved.open_maximized, None);
+
+        let private = subject("firefox", "Mozilla Firefox Private Browsing");
+        let resolved = resolve_window_rules_for(&config, &private);
+        assert_eq!(resolved.block_out_from, None);
+        assert_eq!(resolved.open_maximized, None);
+
+        let resolved = resolve_window_rules_for(&config, &subject("foot", "~"));
+        assert_eq!(resolved.block_out_from, None);
+        assert_eq!(resolved.open_maximized, Some(true));
+    }
+
+    #[test]
+    fn resolve_later_rules_override_earlier() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            window-rule {
+                opacity 0.9
+                open-focused false
+            }
+
+            window-rule {
+                match app-id="^foot$"
+                opacity 0.8
+            }
+
+            window-rule {
+                match title="^scratch$"
+                opacity 1.0
+            }
+            "#,
+        )
+        .unwrap();
+
+        let resolved = resolve_window_rules_for(&config, &subject("foot", "~"));
+        assert_eq!(resolved.opacity, Some(0.8));
+        assert_eq!(resolved.open_focused, Some(false));
+
+        let resolved = resolve_window_rules_for(&config, &subject("foot", "scratch"));
+        assert_eq!(resolved.opacity, Some(1.));
+        assert_eq!(resolved.open_focused, Some(false));
+
+        let resolved = resolve_window_rules_for(&config, &subject("firefox", "scratch"));
+        assert_eq!(resolved.opacity, Some(1.));
+    }
+
+    #[test]
+    fn resolve_ignores_rule_with_missing_preset() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            layout {
+                named-column-widths {
+                    narrow { proportion 0.25; }
+                }
+            }
+
+            window-rule {
+                default-column-width-preset "narrow"
+            }
+
+            window-rule {
+                match app-id="^foot$"
+                default-column-width-preset "wide"
+                open-floating true
+            }
+            "#,
+        )
+        .unwrap();
+
+        let foot = subject("foot", "~");
+        assert_eq!(matching_window_rules_for(&config, &foot), [0]);
+
+        let resolved = resolve_window_rules_for(&config, &foot);
+        assert_eq!(
+            resolved.default_width,
+            Some(Some(ColumnWidth::Proportion(0.25)))
+        );
+        assert_eq!(resolved.open_floating, None);
+    }
+
+    #[test]
+    fn resolve_rules_by_priority() {
+        let config = Config::parse(
+            "test.kdl",
+            r#"
+            window-rule {
+                match app-id="^foot$"
+                priority 1
+                opacity 0.8
+            }
+
+            window-rule {
+                opacity 0.9
+            }
+
+            window-rule {
+                priority -1
+                opacity 0.7
+                open-focused false
+            }
+
+            window-rule {
+                match title="^scratch$"
+                priority 1
+                opacity 1.0
+            }
+            "#,
+        )
+        .unwrap();
+
+        // Equal priorities keep the config order.
+        let scratch = subject("foot", "scratch");
+        assert_eq!(matching_window_rules_for(&config, &scratch), [2, 1, 0, 3]);
+        let resolved = resolve_window_rules_for(&config, &scratch);
+        assert_eq!(resolved.opacity, Some(1.));
+        assert_eq!(resolved.open_focused, Some(false));
+
+        // The specific rule wins over the later, general one.
+        let resolved = resolve_window_rules_for(&config, &subject("foot", "~"));
+        assert_eq!(resolved.opacity, Some(0.8));
+
+        // The lowest priority rule applies first.
+        let resolved = resolve_window_rules_for(&config, &subject("firefox", "~"));
+        assert_eq!(resolved.opacity, Some(0.9));
+    }
+}